@@ -0,0 +1,121 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * To the extent possible under law, the person who associated CC0 with this file has waived all
+ * copyright and related or neighboring rights to this file.
+ *
+ * You should have received a copy of the CC0 legalcode along with this work. If not, see
+ * <http://creativecommons.org/publicdomain/zero/1.0/>.
+ *
+ * SPDX-License-Identifier: CC0-1.0
+ */
+
+//! Measures decoding and encoding of a full-frame (1920x1080) object. Reading a display set
+//! pre-allocates each line according to the declared object width, while decompressing the
+//! same data on its own cannot. Run with `cargo bench -p pgs --bench rle`.
+
+use pgs::{
+    displayset::{
+        Cid,
+        CompositionObject,
+        DisplaySet,
+        Object,
+        ReadDisplaySetExt,
+        Vid,
+        Window,
+        WriteDisplaySetExt,
+    },
+    rle,
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    io::Cursor,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+const WIDTH: u16 = 1920;
+const HEIGHT: u16 = 1080;
+const ITERATIONS: u32 = 20;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn measure(name: &str, mut f: impl FnMut()) {
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+
+    for _ in 0..ITERATIONS {
+        f();
+    }
+
+    let elapsed: Duration = start.elapsed() / ITERATIONS;
+    let allocations = (ALLOCATIONS.load(Ordering::Relaxed) - allocations) / ITERATIONS as usize;
+
+    println!("{name:<32} {elapsed:>12.2?} {allocations:>10} allocations");
+}
+
+fn main() {
+
+    // A dither of two indexes defeats run-length encoding, which is the worst case for both
+    // decoding and encoding.
+    let lines = (0..HEIGHT as usize)
+        .map(|y| (0..WIDTH as usize).map(|x| ((x + y) % 2) as u8 + 1).collect())
+        .collect::<Vec<Vec<u8>>>();
+    let data = rle::compress(&lines).unwrap();
+    let mut display_set = DisplaySet {
+        width: WIDTH,
+        height: HEIGHT,
+        ..Default::default()
+    };
+
+    display_set.windows.insert(0, Window { x: 0, y: 0, width: WIDTH, height: HEIGHT });
+    display_set.objects.insert(
+        Vid {
+            id: 0,
+            version: 0,
+        },
+        Object {
+            width: WIDTH,
+            height: HEIGHT,
+            lines: lines.clone(),
+        },
+    );
+    display_set.composition.objects.insert(Cid::default(), CompositionObject::default());
+
+    let mut buffer = vec![];
+
+    buffer.write_display_set(display_set).unwrap();
+
+    measure("read_display_set (width hint)", || {
+        Cursor::new(&buffer).read_display_set().unwrap();
+    });
+    measure("rle::decompress (no hint)", || {
+        rle::decompress(&data).unwrap();
+    });
+    measure("rle::compress", || {
+        rle::compress(&lines).unwrap();
+    });
+}
@@ -0,0 +1,266 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a
+ * copy of the MPL was not distributed with this file, You can obtain one at
+ * https://mozilla.org/MPL/2.0/.
+ *
+ * SPDX-License-Identifier: MPL-2.0
+ */
+
+//! Authors display sets from a cue list, such as one exported from a spotting tool, where each
+//! cue pairs a time span with a PNG image of its caption.
+
+#[cfg(test)]
+mod tests;
+
+use super::{
+    displayset::{
+        Cid,
+        Composition,
+        CompositionNumberCounter,
+        CompositionObject,
+        DisplaySet,
+        Object,
+        Palette,
+        PaletteEntry,
+        Vid,
+        Window,
+    },
+    rgb::{ycbcr_pixel, ColorMatrix, RgbPixel},
+    segment::CompositionState,
+};
+use std::{collections::BTreeMap, path::PathBuf};
+use thiserror::Error as ThisError;
+
+/// The error type for [build_from_cues].
+#[derive(ThisError, Debug)]
+pub enum BuildError {
+    /// The image of a cue could not be opened or decoded.
+    #[error("cue {index} image could not be imported")]
+    ImageError {
+        /// The position of the cue within the list.
+        index: usize,
+        /// The underlying image error.
+        source: image::ImageError,
+    },
+    /// A cue does not end after it starts, or it starts before the previous cue ends.
+    #[error("cue {index} has invalid timing")]
+    InvalidTiming {
+        /// The position of the cue within the list.
+        index: usize,
+    },
+    /// The image of a cue has more distinct colors than a palette can hold.
+    #[error("cue {index} image has more than 255 visible colors")]
+    TooManyColors {
+        /// The position of the cue within the list.
+        index: usize,
+    },
+    /// The image of a cue does not fit on the screen at the position it is placed.
+    #[error("cue {index} image does not fit on the screen")]
+    OutOfBounds {
+        /// The position of the cue within the list.
+        index: usize,
+    },
+}
+
+/// Where on the screen the image of each cue is placed.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Position {
+    /// Centered horizontally, with the bottom of the image `margin` pixels above the bottom of
+    /// the screen. This is where dialogue is typically placed.
+    BottomCenter {
+        /// The distance between the image and the bottom of the screen in pixels.
+        margin: u16,
+    },
+    /// Centered horizontally, with the top of the image `margin` pixels below the top of the
+    /// screen.
+    TopCenter {
+        /// The distance between the image and the top of the screen in pixels.
+        margin: u16,
+    },
+    /// The top-left corner of the image is placed at the specified offset from the top-left
+    /// corner of the screen.
+    Absolute {
+        /// The horizontal offset in pixels.
+        x: u16,
+        /// The vertical offset in pixels.
+        y: u16,
+    },
+}
+
+/// Builds display sets from a list of cues, each consisting of a start time, an end time, and
+/// the path of a PNG image to show between them. Cues must be listed in order and must not
+/// overlap.
+///
+/// Each cue becomes its own epoch. An [EpochStart](CompositionState::EpochStart) display set
+/// presented at the start time shows the image within a single window fitted to it, placed on
+/// a screen of the specified size according to `position`. A display set presented at the end
+/// time then empties that window. Fully transparent pixels share palette entry zero, while
+/// every other distinct color of the image receives its own entry, so an image may have no
+/// more than 255 of them. Composition numbers are assigned starting from zero, and the DTS of
+/// each display set is left at zero.
+pub fn build_from_cues(
+    cues: &[(u32, u32, PathBuf)],
+    screen: (u16, u16),
+    position: Position,
+) -> Result<Vec<DisplaySet>, BuildError> {
+
+    let mut counter = CompositionNumberCounter::default();
+    let mut display_sets = Vec::<DisplaySet>::with_capacity(cues.len() * 2);
+    let mut previous_end = 0;
+
+    for (index, (start, end, path)) in cues.iter().enumerate() {
+
+        if start >= end || *start < previous_end {
+            return Err(BuildError::InvalidTiming { index })
+        }
+
+        let image = image::open(path)
+            .map_err(|source| BuildError::ImageError { index, source })?
+            .into_rgba8();
+        let (width, height) = match (u16::try_from(image.width()), u16::try_from(image.height())) {
+            (Ok(width), Ok(height)) if width <= screen.0 && height <= screen.1 => {
+                (width, height)
+            }
+            _ => {
+                return Err(BuildError::OutOfBounds { index })
+            }
+        };
+        let (x, y) = match position {
+            Position::BottomCenter { margin } => {
+                ((screen.0 - width) / 2, (screen.1 - height).saturating_sub(margin))
+            }
+            Position::TopCenter { margin } => {
+                ((screen.0 - width) / 2, margin)
+            }
+            Position::Absolute { x, y } => {
+                (x, y)
+            }
+        };
+
+        if x > screen.0 - width || y > screen.1 - height {
+            return Err(BuildError::OutOfBounds { index })
+        }
+
+        let mut colors = BTreeMap::<[u8; 4], u8>::new();
+        let mut entries = BTreeMap::<u8, PaletteEntry>::new();
+        let mut lines = Vec::<Vec<u8>>::with_capacity(height as usize);
+
+        entries.insert(
+            0,
+            PaletteEntry {
+                y: 16,
+                cr: 128,
+                cb: 128,
+                alpha: 0,
+            },
+        );
+
+        for row in image.rows() {
+
+            let mut line = Vec::<u8>::with_capacity(width as usize);
+
+            for pixel in row {
+
+                let [red, green, blue, alpha] = pixel.0;
+
+                if alpha == 0 {
+                    line.push(0);
+                    continue
+                }
+
+                let next = colors.len() + 1;
+                let entry_id = match colors.get(&pixel.0) {
+                    Some(&entry_id) => {
+                        entry_id
+                    }
+                    None if next <= u8::MAX as usize => {
+                        let ycbcr = ycbcr_pixel(
+                            RgbPixel {
+                                red: red as f64 / 255.0,
+                                green: green as f64 / 255.0,
+                                blue: blue as f64 / 255.0,
+                            },
+                            ColorMatrix::Bt709,
+                        );
+                        entries.insert(
+                            next as u8,
+                            PaletteEntry {
+                                y: ycbcr.y,
+                                cr: ycbcr.cr,
+                                cb: ycbcr.cb,
+                                alpha,
+                            },
+                        );
+                        colors.insert(pixel.0, next as u8);
+                        next as u8
+                    }
+                    None => {
+                        return Err(BuildError::TooManyColors { index })
+                    }
+                };
+
+                line.push(entry_id);
+            }
+
+            lines.push(line);
+        }
+
+        let window = Window {
+            x,
+            y,
+            width,
+            height,
+        };
+        let cid = Cid {
+            object_id: 0,
+            window_id: 0,
+        };
+        let mut show = DisplaySet {
+            pts: *start,
+            width: screen.0,
+            height: screen.1,
+            frame_rate: 0x10,
+            composition: Composition {
+                number: counter.next_number(),
+                state: CompositionState::EpochStart,
+                order: vec![cid.clone()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        show.windows.insert(0, window.clone());
+        show.palettes.insert(Vid::default(), Palette { entries });
+        show.objects.insert(
+            Vid::default(),
+            Object {
+                width,
+                height,
+                lines,
+            },
+        );
+        show.composition.objects.insert(cid, CompositionObject { x, y, ..Default::default() });
+
+        let mut clear = DisplaySet {
+            pts: *end,
+            width: screen.0,
+            height: screen.1,
+            frame_rate: 0x10,
+            composition: Composition {
+                number: counter.next_number(),
+                state: CompositionState::Normal,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        clear.windows.insert(0, window);
+        display_sets.push(show);
+        display_sets.push(clear);
+        previous_end = *end;
+    }
+
+    Ok(display_sets)
+}
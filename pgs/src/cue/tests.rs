@@ -0,0 +1,110 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * To the extent possible under law, the person who associated CC0 with this file has waived all
+ * copyright and related or neighboring rights to this file.
+ *
+ * You should have received a copy of the CC0 legalcode along with this work. If not, see
+ * <http://creativecommons.org/publicdomain/zero/1.0/>.
+ *
+ * SPDX-License-Identifier: CC0-1.0
+ */
+
+use super::{
+    *,
+    super::displayset::{ScreenClearKind, WriteDisplaySetExt},
+};
+use std::{env, fs, process};
+
+fn write_cue_image(name: &str, width: u32, height: u32, color: [u8; 4]) -> PathBuf {
+
+    let path = env::temp_dir().join(format!("pgs-cue-{}-{}.png", process::id(), name));
+    let image = image::RgbaImage::from_fn(width, height, |x, _| {
+        if x == 0 {
+            image::Rgba([0, 0, 0, 0])
+        } else {
+            image::Rgba(color)
+        }
+    });
+
+    image.save(&path).unwrap();
+
+    path
+}
+
+#[test]
+fn test_build_from_cues() {
+
+    let first = write_cue_image("first", 64, 16, [255, 255, 255, 255]);
+    let second = write_cue_image("second", 32, 8, [255, 255, 0, 128]);
+    let cues = vec![(90_000, 180_000, first.clone()), (270_000, 360_000, second.clone())];
+    let display_sets = build_from_cues(
+        &cues,
+        (1920, 1080),
+        Position::BottomCenter { margin: 40 },
+    ).unwrap();
+
+    assert_eq!(display_sets.len(), 4);
+    assert_eq!(
+        display_sets.iter().map(|ds| ds.pts).collect::<Vec<u32>>(),
+        vec![90_000, 180_000, 270_000, 360_000],
+    );
+    assert_eq!(
+        display_sets.iter().map(|ds| ds.composition.number).collect::<Vec<u16>>(),
+        vec![0, 1, 2, 3],
+    );
+
+    let show = &display_sets[0];
+    let cid = Cid { object_id: 0, window_id: 0 };
+
+    assert_eq!(show.composition.state, CompositionState::EpochStart);
+    assert_eq!((show.width, show.height), (1920, 1080));
+    assert_eq!(show.windows[&0], Window { x: 928, y: 1024, width: 64, height: 16 });
+    assert_eq!(show.composition.objects[&cid].x, 928);
+    assert_eq!(show.composition.objects[&cid].y, 1024);
+
+    let object = &show.objects[&Vid::default()];
+    let palette = &show.palettes[&Vid::default()];
+
+    assert_eq!((object.width, object.height), (64, 16));
+    assert!(object.lines.iter().all(|line| line[0] == 0 && line[1..].iter().all(|&i| i == 1)));
+    assert_eq!(palette.entries[&0].alpha, 0);
+    assert_eq!(palette.entries[&1], PaletteEntry { y: 235, cr: 128, cb: 128, alpha: 255 });
+
+    let clear = &display_sets[1];
+
+    assert_eq!(clear.composition.state, CompositionState::Normal);
+    assert_eq!(clear.screen_clear_kind(), Some(ScreenClearKind::CompositionEmpty));
+    assert_eq!(clear.windows, show.windows);
+
+    let show = &display_sets[2];
+
+    assert_eq!(show.composition.state, CompositionState::EpochStart);
+    assert_eq!(show.windows[&0], Window { x: 944, y: 1032, width: 32, height: 8 });
+    assert_eq!(show.palettes[&Vid::default()].entries[&1].alpha, 128);
+    assert_eq!(display_sets[3].screen_clear_kind(), Some(ScreenClearKind::CompositionEmpty));
+
+    let mut buffer = vec![];
+
+    for ds in display_sets {
+        buffer.write_display_set(ds).unwrap();
+    }
+
+    let overlapping = vec![(90_000, 180_000, first.clone()), (170_000, 360_000, second.clone())];
+
+    assert!(matches!(
+        build_from_cues(&overlapping, (1920, 1080), Position::TopCenter { margin: 40 }),
+        Err(BuildError::InvalidTiming { index: 1 }),
+    ));
+    assert!(matches!(
+        build_from_cues(&cues, (48, 1080), Position::TopCenter { margin: 40 }),
+        Err(BuildError::OutOfBounds { index: 0 }),
+    ));
+    assert!(matches!(
+        build_from_cues(&cues, (1920, 1080), Position::Absolute { x: 1900, y: 0 }),
+        Err(BuildError::OutOfBounds { index: 0 }),
+    ));
+
+    fs::remove_file(first).unwrap();
+    fs::remove_file(second).unwrap();
+}
@@ -35,6 +35,8 @@
 
 #[cfg(test)]
 mod tests;
+#[cfg(test)]
+mod objectlengthtests;
 
 mod displaysetread;
 mod displaysetwrite;
@@ -42,8 +44,109 @@ mod displaysetwrite;
 pub use displaysetread::*;
 pub use displaysetwrite::*;
 
-use std::collections::BTreeMap;
-use super::segment::{Crop, CompositionState};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    hash::{Hash, Hasher},
+    io::Cursor,
+    mem,
+};
+use super::{
+    limits::{MAX_COMPOSITION_OBJECTS, MIN_OBJECT_DIMENSION},
+    rgb::{rgb_pixel, ycbcr_pixel, ColorMatrix, RgbPixel, YcbcrPixel},
+    segment::{Crop, CompositionState, UnknownSegment},
+};
+use thiserror::Error as ThisError;
+
+/// The error type for [roundtrip].
+#[derive(ThisError, Debug)]
+pub enum RoundtripError {
+    /// The display set could not be written.
+    #[error("display set write error")]
+    WriteError {
+        /// The underlying display set write error.
+        #[from]
+        source: WriteError,
+    },
+    /// The display set could not be read back.
+    #[error("display set read error")]
+    ReadError {
+        /// The underlying display set read error.
+        #[from]
+        source: ReadError,
+    },
+}
+
+/// The error type for [DisplaySet::validate_crops].
+#[derive(ThisError, Debug, Eq, PartialEq)]
+pub enum ValidationError {
+    /// A composition object declares a crop region that extends beyond the bounds of the
+    /// object it references.
+    #[error("crop region exceeds the bounds of object {object_id}")]
+    CropExceedsObject {
+        /// The ID of the object.
+        object_id: u16,
+    },
+}
+
+/// The error type for [DisplaySet::tile_oversized_objects] and
+/// [Epoch::tile_oversized_objects](super::epoch::Epoch::tile_oversized_objects).
+#[derive(ThisError, Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TileError {
+    /// An object would be split into a tile smaller than [MIN_OBJECT_DIMENSION] in either
+    /// direction.
+    #[error("object {object_id} would be split into a {width}x{height} tile")]
+    TileTooSmall {
+        /// The ID of the object being split.
+        object_id: u16,
+        /// The width of the tile.
+        width: u16,
+        /// The height of the tile.
+        height: u16,
+    },
+    /// The DS would compose more than [MAX_COMPOSITION_OBJECTS] objects once tiled.
+    #[error("tiling would compose {count} objects")]
+    TooManyCompositionObjects {
+        /// The number of composition objects the DS would have.
+        count: usize,
+    },
+    /// Every object ID is already in use.
+    #[error("no object IDs remain")]
+    ObjectIdsExhausted,
+}
+
+/// The error type for [DisplaySet::auto_window].
+#[derive(ThisError, Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AutoWindowError {
+    /// Composition objects overlap such that the windows holding them could not be kept apart
+    /// without holding more than the requested number of objects each.
+    #[error("composition objects overlap too much to be split into windows")]
+    OverlappingObjects,
+    /// Every window ID is already in use.
+    #[error("no window IDs remain")]
+    WindowIdsExhausted,
+}
+
+/// The error type for [DisplaySet::recenter_horizontal], [DisplaySet::recenter_vertical], and
+/// their [Epoch](super::epoch::Epoch) counterparts.
+#[derive(ThisError, Debug, Eq, PartialEq)]
+pub enum RecenterError {
+    /// The content is larger than the screen along the axis being centered.
+    #[error("content size of {content_size} exceeds screen size of {screen_size}")]
+    ContentTooLarge {
+        /// The size of the content's bounding box along the axis being centered.
+        content_size: u32,
+        /// The size of the screen along the axis being centered.
+        screen_size: u16,
+    },
+}
+
+/// The error type for [Object::to_rgba_image].
+#[derive(ThisError, Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[error("palette has no entry {index}")]
+pub struct MissingPaletteEntryError {
+    /// The pixel value that the palette does not define.
+    pub index: u8,
+}
 
 /// Represents a complete display set (DS) within an epoch.
 #[derive(Clone, Debug, Default, Hash, PartialEq)]
@@ -74,18 +177,74 @@ pub struct DisplaySet {
     pub objects: BTreeMap<Vid<u16>, Object>,
     /// Defines the composition of objects into windows.
     pub composition: Composition,
+    /// Segments of unrecognized kinds that were read as part of this DS, which are only
+    /// captured when [preserve_unknown](super::segment::ReadOptions::preserve_unknown) is set.
+    /// They are written back out in the same order just before the END segment, taking on the
+    /// PTS and DTS of this DS.
+    pub unknown_segments: Vec<UnknownSegment>,
 }
 
 /// Represents a composition of objects into windows.
-#[derive(Clone, Debug, Default, Hash, PartialEq)]
+///
+/// Two compositions are equal when their numbers, states, and composition objects match and
+/// those objects are drawn in the same effective order. The raw [order](Self::order) list
+/// itself is not compared, so listing every object in compound ID order is the same as
+/// listing none of them.
+#[derive(Clone, Debug, Default)]
 pub struct Composition {
-    /// Starting at zero, this increments each time graphics are updated within an epoch.
+    /// Starting at zero, this increments with each DS across the entire presentation. It is
+    /// not reset when a new epoch begins. See [CompositionNumberCounter] for assigning these
+    /// while authoring.
     pub number: u16,
     /// Defines the role of this DS within the larger epoch.
     pub state: CompositionState,
     /// A collection of [CompositionObject]s, each mapped according to its compound ID (object
     /// ID + window ID).
     pub objects: BTreeMap<Cid, CompositionObject>,
+    /// The order in which composition objects are drawn, from back to front. PGS has no
+    /// explicit z-order, so overlapping objects are composited in the order they are listed
+    /// within the PCS. Any composition objects not listed here are drawn afterward in order of
+    /// their compound IDs, while any listed IDs without a corresponding composition object are
+    /// ignored.
+    pub order: Vec<Cid>,
+}
+
+impl Composition {
+
+    /// Returns the composition objects in the order they are to be drawn, as defined by
+    /// [order](Self::order).
+    pub fn ordered_objects(&self) -> Vec<(&Cid, &CompositionObject)> {
+
+        let mut positions = BTreeMap::<&Cid, usize>::new();
+
+        for (position, cid) in self.order.iter().enumerate() {
+            positions.entry(cid).or_insert(position);
+        }
+
+        let mut ordered = self.objects.iter().collect::<Vec<(&Cid, &CompositionObject)>>();
+
+        ordered.sort_by_key(|(cid, _)| positions.get(cid).copied().unwrap_or(usize::MAX));
+
+        ordered
+    }
+}
+
+impl PartialEq for Composition {
+
+    fn eq(&self, other: &Self) -> bool {
+        self.number == other.number
+            && self.state == other.state
+            && self.ordered_objects() == other.ordered_objects()
+    }
+}
+
+impl Hash for Composition {
+
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.number.hash(state);
+        self.state.hash(state);
+        self.ordered_objects().hash(state);
+    }
 }
 
 /// Defines a compound ID, combining an object and window identifier.
@@ -168,6 +327,21 @@ pub struct Object {
     pub lines: Vec<Vec<u8>>,
 }
 
+/// Describes the runs of identical pixels within an object's lines, as they would be seen by
+/// the RLE encoder. Runs never span multiple lines.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RunStats {
+    /// The total number of runs.
+    pub run_count: usize,
+    /// The number of runs consisting of palette index zero. These are tracked separately
+    /// because the RLE scheme encodes them differently than other runs.
+    pub zero_run_count: usize,
+    /// The average length of a run in pixels, or zero if there are no runs.
+    pub average_run_length: f64,
+    /// The number of runs having each length, keyed by length.
+    pub length_distribution: BTreeMap<usize, usize>,
+}
+
 /// A versioned identifier.
 #[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Vid<T> {
@@ -176,3 +350,1300 @@ pub struct Vid<T> {
     /// The version.
     pub version: u8,
 }
+
+/// Defines the manner in which a display set (DS) clears the screen.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ScreenClearKind {
+    /// The DS composes no objects, but still defines the windows of the epoch. The windows
+    /// persist while their contents are removed. This is what is typically encountered on
+    /// commercial Blu-ray discs, where the final DS of an epoch redeclares its windows.
+    CompositionEmpty,
+    /// The DS composes no objects and defines no windows. This is commonly produced by
+    /// third-party authoring tools, which omit the window definition segment (WDS) from the
+    /// DS that tears down an epoch.
+    NoWindows,
+}
+
+impl DisplaySet {
+
+    /// Returns this DS with its [composition number](Composition::number) replaced.
+    pub fn with_composition_number(mut self, number: u16) -> Self {
+        self.composition.number = number;
+        self
+    }
+
+    /// Returns the palette that objects within this DS are rendered against. This is the
+    /// latest version of the palette whose ID matches [palette_id](Self::palette_id), and not
+    /// simply any palette that the DS happens to define. Returns `None` if this DS does not
+    /// define a matching palette.
+    pub fn active_palette(&self) -> Option<&Palette> {
+        latest(&self.palettes, self.palette_id)
+    }
+
+    /// Returns the IDs of windows that are referenced by this DS's composition objects, but
+    /// that this DS does not define. Such windows must have been inherited from an earlier DS
+    /// within the epoch. Each ID is returned once, in ascending order.
+    pub fn unresolved_windows(&self) -> Vec<u8> {
+
+        let mut window_ids = self.composition.objects.keys()
+            .map(|cid| cid.window_id)
+            .filter(|window_id| !self.windows.contains_key(window_id))
+            .collect::<Vec<u8>>();
+
+        window_ids.sort_unstable();
+        window_ids.dedup();
+
+        window_ids
+    }
+
+    /// Determines whether or not this DS is required to define windows, but defines none.
+    ///
+    /// A DS whose composition state is [EpochStart](CompositionState::EpochStart) or
+    /// [AcquisitionPoint](CompositionState::AcquisitionPoint) must (re)define every window of
+    /// the epoch, as a player that seeks to it will not have seen any earlier DS. Writing such a
+    /// DS without any windows omits the window definition segment (WDS) entirely, leaving the
+    /// stream unseekable at that point.
+    pub fn needs_windows(&self) -> bool {
+        self.composition.state != CompositionState::Normal && self.windows.is_empty()
+    }
+
+    /// Remaps the color of every palette entry within this DS. Each entry is converted to RGB,
+    /// with each channel normalized to the range of `0.0` to `1.0`, and passed through the
+    /// provided lookup function. The result is then converted back to
+    /// YC<sub>b</sub>C<sub>r</sub>. Alpha values are left untouched. This can be used to
+    /// tonemap the palettes of HDR subtitles to SDR, for example.
+    ///
+    /// Palette versions are not changed. As long as the same lookup function is applied to
+    /// every DS within an epoch, each version continues to identify a single set of entries.
+    ///
+    /// Conversions use the [BT.709](ColorMatrix::Bt709) matrix. See
+    /// [apply_color_lut_with_matrix](Self::apply_color_lut_with_matrix) to select another.
+    pub fn apply_color_lut(&mut self, lut: &dyn Fn(f64, f64, f64) -> (f64, f64, f64)) {
+        self.apply_color_lut_with_matrix(lut, ColorMatrix::Bt709)
+    }
+
+    /// Remaps the color of every palette entry within this DS as
+    /// [apply_color_lut](Self::apply_color_lut) does, converting between
+    /// YC<sub>b</sub>C<sub>r</sub> and RGB using the specified matrix.
+    pub fn apply_color_lut_with_matrix(
+        &mut self,
+        lut: &dyn Fn(f64, f64, f64) -> (f64, f64, f64),
+        matrix: ColorMatrix,
+    ) {
+        for palette in self.palettes.values_mut() {
+            for entry in palette.entries.values_mut() {
+                let rgb = rgb_pixel(YcbcrPixel { y: entry.y, cb: entry.cb, cr: entry.cr }, matrix);
+                let (red, green, blue) = lut(rgb.red, rgb.green, rgb.blue);
+                let ycbcr = ycbcr_pixel(RgbPixel { red, green, blue }, matrix);
+                entry.y = ycbcr.y;
+                entry.cb = ycbcr.cb;
+                entry.cr = ycbcr.cr;
+            }
+        }
+    }
+
+    /// Returns each pair of composition objects whose placed rectangles intersect on the screen,
+    /// accounting for both object size and cropping. Overlapping objects are blended in draw
+    /// order, which can produce surprising results. Pairs are listed in draw order, as are the
+    /// objects within each pair. Composition objects whose objects are not defined within this
+    /// DS are skipped, as their sizes are unknown.
+    pub fn overlapping_compositions(&self) -> Vec<(Cid, Cid)> {
+
+        let rectangles = self.composition.ordered_objects().into_iter()
+            .filter_map(|(cid, co)| {
+                latest(&self.objects, cid.object_id).map(|object| {
+                    let (_, _, width, height) = visible_region(co, object);
+                    let (x, y) = (co.x as u32, co.y as u32);
+                    (cid, x, y, x + width as u32, y + height as u32)
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut pairs = Vec::<(Cid, Cid)>::new();
+
+        for (index, (a, a_left, a_top, a_right, a_bottom)) in rectangles.iter().enumerate() {
+            for (b, b_left, b_top, b_right, b_bottom) in &rectangles[index + 1..] {
+                if a_left < b_right && b_left < a_right && a_top < b_bottom && b_top < a_bottom {
+                    pairs.push(((*a).clone(), (*b).clone()));
+                }
+            }
+        }
+
+        pairs
+    }
+
+    /// Determines how (if at all) this DS clears the screen.
+    ///
+    /// A DS that only updates palettes never clears the screen, even if it composes no objects.
+    pub fn screen_clear_kind(&self) -> Option<ScreenClearKind> {
+        if self.palete_update_only || !self.composition.objects.is_empty() {
+            None
+        } else if self.windows.is_empty() {
+            Some(ScreenClearKind::NoWindows)
+        } else {
+            Some(ScreenClearKind::CompositionEmpty)
+        }
+    }
+
+    /// Determines whether or not this DS clears the screen. Both kinds of
+    /// [ScreenClearKind] are treated as marking the end of a caption.
+    pub fn clears_screen(&self) -> bool {
+        self.screen_clear_kind().is_some()
+    }
+
+    /// Estimates the number of RLE operations a player must perform to decode every object
+    /// defined within this DS, for flagging display sets that may not decode in time on players
+    /// with a fixed decode budget. This counts the codes that the objects are compressed into
+    /// when written: one for each run, one for each pixel of a literal run of one or two
+    /// non-transparent pixels, and one to end each line. As the runs are derived directly from
+    /// the decoded lines, nothing needs to be compressed or decompressed to calculate this.
+    pub fn decode_complexity(&self) -> usize {
+        self.objects.values().flat_map(|object| &object.lines).map(|line| {
+            line.chunk_by(|a, b| a == b).map(|run| match (run[0], run.len()) {
+                (0, _) => 1,
+                (_, 2) => 2,
+                _ => 1,
+            }).sum::<usize>() + 1
+        }).sum()
+    }
+
+    /// Calculates an [adler32](Object::adler32) checksum of every object defined within this
+    /// DS. These can be stored alongside a stream as a sidecar and later checked with
+    /// [verify_object_checksums](Self::verify_object_checksums) to detect corruption, without
+    /// altering the stream itself.
+    pub fn object_checksums(&self) -> BTreeMap<Vid<u16>, u32> {
+        self.objects.iter().map(|(vid, object)| (vid.clone(), object.adler32())).collect()
+    }
+
+    /// Compares the objects defined within this DS against checksums previously calculated by
+    /// [object_checksums](Self::object_checksums), returning the ID and version of each object
+    /// that does not match. This includes objects that are missing from either side. An empty
+    /// result means every object is intact.
+    pub fn verify_object_checksums(&self, expected: &BTreeMap<Vid<u16>, u32>) -> Vec<Vid<u16>> {
+
+        let actual = self.object_checksums();
+
+        actual.keys().chain(expected.keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter(|vid| actual.get(vid) != expected.get(vid))
+            .cloned()
+            .collect()
+    }
+
+    /// Calculates, for each window, the fraction of its area that is covered by
+    /// non-transparent object pixels.
+    ///
+    /// Objects are resolved against the latest version of each object defined within this DS,
+    /// while colors are resolved against the latest version of this DS's palette within
+    /// `palettes`. Windows without any composited objects have a ratio of `0.0`. Only the pixels
+    /// that are covered are tracked, so memory use is bounded by the objects themselves rather
+    /// than by the dimensions a window claims to have.
+    pub fn window_fill_ratios(&self, palettes: &BTreeMap<Vid<u8>, Palette>) -> BTreeMap<u8, f64> {
+
+        let palette = latest(palettes, self.palette_id);
+
+        self.windows.iter().map(|(&window_id, window)| {
+
+            let area = window.width as u64 * window.height as u64;
+            let covered = self.opaque_window_pixels(window_id, window, palette)
+                .collect::<BTreeSet<(u32, u32)>>();
+            let ratio = if area == 0 {
+                0.0
+            } else {
+                covered.len() as f64 / area as f64
+            };
+
+            (window_id, ratio)
+        }).collect()
+    }
+
+    /// Moves every window and composition object toward the center of the screen so that
+    /// captions stay clear of the edges on displays that overscan. For example, a `percent` of
+    /// `90.0` corresponds to the action-safe area.
+    ///
+    /// Unlike margin enforcement, which only nudges items touching the edges, every position is
+    /// scaled by `percent` about the center of the screen. This is applied to the top-left
+    /// corner of each item, which keeps composition objects within their windows and allows
+    /// each DS of an epoch to be adjusted independently, even when it inherits its windows or
+    /// objects. As sizes are not scaled, an item reaching the far edge of the screen lands
+    /// within the safe area less the same proportion of its own size.
+    pub fn apply_safe_area(&mut self, percent: f64) {
+
+        let scale = (percent / 100.0).clamp(0.0, 1.0);
+        let map = |offset: u16, screen_size: u16| {
+            (offset as f64 * scale + screen_size as f64 * (1.0 - scale) / 2.0).round() as u16
+        };
+
+        for window in self.windows.values_mut() {
+            window.x = map(window.x, self.width);
+            window.y = map(window.y, self.height);
+        }
+
+        for co in self.composition.objects.values_mut() {
+            co.x = map(co.x, self.width);
+            co.y = map(co.y, self.height);
+        }
+    }
+
+    /// Shifts every window and composition object horizontally so that their combined bounding
+    /// box is centered on the screen. The bounding box covers each window defined by this DS
+    /// along with the visible area of each composition object whose object is defined by it.
+    /// Nothing is changed if neither are present, or if the content is wider than the screen,
+    /// in which case an error is returned. To shift every display set of an epoch alike, use
+    /// [Epoch::recenter_horizontal](super::epoch::Epoch::recenter_horizontal) instead.
+    pub fn recenter_horizontal(&mut self) -> Result<(), RecenterError> {
+        self.recenter(true)
+    }
+
+    /// Shifts every window and composition object vertically so that their combined bounding
+    /// box is centered on the screen, as [recenter_horizontal](Self::recenter_horizontal) does
+    /// horizontally. An error is returned if the content is taller than the screen.
+    pub fn recenter_vertical(&mut self) -> Result<(), RecenterError> {
+        self.recenter(false)
+    }
+
+    fn recenter(&mut self, horizontal: bool) -> Result<(), RecenterError> {
+
+        let screen_size = if horizontal { self.width } else { self.height };
+        let extent = content_extent(horizontal, &self.windows, &self.composition, &self.objects);
+
+        match extent {
+            Some(extent) => self.recenter_extent(horizontal, extent, screen_size),
+            None => Ok(()),
+        }
+    }
+
+    /// Shifts every window and composition object along one axis so that content spanning
+    /// `(start, end)` along it is centered within `screen_size`.
+    pub(crate) fn recenter_extent(
+        &mut self,
+        horizontal: bool,
+        (start, end): (u32, u32),
+        screen_size: u16,
+    ) -> Result<(), RecenterError> {
+
+        let content_size = end - start;
+
+        if content_size > screen_size as u32 {
+            return Err(RecenterError::ContentTooLarge { content_size, screen_size })
+        }
+
+        let delta = ((screen_size as u32 - content_size) / 2) as i64 - start as i64;
+        let shift = |offset: &mut u16| {
+            *offset = (*offset as i64 + delta).clamp(0, u16::MAX as i64) as u16;
+        };
+
+        for window in self.windows.values_mut() {
+            shift(if horizontal { &mut window.x } else { &mut window.y });
+        }
+
+        for co in self.composition.objects.values_mut() {
+            shift(if horizontal { &mut co.x } else { &mut co.y });
+        }
+
+        Ok(())
+    }
+
+    /// Resizes each window to the tight bounding box of its composited, non-transparent
+    /// content.
+    ///
+    /// Composition objects extending beyond a shrunken window are cropped (and repositioned) to
+    /// fit within it, while those left with no visible area are removed. As only transparent
+    /// pixels are discarded, the rendered output is unchanged. Windows that have no content are
+    /// either left untouched or, if `remove_empty` is set, removed along with any composition
+    /// objects referencing them. Windows that compose objects not defined within this DS are
+    /// always left untouched, as their content cannot be determined.
+    pub fn shrink_windows_to_content(
+        &mut self,
+        palettes: &BTreeMap<Vid<u8>, Palette>,
+        remove_empty: bool,
+    ) {
+
+        let palette = latest(palettes, self.palette_id);
+        let window_ids = self.windows.keys().copied().collect::<Vec<u8>>();
+
+        for window_id in window_ids {
+
+            let window = &self.windows[&window_id];
+            let unresolved = self.composition.objects.keys().any(|cid|
+                cid.window_id == window_id && latest(&self.objects, cid.object_id).is_none()
+            );
+
+            if unresolved {
+                continue
+            }
+
+            let bounds = self.opaque_window_pixels(window_id, window, palette).fold(
+                None,
+                |bounds: Option<(u32, u32, u32, u32)>, (x, y)| match bounds {
+                    Some((left, top, right, bottom)) => {
+                        Some((left.min(x), top.min(y), right.max(x + 1), bottom.max(y + 1)))
+                    }
+                    None => {
+                        Some((x, y, x + 1, y + 1))
+                    }
+                },
+            );
+
+            match bounds {
+                Some((left, top, right, bottom)) => {
+                    let objects = &self.objects;
+                    self.windows.insert(
+                        window_id,
+                        Window {
+                            x: left as u16,
+                            y: top as u16,
+                            width: (right - left) as u16,
+                            height: (bottom - top) as u16,
+                        },
+                    );
+                    self.composition.objects.retain(|cid, co| {
+                        if cid.window_id != window_id {
+                            return true
+                        }
+                        match latest(objects, cid.object_id) {
+                            Some(object) => crop_to_region(co, object, left, top, right, bottom),
+                            None => true,
+                        }
+                    });
+                }
+                None => {
+                    if remove_empty {
+                        self.windows.remove(&window_id);
+                        self.composition.objects.retain(|cid, _| cid.window_id != window_id);
+                    }
+                }
+            }
+        }
+
+        let composition_objects = &self.composition.objects;
+
+        self.composition.order.retain(|cid| composition_objects.contains_key(cid));
+    }
+
+    /// Verifies that the crop region of each composition object lies within the bounds of the
+    /// object it references, as a player may otherwise render garbage. Composition objects that
+    /// reference objects not defined within this DS are skipped, as their dimensions are
+    /// unknown.
+    pub fn validate_crops(&self) -> Result<(), ValidationError> {
+
+        for (cid, co) in &self.composition.objects {
+            if let (Some(crop), Some(object)) = (&co.crop, latest(&self.objects, cid.object_id)) {
+                if crop.x as u32 + crop.width as u32 > object.width as u32
+                    || crop.y as u32 + crop.height as u32 > object.height as u32 {
+                    return Err(ValidationError::CropExceedsObject { object_id: cid.object_id })
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Shrinks the crop region of each composition object to lie within the bounds of the
+    /// object it references, rescuing malformed streams that
+    /// [validate_crops](Self::validate_crops) would reject before they reach a renderer. Offsets
+    /// beyond the object are moved to its far edge, and widths and heights are reduced so that
+    /// the region ends at or before the object's edges. Composition objects that reference
+    /// objects not defined within this DS are left untouched.
+    pub fn clamp_crops(&mut self) {
+        for (cid, co) in self.composition.objects.iter_mut() {
+            let object = latest(&self.objects, cid.object_id);
+            if let (Some(crop), Some(object)) = (&mut co.crop, object) {
+                crop.x = crop.x.min(object.width);
+                crop.y = crop.y.min(object.height);
+                crop.width = crop.width.min(object.width - crop.x);
+                crop.height = crop.height.min(object.height - crop.y);
+            }
+        }
+    }
+
+    /// Removes the crop region from every composition object, so that each one shows its
+    /// entire object.
+    ///
+    /// This is a naive strip that will change the appearance of any cropped composition object,
+    /// as areas of the object that were previously hidden will become visible. To preserve the
+    /// appearance, call [bake_crops](super::epoch::Epoch::bake_crops) on the epoch first.
+    pub fn remove_crops(&mut self) {
+        for co in self.composition.objects.values_mut() {
+            co.crop = None;
+        }
+    }
+
+    /// Splits every object exceeding `max_dim` pixels in either dimension into a grid of tiles,
+    /// each no larger than `max_dim` in either dimension, so that players with a maximum object
+    /// size can still show it. Each tile is composed into the same window as the original at
+    /// the corresponding offset, so the composition appears unchanged.
+    ///
+    /// Only the visible area of a cropped composition object is tiled, and the tiles themselves
+    /// are not cropped. The first tile replaces the original object in place when no other
+    /// composition object references it, while the remaining tiles are defined under new object
+    /// IDs. An oversized object that is no longer referenced once tiling is complete is removed.
+    /// Composition objects that reference objects not defined within this DS are left untouched.
+    ///
+    /// Each dimension is divided into the fewest tiles that fit, with their sizes kept as even
+    /// as possible. Should any tile be smaller than [MIN_OBJECT_DIMENSION], should the DS then
+    /// compose more than [MAX_COMPOSITION_OBJECTS] objects, or should object IDs run out, an
+    /// error is returned and this DS is left unchanged.
+    ///
+    /// New object IDs are only chosen to avoid those used within this DS, so this is best applied
+    /// to a DS that makes up an epoch on its own. Use
+    /// [Epoch::tile_oversized_objects](super::epoch::Epoch::tile_oversized_objects) to avoid
+    /// every ID used within an epoch.
+    pub fn tile_oversized_objects(&mut self, max_dim: u16) -> Result<(), TileError> {
+        self.tile_oversized_objects_avoiding(max_dim, &mut self.object_ids().collect())
+    }
+
+    /// Tiles oversized objects as [tile_oversized_objects](Self::tile_oversized_objects) does,
+    /// allocating new object IDs that are not within `used_ids` and adding them to it. Neither
+    /// this DS nor `used_ids` is changed should an error be returned.
+    pub(crate) fn tile_oversized_objects_avoiding(
+        &mut self,
+        max_dim: u16,
+        used_ids: &mut BTreeSet<u16>,
+    ) -> Result<(), TileError> {
+
+        if max_dim == 0 {
+            return Ok(())
+        }
+
+        let mut tiled = self.clone();
+        let mut used = used_ids.clone();
+        let mut tiled_vids = Vec::<Vid<u16>>::new();
+        let cids = tiled.composition.objects.keys().cloned().collect::<Vec<Cid>>();
+
+        for cid in cids {
+
+            let co = &tiled.composition.objects[&cid];
+            let (vid, object) = match tiled.objects.iter().rev().find(|(vid, _)|
+                vid.id == cid.object_id
+            ) {
+                Some((vid, object)) => (vid.clone(), object),
+                None => continue,
+            };
+
+            if object.width <= max_dim && object.height <= max_dim {
+                continue
+            }
+
+            let (crop_x, crop_y, crop_width, crop_height) = visible_region(co, object);
+            let mut tiles = Vec::<(u16, u16, Object)>::new();
+
+            for (tile_y, height) in even_spans(crop_height, max_dim) {
+                for (tile_x, width) in even_spans(crop_width, max_dim) {
+                    if width < MIN_OBJECT_DIMENSION || height < MIN_OBJECT_DIMENSION {
+                        return Err(TileError::TileTooSmall { object_id: vid.id, width, height })
+                    }
+                    tiles.push((
+                        tile_x,
+                        tile_y,
+                        object.region(crop_x + tile_x, crop_y + tile_y, width, height),
+                    ));
+                }
+            }
+
+            let shared = tiled.composition.objects.keys()
+                .filter(|other| other.object_id == cid.object_id)
+                .count() > 1;
+            let new_ids = (0..=u16::MAX)
+                .filter(|id| !used.contains(id))
+                .take(tiles.len() - if shared { 0 } else { 1 })
+                .collect::<Vec<u16>>();
+
+            if new_ids.len() < tiles.len() - if shared { 0 } else { 1 } {
+                return Err(TileError::ObjectIdsExhausted)
+            }
+
+            used.extend(&new_ids);
+
+            let mut new_ids = new_ids.into_iter();
+
+            let original = tiled.composition.objects.remove(&cid).unwrap();
+            let mut tile_cids = Vec::<Cid>::new();
+
+            for (index, (tile_x, tile_y, tile)) in tiles.into_iter().enumerate() {
+
+                let tile_vid = if index == 0 && !shared {
+                    vid.clone()
+                } else {
+                    Vid {
+                        id: new_ids.next().unwrap(),
+                        version: 0,
+                    }
+                };
+                let tile_cid = Cid {
+                    object_id: tile_vid.id,
+                    window_id: cid.window_id,
+                };
+
+                tiled.objects.insert(tile_vid, tile);
+                tiled.composition.objects.insert(
+                    tile_cid.clone(),
+                    CompositionObject {
+                        x: original.x.saturating_add(tile_x),
+                        y: original.y.saturating_add(tile_y),
+                        forced: original.forced,
+                        crop: None,
+                    },
+                );
+                tile_cids.push(tile_cid);
+            }
+
+            if let Some(position) = tiled.composition.order.iter().position(|other| *other == cid) {
+                tiled.composition.order.splice(position..=position, tile_cids);
+            }
+
+            if shared {
+                tiled_vids.push(vid);
+            }
+        }
+
+        for vid in tiled_vids {
+            if !tiled.composition.objects.keys().any(|cid| cid.object_id == vid.id) {
+                tiled.objects.remove(&vid);
+            }
+        }
+
+        let count = tiled.composition.objects.len();
+
+        if count > MAX_COMPOSITION_OBJECTS {
+            return Err(TileError::TooManyCompositionObjects { count })
+        }
+
+        *self = tiled;
+        *used_ids = used;
+
+        Ok(())
+    }
+
+    /// Renumbers the objects of this DS so that their IDs form a dense range starting at zero,
+    /// which relieves players having small object ID tables. IDs keep their relative order, and
+    /// composition objects are updated to reference the renumbered objects. IDs that are
+    /// referenced by composition objects but not defined within this DS are renumbered
+    /// alongside the rest. As the new IDs are only assigned within this DS, this is best applied
+    /// to display sets that define everything they compose, such as those beginning an epoch.
+    pub fn compact_object_ids(&mut self) {
+
+        let ids = self.objects.keys().map(|vid| vid.id)
+            .chain(self.composition.objects.keys().map(|cid| cid.object_id))
+            .collect::<BTreeSet<u16>>();
+        let map = ids.into_iter()
+            .enumerate()
+            .map(|(index, id)| (id, index as u16))
+            .collect::<BTreeMap<u16, u16>>();
+
+        self.objects = mem::take(&mut self.objects).into_iter()
+            .map(|(vid, object)| (Vid { id: map[&vid.id], version: vid.version }, object))
+            .collect();
+        self.remap_cids(|cid| Cid { object_id: map[&cid.object_id], window_id: cid.window_id });
+    }
+
+    /// Renumbers the windows of this DS so that their IDs form a dense range starting at zero.
+    /// IDs keep their relative order, and composition objects are updated to reference the
+    /// renumbered windows. The same caveats apply as for
+    /// [compact_object_ids](Self::compact_object_ids).
+    pub fn compact_window_ids(&mut self) {
+
+        let ids = self.windows.keys().copied()
+            .chain(self.composition.objects.keys().map(|cid| cid.window_id))
+            .collect::<BTreeSet<u8>>();
+        let map = ids.into_iter()
+            .enumerate()
+            .map(|(index, id)| (id, index as u8))
+            .collect::<BTreeMap<u8, u8>>();
+
+        self.windows = mem::take(&mut self.windows).into_iter()
+            .map(|(id, window)| (map[&id], window))
+            .collect();
+        self.remap_cids(|cid| Cid { object_id: cid.object_id, window_id: map[&cid.window_id] });
+    }
+
+    /// Renumbers the palettes of this DS so that their IDs form a dense range starting at zero.
+    /// IDs keep their relative order, and the [palette ID](Self::palette_id) is updated to
+    /// reference the renumbered palette. The same caveats apply as for
+    /// [compact_object_ids](Self::compact_object_ids).
+    pub fn compact_palette_ids(&mut self) {
+
+        let ids = self.palettes.keys().map(|vid| vid.id)
+            .chain([self.palette_id])
+            .collect::<BTreeSet<u8>>();
+        let map = ids.into_iter()
+            .enumerate()
+            .map(|(index, id)| (id, index as u8))
+            .collect::<BTreeMap<u8, u8>>();
+
+        self.palettes = mem::take(&mut self.palettes).into_iter()
+            .map(|(vid, palette)| (Vid { id: map[&vid.id], version: vid.version }, palette))
+            .collect();
+        self.palette_id = map[&self.palette_id];
+    }
+
+    /// Gives each composition object its own object, so that an object composited into more than
+    /// one window can have each instance edited independently. The first composition object
+    /// referencing an object keeps it, while every other one is given a copy of the object's
+    /// latest version under the lowest unused object ID, with composition objects (and the
+    /// composition order) updated accordingly. Objects that are not defined within this DS
+    /// cannot be copied and remain shared.
+    ///
+    /// Object IDs are only considered unused if this DS does not use them, so this is best
+    /// applied to a DS that makes up an epoch on its own. Use
+    /// [Epoch::unshare_objects](super::epoch::Epoch::unshare_objects) to avoid every ID used
+    /// within an epoch.
+    pub fn unshare_objects(&mut self) {
+        self.unshare_objects_avoiding(&mut self.object_ids().collect());
+    }
+
+    /// Unshares objects as [unshare_objects](Self::unshare_objects) does, allocating new object
+    /// IDs that are not within `used` and adding them to it.
+    pub(crate) fn unshare_objects_avoiding(&mut self, used: &mut BTreeSet<u16>) {
+
+        let mut map = BTreeMap::<Cid, Cid>::new();
+        let mut copies = Vec::<(Vid<u16>, Object)>::new();
+        let mut claimed = BTreeSet::<u16>::new();
+
+        for cid in self.composition.objects.keys() {
+
+            if claimed.insert(cid.object_id) {
+                continue
+            }
+
+            let Some((vid, object)) = self.objects.iter()
+                .rev()
+                .find(|(vid, _)| vid.id == cid.object_id)
+            else {
+                continue
+            };
+            let Some(id) = (0..=u16::MAX).find(|id| !used.contains(id)) else {
+                break
+            };
+
+            used.insert(id);
+            copies.push((Vid { id, version: vid.version }, object.clone()));
+            map.insert(cid.clone(), Cid { object_id: id, window_id: cid.window_id });
+        }
+
+        self.objects.extend(copies);
+        self.remap_cids(|cid| map.get(cid).cloned().unwrap_or_else(|| cid.clone()));
+    }
+
+    /// Distributes composition objects that all share a single window into multiple windows,
+    /// each holding no more than `max_per_window` of them and sized to their bounding box. This
+    /// converts a flat composition into a layout more friendly to players.
+    ///
+    /// Composition objects are grouped by position, from top to bottom and then from left to
+    /// right, and each is measured by its visible region. As players only allow two objects per
+    /// window, `max_per_window` is limited to between one and two. Groups whose bounding boxes
+    /// would overlap are merged, as windows may not overlap. The shared window is replaced by
+    /// the new windows, which are given the lowest IDs not used by any other window. Nothing is
+    /// changed if the composition objects are spread across more than one window already, or if
+    /// any of them references an object not defined within this DS.
+    ///
+    /// Should a merged group exceed `max_per_window`, or should window IDs run out, an error is
+    /// returned and this DS is left unchanged.
+    pub fn auto_window(&mut self, max_per_window: u8) -> Result<(), AutoWindowError> {
+
+        let window_ids = self.composition.objects.keys()
+            .map(|cid| cid.window_id)
+            .collect::<BTreeSet<u8>>();
+
+        if window_ids.len() != 1 {
+            return Ok(())
+        }
+
+        let mut extents = Vec::<(Cid, (u32, u32, u32, u32))>::new();
+
+        for (cid, co) in &self.composition.objects {
+            let Some(object) = latest(&self.objects, cid.object_id) else {
+                return Ok(())
+            };
+            let (_, _, width, height) = visible_region(co, object);
+            let (x, y) = (co.x as u32, co.y as u32);
+            extents.push((cid.clone(), (x, y, x + width as u32, y + height as u32)));
+        }
+
+        extents.sort_by_key(|(_, (x, y, _, _))| (*y, *x));
+
+        let max_per_window = max_per_window.clamp(1, 2) as usize;
+        let mut groups = extents.chunks(max_per_window)
+            .map(|group| {
+                let cids = group.iter().map(|(cid, _)| cid.clone()).collect::<Vec<Cid>>();
+                let bounds = group.iter()
+                    .map(|(_, bounds)| *bounds)
+                    .reduce(union_bounds)
+                    .unwrap_or_default();
+                (cids, bounds)
+            })
+            .collect::<Vec<_>>();
+
+        while let Some((a, b)) = (0..groups.len())
+            .flat_map(|a| (a + 1..groups.len()).map(move |b| (a, b)))
+            .find(|&(a, b)| bounds_overlap(groups[a].1, groups[b].1)) {
+            let (cids, bounds) = groups.remove(b);
+            groups[a].0.extend(cids);
+            groups[a].1 = union_bounds(groups[a].1, bounds);
+        }
+
+        if groups.iter().any(|(cids, _)| cids.len() > max_per_window) {
+            return Err(AutoWindowError::OverlappingObjects)
+        }
+
+        let mut windows = self.windows.clone();
+        let mut map = BTreeMap::<Cid, Cid>::new();
+
+        for window_id in window_ids {
+            windows.remove(&window_id);
+        }
+
+        for (cids, (left, top, right, bottom)) in groups {
+
+            let Some(window_id) = (0..=u8::MAX).find(|id| !windows.contains_key(id)) else {
+                return Err(AutoWindowError::WindowIdsExhausted)
+            };
+
+            windows.insert(
+                window_id,
+                Window {
+                    x: left as u16,
+                    y: top as u16,
+                    width: (right - left).min(u16::MAX as u32) as u16,
+                    height: (bottom - top).min(u16::MAX as u32) as u16,
+                },
+            );
+
+            for cid in cids {
+                map.insert(cid.clone(), Cid { object_id: cid.object_id, window_id });
+            }
+        }
+
+        self.windows = windows;
+        self.remap_cids(|cid| map.get(cid).cloned().unwrap_or_else(|| cid.clone()));
+
+        Ok(())
+    }
+
+    /// Iterates over every object ID that this DS either defines or composes. IDs may repeat.
+    pub(crate) fn object_ids(&self) -> impl Iterator<Item = u16> + '_ {
+        self.objects.keys().map(|vid| vid.id)
+            .chain(self.composition.objects.keys().map(|cid| cid.object_id))
+    }
+
+    /// Replaces the compound ID of every composition object, including those listed within the
+    /// composition order. Stale entries of the composition order, which have no corresponding
+    /// composition object, are dropped first so that `f` is only given IDs that are in use.
+    pub(crate) fn remap_cids(&mut self, f: impl Fn(&Cid) -> Cid) {
+
+        let objects = &self.composition.objects;
+
+        self.composition.order.retain(|cid| objects.contains_key(cid));
+        self.composition.objects = mem::take(&mut self.composition.objects).into_iter()
+            .map(|(cid, co)| (f(&cid), co))
+            .collect();
+
+        for cid in self.composition.order.iter_mut() {
+            *cid = f(cid);
+        }
+    }
+
+    /// Iterates over the screen coordinates of every non-transparent pixel composited into the
+    /// specified window. Pixels falling outside of the window are excluded.
+    fn opaque_window_pixels<'a>(
+        &'a self,
+        window_id: u8,
+        window: &'a Window,
+        palette: Option<&'a Palette>,
+    ) -> impl Iterator<Item = (u32, u32)> + 'a {
+        self.composition.objects.iter()
+            .filter(move |(cid, _)| cid.window_id == window_id)
+            .filter_map(|(cid, co)| latest(&self.objects, cid.object_id).map(|o| (co, o)))
+            .flat_map(|(co, object)| visible_pixels(co, object))
+            .filter(move |&(x, y, index)| is_opaque(palette, index) && window.contains(x, y))
+            .map(|(x, y, _)| (x, y))
+    }
+}
+
+impl Palette {
+
+    /// Adjusts the brightness, contrast, and saturation of every entry in RGB space. A value of
+    /// `1.0` leaves the respective property unchanged.
+    ///
+    /// Saturation is applied by blending each channel with the BT.709 luminance of the entry,
+    /// where a factor of `0.0` yields grayscale. Contrast is then applied by scaling each
+    /// channel about its midpoint of `0.5`. Brightness is not a factor, but is instead applied
+    /// last by adding `brightness - 1.0` to each channel, so that `1.5` raises every channel by
+    /// half of its full range and `0.5` lowers it by the same amount.
+    pub fn adjust(&mut self, brightness: f64, contrast: f64, saturation: f64) {
+        for entry in self.entries.values_mut() {
+
+            let rgb = rgb_pixel(
+                YcbcrPixel { y: entry.y, cb: entry.cb, cr: entry.cr },
+                ColorMatrix::Bt709,
+            );
+            let luma = 0.2126 * rgb.red + 0.7152 * rgb.green + 0.0722 * rgb.blue;
+            let adjust = |value: f64| {
+                let saturated = value * saturation + luma * (1.0 - saturation);
+                saturated * contrast + 0.5 * (1.0 - contrast) + (brightness - 1.0)
+            };
+            let ycbcr = ycbcr_pixel(
+                RgbPixel {
+                    red: adjust(rgb.red),
+                    green: adjust(rgb.green),
+                    blue: adjust(rgb.blue),
+                },
+                ColorMatrix::Bt709,
+            );
+
+            entry.y = ycbcr.y;
+            entry.cb = ycbcr.cb;
+            entry.cr = ycbcr.cr;
+        }
+    }
+
+    /// Clamps the luminosity value of every entry to the legal studio range of `16` to `235`,
+    /// which some broadcast tools require. Chroma values are likewise clamped to the range of
+    /// `16` to `240` if requested. Color edits such as luminosity scaling can otherwise leave
+    /// values outside of these ranges.
+    pub fn clamp_studio_range(&mut self, clamp_chroma: bool) {
+        for entry in self.entries.values_mut() {
+            entry.y = entry.y.clamp(16, 235);
+            if clamp_chroma {
+                entry.cr = entry.cr.clamp(16, 240);
+                entry.cb = entry.cb.clamp(16, 240);
+            }
+        }
+    }
+}
+
+impl Object {
+
+    /// Copies the specified rectangular region of this object into a new object. The region is
+    /// expected to lie within the object's bounds.
+    pub(crate) fn region(&self, x: u16, y: u16, width: u16, height: u16) -> Object {
+        Object {
+            width,
+            height,
+            lines: self.lines.iter()
+                .skip(y as usize)
+                .take(height as usize)
+                .map(|line| line.iter().skip(x as usize).take(width as usize).copied().collect())
+                .collect(),
+        }
+    }
+
+    /// Collects statistics about the runs of identical pixels within this object's lines. This
+    /// is useful for diagnosing why an object compresses poorly.
+    pub fn run_statistics(&self) -> RunStats {
+
+        let mut stats = RunStats::default();
+        let mut total_length = 0;
+
+        for line in &self.lines {
+            for run in line.chunk_by(|a, b| a == b) {
+                stats.run_count += 1;
+                if run[0] == 0 {
+                    stats.zero_run_count += 1;
+                }
+                *stats.length_distribution.entry(run.len()).or_insert(0) += 1;
+                total_length += run.len();
+            }
+        }
+
+        if stats.run_count > 0 {
+            stats.average_run_length = total_length as f64 / stats.run_count as f64;
+        }
+
+        stats
+    }
+
+    /// Renders this object as ASCII art for previewing within a terminal. Each line of output
+    /// ends with a newline. Transparent pixels, along with those referring to entries missing
+    /// from `palette`, become spaces. All other pixels are shaded from `.` to `@` according to
+    /// their luminosity weighted by their alpha value. Objects wider than 80 columns are
+    /// downscaled by an integer factor in both dimensions, sampling the top-left pixel of each
+    /// block.
+    pub fn to_ascii(&self, palette: &Palette) -> String {
+
+        const RAMP: &[u8] = b" .:-=+*#%@";
+        const COLUMNS: usize = 80;
+
+        let factor = (self.width as usize).div_ceil(COLUMNS).max(1);
+        let mut ascii = String::new();
+
+        for line in self.lines.iter().step_by(factor) {
+            for &pixel in line.iter().step_by(factor) {
+                ascii.push(match palette.entries.get(&pixel) {
+                    Some(entry) if entry.alpha > 0 => {
+                        let luma = ((entry.y as f64 - 16.0) / 219.0).clamp(0.0, 1.0);
+                        let coverage = luma * entry.alpha as f64 / 255.0;
+                        RAMP[1 + (coverage * (RAMP.len() - 2) as f64).round() as usize] as char
+                    }
+                    _ => ' ',
+                });
+            }
+            ascii.push('\n');
+        }
+
+        ascii
+    }
+
+    /// Converts this object to 8-bit RGBA samples in row-major order, mapping each pixel through
+    /// `palette` and converting it to RGB using [rgb_pixel] with the BT.709 matrix. The output
+    /// always covers the full width and height of the object; lines that are shorter than the
+    /// object's width, along with any lines that are missing, are padded with fully-transparent
+    /// pixels. Should a pixel refer to an entry that the palette does not define, an error is
+    /// returned.
+    pub fn to_rgba_image(&self, palette: &Palette) -> Result<Vec<u8>, MissingPaletteEntryError> {
+
+        let width = self.width as usize;
+        let mut rgba = vec![0_u8; width * self.height as usize * 4];
+
+        if width == 0 {
+            return Ok(rgba)
+        }
+
+        for (line, row) in self.lines.iter().zip(rgba.chunks_exact_mut(width * 4)) {
+            for (&index, pixel) in line.iter().zip(row.chunks_exact_mut(4)) {
+                let entry = palette.entries.get(&index).ok_or(MissingPaletteEntryError { index })?;
+                let rgb = rgb_pixel(
+                    YcbcrPixel { y: entry.y, cb: entry.cb, cr: entry.cr },
+                    ColorMatrix::Bt709,
+                );
+                for (sample, channel) in pixel.iter_mut().zip([rgb.red, rgb.green, rgb.blue]) {
+                    *sample = (channel.clamp(0.0, 1.0) * 255.0).round() as u8;
+                }
+                pixel[3] = entry.alpha;
+            }
+        }
+
+        Ok(rgba)
+    }
+
+    /// Calculates the Adler-32 checksum of this object's dimensions and decoded lines. The width
+    /// and height are each included as two big-endian bytes, followed by the pixels of every
+    /// line in order.
+    pub fn adler32(&self) -> u32 {
+
+        let dimensions = self.width.to_be_bytes().into_iter().chain(self.height.to_be_bytes());
+
+        adler32(dimensions.chain(self.lines.iter().flatten().copied()))
+    }
+}
+
+impl Window {
+
+    /// Determines whether or not the specified screen coordinates lie within this window.
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x as u32
+            && y >= self.y as u32
+            && x < self.x as u32 + self.width as u32
+            && y < self.y as u32 + self.height as u32
+    }
+}
+
+/// Assigns composition numbers to display sets as they are authored, so that they need not be
+/// tracked manually.
+///
+/// The composition number increments with each DS across the entire presentation rather than
+/// restarting with each epoch, wrapping around once it exceeds `65,535`. It is unrelated to
+/// object IDs.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct CompositionNumberCounter {
+    next: u16,
+}
+
+impl CompositionNumberCounter {
+
+    /// Creates a counter whose first assigned number will be the one specified.
+    pub fn new(first: u16) -> Self {
+        CompositionNumberCounter { next: first }
+    }
+
+    /// Returns the next composition number and advances the counter.
+    pub fn next_number(&mut self) -> u16 {
+
+        let number = self.next;
+
+        self.next = self.next.wrapping_add(1);
+
+        number
+    }
+
+    /// Returns the display set with the next composition number assigned to it.
+    pub fn assign(&mut self, ds: DisplaySet) -> DisplaySet {
+        ds.with_composition_number(self.next_number())
+    }
+}
+
+/// Borrows a display set for use as a key that is compared and hashed according to its visual
+/// content alone. This allows display sets that appear identical, but that are presented at
+/// different times, to be deduplicated within a hash map or set.
+///
+/// The screen dimensions, palette ID, windows, palettes, objects, and composition objects
+/// (including their order) are considered. Palettes and objects are compared by ID along with
+/// the content of the latest version of each, so the same content defined under different
+/// versions is considered equal. Timing, the frame rate, the composition number, and the
+/// composition state are ignored.
+#[derive(Clone, Copy, Debug)]
+pub struct ContentKey<'a>(pub &'a DisplaySet);
+
+impl PartialEq for ContentKey<'_> {
+
+    fn eq(&self, other: &Self) -> bool {
+
+        let (a, b) = (self.0, other.0);
+
+        a.width == b.width
+            && a.height == b.height
+            && a.palette_id == b.palette_id
+            && a.palete_update_only == b.palete_update_only
+            && a.windows == b.windows
+            && resolved(&a.palettes) == resolved(&b.palettes)
+            && resolved(&a.objects) == resolved(&b.objects)
+            && a.composition.ordered_objects() == b.composition.ordered_objects()
+    }
+}
+
+impl Eq for ContentKey<'_> {
+}
+
+impl Hash for ContentKey<'_> {
+
+    fn hash<H: Hasher>(&self, state: &mut H) {
+
+        let ds = self.0;
+
+        ds.width.hash(state);
+        ds.height.hash(state);
+        ds.palette_id.hash(state);
+        ds.palete_update_only.hash(state);
+        ds.windows.hash(state);
+        resolved(&ds.palettes).hash(state);
+        resolved(&ds.objects).hash(state);
+        ds.composition.ordered_objects().hash(state);
+    }
+}
+
+/// Writes a display set to an in-memory buffer and then reads it back.
+///
+/// This is useful for verifying that a display set survives being encoded and decoded without
+/// any loss of fidelity.
+pub fn roundtrip(ds: &DisplaySet) -> Result<DisplaySet, RoundtripError> {
+
+    let mut buffer = vec![];
+
+    buffer.write_display_set(ds.clone())?;
+
+    Ok(Cursor::new(buffer).read_display_set()?)
+}
+
+/// Determines whether or not two objects define the same visual content.
+///
+/// Only the dimensions and decoded lines of each object are considered. As an object's version
+/// is tracked by its [Vid] rather than by the object itself, this may be used to detect where
+/// the same object has been redefined within an epoch under a bumped version.
+pub fn object_content_eq(a: &Object, b: &Object) -> bool {
+    a.width == b.width && a.height == b.height && a.lines == b.lines
+}
+
+/// Extracts every palette definition and update from a sequence of display sets.
+///
+/// Each entry is returned as `(pts, palette_id, palette)` and the result is ordered by PTS and
+/// then by palette ID. This includes palettes defined as part of a new composition as well as
+/// those within display sets that only perform palette updates, allowing the colors of an epoch
+/// to be charted over time (during a fade, for example).
+pub fn palette_timeline(sets: &[DisplaySet]) -> Vec<(u32, u8, Palette)> {
+
+    let mut timeline = sets.iter().flat_map(|ds|
+        ds.palettes.iter().map(|(vid, palette)| (ds.pts, vid.id, palette.clone()))
+    ).collect::<Vec<(u32, u8, Palette)>>();
+
+    timeline.sort_by_key(|(pts, id, _)| (*pts, *id));
+
+    timeline
+}
+
+/// Determines whether any display set within a sequence only updates palettes. Palette updates
+/// are typically used to animate fades, so a renderer that does not support them can use this
+/// to warn that such animation will be lost.
+pub fn uses_palette_updates(sets: &[DisplaySet]) -> bool {
+    sets.iter().any(|ds| ds.palete_update_only)
+}
+
+/// Counts the display sets within a sequence that only update palettes. See
+/// [uses_palette_updates].
+pub fn palette_update_count(sets: &[DisplaySet]) -> usize {
+    sets.iter().filter(|ds| ds.palete_update_only).count()
+}
+
+/// Determines the span along one axis, as `(start, end)`, that is covered by the specified
+/// windows along with the visible area of each composition object whose object is defined.
+pub(crate) fn content_extent(
+    horizontal: bool,
+    windows: &BTreeMap<u8, Window>,
+    composition: &Composition,
+    objects: &BTreeMap<Vid<u16>, Object>,
+) -> Option<(u32, u32)> {
+
+    let axis = |x: u16, y: u16| if horizontal { x } else { y };
+    let window_extents = windows.values()
+        .map(|window| (window.x, window.y, window.width, window.height));
+    let object_extents = composition.objects.iter()
+        .filter_map(|(cid, co)| latest(objects, cid.object_id).map(|o| (co, o)))
+        .map(|(co, object)| {
+            let (_, _, width, height) = visible_region(co, object);
+            (co.x, co.y, width, height)
+        });
+
+    window_extents.chain(object_extents)
+        .map(|(x, y, width, height)| {
+            let start = axis(x, y) as u32;
+            Some((start, start + axis(width, height) as u32))
+        })
+        .fold(None, union_extents)
+}
+
+/// Combines two optional spans into the smallest span covering both.
+pub(crate) fn union_extents(a: Option<(u32, u32)>, b: Option<(u32, u32)>) -> Option<(u32, u32)> {
+    match (a, b) {
+        (Some((start_a, end_a)), Some((start_b, end_b))) => {
+            Some((start_a.min(start_b), end_a.max(end_b)))
+        }
+        (a, b) => {
+            a.or(b)
+        }
+    }
+}
+
+/// Looks up the latest version of the specified ID within a versioned collection.
+pub(crate) fn latest<T: Copy + Ord, V>(map: &BTreeMap<Vid<T>, V>, id: T) -> Option<&V> {
+    map.iter().rev().find(|(vid, _)| vid.id == id).map(|(_, value)| value)
+}
+
+/// Maps each ID within a versioned collection to its latest version, dropping the versions
+/// themselves.
+fn resolved<T: Copy + Ord, V>(map: &BTreeMap<Vid<T>, V>) -> BTreeMap<T, &V> {
+    map.iter().map(|(vid, value)| (vid.id, value)).collect()
+}
+
+/// Calculates the Adler-32 checksum of a sequence of bytes.
+pub(crate) fn adler32(bytes: impl IntoIterator<Item = u8>) -> u32 {
+
+    let mut a = 1_u32;
+    let mut b = 0_u32;
+
+    for byte in bytes {
+        a = (a + byte as u32) % 65_521;
+        b = (b + a) % 65_521;
+    }
+
+    (b << 16) | a
+}
+
+/// Determines whether or not a pixel index resolves to a visible palette entry. Indexes that
+/// are not defined by the palette are treated as transparent.
+fn is_opaque(palette: Option<&Palette>, index: u8) -> bool {
+    palette.and_then(|p| p.entries.get(&index)).is_some_and(|entry| entry.alpha > 0)
+}
+
+/// Determines the region of an object that is visible for a composition object as
+/// `(x, y, width, height)`, relative to the object itself. The crop region (if any) is clamped
+/// to the object's bounds.
+pub(crate) fn visible_region(co: &CompositionObject, object: &Object) -> (u16, u16, u16, u16) {
+    match &co.crop {
+        Some(crop) => {
+            let x = crop.x.min(object.width);
+            let y = crop.y.min(object.height);
+            (x, y, crop.width.min(object.width - x), crop.height.min(object.height - y))
+        }
+        None => {
+            (0, 0, object.width, object.height)
+        }
+    }
+}
+
+/// Divides a length into the fewest spans no longer than `max`, returned as `(offset, length)`.
+/// The lengths of the spans differ by no more than one.
+fn even_spans(length: u16, max: u16) -> Vec<(u16, u16)> {
+
+    let count = length.div_ceil(max);
+    let mut offset = 0;
+
+    (0..count).map(|index| {
+        let span = length / count + (index < length % count) as u16;
+        offset += span;
+        (offset - span, span)
+    }).collect()
+}
+
+/// Combines two `(left, top, right, bottom)` bounding boxes into one covering both.
+fn union_bounds(a: (u32, u32, u32, u32), b: (u32, u32, u32, u32)) -> (u32, u32, u32, u32) {
+    (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3))
+}
+
+/// Determines whether or not two `(left, top, right, bottom)` bounding boxes overlap.
+fn bounds_overlap(a: (u32, u32, u32, u32), b: (u32, u32, u32, u32)) -> bool {
+    a.0 < b.2 && b.0 < a.2 && a.1 < b.3 && b.1 < a.3
+}
+
+/// Crops and repositions a composition object so that only the portion of it lying within the
+/// specified screen region remains visible. Returns `false` if no portion remains visible.
+fn crop_to_region(
+    co: &mut CompositionObject,
+    object: &Object,
+    left: u32,
+    top: u32,
+    right: u32,
+    bottom: u32,
+) -> bool {
+
+    let (crop_x, crop_y, crop_width, crop_height) = visible_region(co, object);
+    let (x, y) = (co.x as u32, co.y as u32);
+    let new_left = left.max(x);
+    let new_top = top.max(y);
+    let new_right = right.min(x + crop_width as u32);
+    let new_bottom = bottom.min(y + crop_height as u32);
+
+    if new_left >= new_right || new_top >= new_bottom {
+        return false
+    }
+
+    if (new_left, new_top, new_right, new_bottom)
+        != (x, y, x + crop_width as u32, y + crop_height as u32) {
+        co.crop = Some(
+            Crop {
+                x: crop_x + (new_left - x) as u16,
+                y: crop_y + (new_top - y) as u16,
+                width: (new_right - new_left) as u16,
+                height: (new_bottom - new_top) as u16,
+            }
+        );
+        co.x = new_left as u16;
+        co.y = new_top as u16;
+    }
+
+    true
+}
+
+/// Iterates over the visible pixels of a composed object, yielding the screen coordinates and
+/// palette index of each one. The crop region (if any) is clamped to the object's bounds.
+pub(crate) fn visible_pixels<'a>(
+    co: &'a CompositionObject,
+    object: &'a Object,
+) -> impl Iterator<Item = (u32, u32, u8)> + 'a {
+
+    let (crop_x, crop_y, crop_width, crop_height) = visible_region(co, object);
+
+    object.lines.iter()
+        .take(object.height as usize)
+        .skip(crop_y as usize)
+        .take(crop_height as usize)
+        .enumerate()
+        .flat_map(move |(row, line)| {
+            line.iter()
+                .take(object.width as usize)
+                .skip(crop_x as usize)
+                .take(crop_width as usize)
+                .enumerate()
+                .map(move |(column, &index)|
+                    (co.x as u32 + column as u32, co.y as u32 + row as u32, index)
+                )
+        })
+}
@@ -9,6 +9,7 @@
  */
 
 use super::{
+    latest,
     Cid,
     Composition,
     CompositionObject,
@@ -18,15 +19,19 @@ use super::{
     PaletteEntry,
     Vid,
     Window,
+    super::rle::{self, RleError},
     super::segment::{
         ReadError as SegmentReadError,
+        ReadOptions as SegmentReadOptions,
+        CompositionState,
         ReadSegmentExt,
         Segment,
+        UnknownSegment,
     },
 };
 use std::{
     collections::BTreeMap,
-    io::Read,
+    io::{ErrorKind, Read, Seek},
 };
 use thiserror::Error as ThisError;
 
@@ -54,6 +59,13 @@ pub enum ReadError {
         #[from]
         source: ParseError,
     },
+    /// The end of the input was reached partway through a multi-part object. That is, an
+    /// initial object definition segment (ODS) was read, but its final portion was not.
+    #[error("input ends within object sequence")]
+    TruncatedObjectSequence {
+        /// The ID of the object being defined.
+        object_id: u16,
+    },
 }
 
 /// The error type for parsing a collection of segments into a display set.
@@ -108,6 +120,17 @@ pub enum ParseError {
     /// The different portions of a compound object have inconsistent versions.
     #[error("object portions have inconsistent versions")]
     InconsistentObjectVersion,
+    /// The data length declared by the initial portion of a compound object does not agree
+    /// with the data actually held by all of its portions. As with single-part objects, the
+    /// declared length includes the four bytes occupied by the object's dimensions. This is
+    /// only checked in strict mode, as the data itself is sized by its segments.
+    #[error("object data length does not match its portions")]
+    InconsistentObjectDataLength {
+        /// The data length declared by the initial portion.
+        declared_length: usize,
+        /// The data length held by all portions, plus four.
+        actual_length: usize,
+    },
     /// The bitstream declares an incomplete RLE sequence within an object definition segment
     /// (ODS).
     #[error("incomplete RLE sequence")]
@@ -119,6 +142,48 @@ pub enum ParseError {
     /// The bitstream declares an incomplete RLE line within an object definition segment (ODS).
     #[error("incomplete RLE line")]
     IncompleteRleLine,
+    /// A composition object declares a crop region that does not fit within the object it
+    /// references. This is only checked in strict mode and only when the referenced object is
+    /// defined within the same display set.
+    #[error("composition object crop region exceeds object bounds")]
+    InvalidCropRegion,
+    /// A composition object references a window that the display set does not define. This is
+    /// only checked in strict mode and only for display sets whose composition state is either
+    /// [EpochStart](CompositionState::EpochStart) or
+    /// [AcquisitionPoint](CompositionState::AcquisitionPoint), as all other display sets may
+    /// inherit their windows.
+    #[error("composition object references unknown window")]
+    CompositionReferencesUnknownWindow {
+        /// The ID of the window that was referenced.
+        window_id: u8,
+    },
+    /// A display set that only updates a palette also defines windows, objects, or palettes
+    /// other than the one being updated. This is only checked in strict mode.
+    #[error("palette update defines more than its palette")]
+    MalformedPaletteUpdate,
+}
+
+impl From<RleError> for ParseError {
+
+    fn from(err: RleError) -> Self {
+        match err {
+            RleError::IncompleteSequence => ParseError::IncompleteRleSequence,
+            RleError::IncompleteLine => ParseError::IncompleteRleLine,
+            // Decompression never reports an overly long line, as any run it decodes is short
+            // enough to have been encoded in the first place.
+            RleError::InvalidSequence | RleError::LineTooLong => ParseError::InvalidRleSequence,
+        }
+    }
+}
+
+/// Defines options that control how display sets are read.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ReadOptions {
+    /// Enables additional validation of display set properties that are not necessary to parse
+    /// the bitstream, but that would otherwise produce nonsensical results for a renderer.
+    pub strict: bool,
+    /// The options used to read each underlying segment.
+    pub segment: SegmentReadOptions,
 }
 
 #[derive(PartialEq)]
@@ -133,16 +198,29 @@ enum Sequence {
 pub trait ReadDisplaySetExt {
     /// Reads the next display set from an input source.
     fn read_display_set(&mut self) -> ReadResult<DisplaySet>;
+    /// Reads the next display set from an input source using the specified options.
+    fn read_display_set_with_options(&mut self, options: &ReadOptions)
+        -> ReadResult<DisplaySet>;
+    /// Reads the next display set from a seekable input source, also returning the stream
+    /// offset at which it begins (that is, the offset of its PCS). These offsets may be
+    /// retained in order to seek directly to a display set later.
+    fn read_display_set_at(&mut self) -> ReadResult<(u64, DisplaySet)> where
+        Self: Seek;
 }
 
 impl<T> ReadDisplaySetExt for T where
     T: Read,
 {
     fn read_display_set(&mut self) -> ReadResult<DisplaySet> {
+        self.read_display_set_with_options(&ReadOptions::default())
+    }
+
+    fn read_display_set_with_options(&mut self, options: &ReadOptions)
+        -> ReadResult<DisplaySet> {
 
         let mut segments = Vec::<Segment>::new();
 
-        match self.read_segment()? {
+        match self.read_segment_with_options(&options.segment)? {
             Segment::PresentationComposition(pcs) => {
                 segments.push(Segment::PresentationComposition(pcs));
             }
@@ -155,8 +233,41 @@ impl<T> ReadDisplaySetExt for T where
             }
         };
 
+        let mut open_object_id = None;
+
         loop {
-            match self.read_segment()? {
+
+            let segment = match self.read_segment_with_options(&options.segment) {
+                Ok(segment) => {
+                    segment
+                }
+                Err(err) => {
+                    return Err(
+                        match (open_object_id, &err) {
+                            (Some(object_id), SegmentReadError::IoError { source })
+                                if source.kind() == ErrorKind::UnexpectedEof => {
+                                ReadError::TruncatedObjectSequence { object_id }
+                            }
+                            _ => {
+                                err.into()
+                            }
+                        }
+                    )
+                }
+            };
+
+            match &segment {
+                Segment::InitialObjectDefinition(iods) => {
+                    open_object_id = Some(iods.id);
+                }
+                Segment::SingleObjectDefinition(_) | Segment::FinalObjectDefinition(_) => {
+                    open_object_id = None;
+                }
+                _ => {
+                }
+            }
+
+            match segment {
                 Segment::PresentationComposition(_) => {
                     return Err(
                         ReadError::ParseError {
@@ -174,13 +285,70 @@ impl<T> ReadDisplaySetExt for T where
             }
         }
 
-        Ok(DisplaySet::try_from(&segments)?)
+        Ok(DisplaySet::try_from(&segments, options)?)
+    }
+
+    fn read_display_set_at(&mut self) -> ReadResult<(u64, DisplaySet)> where
+        Self: Seek,
+    {
+        let offset = self.stream_position().map_err(SegmentReadError::from)?;
+        let display_set = self.read_display_set()?;
+
+        Ok((offset, display_set))
+    }
+}
+
+/// Returns an iterator that lazily reads display sets from an input source until it is exhausted.
+/// Reaching the end of the input before the first byte of a display set ends the iteration,
+/// whereas reaching it anywhere else yields an error. The iterator stops after its first error.
+pub fn display_sets<R: Read>(reader: R) -> DisplaySetIterator<R> {
+    DisplaySetIterator { reader, failed: false }
+}
+
+/// Lazily reads display sets from an input source. This is returned by [display_sets].
+pub struct DisplaySetIterator<R: Read> {
+    reader: R,
+    failed: bool,
+}
+
+impl<R: Read> DisplaySetIterator<R> {
+
+    /// Consumes this iterator, returning the underlying input source.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Iterator for DisplaySetIterator<R> {
+
+    type Item = ReadResult<DisplaySet>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+
+        if self.failed {
+            return None
+        }
+
+        let mut first_byte = [0x00_u8; 1];
+
+        let result = loop {
+            match self.reader.read(&mut first_byte) {
+                Ok(0) => return None,
+                Ok(_) => break (&first_byte[..]).chain(&mut self.reader).read_display_set(),
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => break Err(SegmentReadError::from(err).into()),
+            }
+        };
+
+        self.failed = result.is_err();
+
+        Some(result)
     }
 }
 
 impl DisplaySet {
 
-    fn try_from<'a, T>(value: T) -> ParseResult<Self> where
+    fn try_from<'a, T>(value: T, options: &ReadOptions) -> ParseResult<Self> where
         T: IntoIterator<Item = &'a Segment>
     {
         let mut es = None;
@@ -191,6 +359,8 @@ impl DisplaySet {
         let mut palettes = BTreeMap::<Vid<u8>, Palette>::new();
         let mut objects = BTreeMap::<Vid<u16>, Object>::new();
         let mut composition_objects = BTreeMap::<Cid, CompositionObject>::new();
+        let mut order = Vec::<Cid>::new();
+        let mut unknown_segments = Vec::<UnknownSegment>::new();
         let mut iterator = value.into_iter();
         let pcs = match iterator.next() {
             Some(segment) => {
@@ -204,7 +374,7 @@ impl DisplaySet {
             }
         };
 
-        while let Some(segment) = iterator.next() {
+        for segment in iterator {
 
             if es.is_some() {
                 return Err(ParseError::SegmentAfterEnd)
@@ -284,7 +454,7 @@ impl DisplaySet {
                             Object {
                                 width: sods.width,
                                 height: sods.height,
-                                lines: rle_decompress(&sods.data)?,
+                                lines: rle::decompress_with_width(&sods.data, sods.width)?,
                             },
                         );
                         sequence = Sequence::Single;
@@ -366,12 +536,20 @@ impl DisplaySet {
                                     data.append(&mut mods.data.clone());
                                 }
                                 data.append(&mut fods.data.clone());
+                                if options.strict && iods.length != data.len() + 4 {
+                                    return Err(
+                                        ParseError::InconsistentObjectDataLength {
+                                            declared_length: iods.length,
+                                            actual_length: data.len() + 4,
+                                        }
+                                    )
+                                }
                                 objects.insert(
                                     vid,
                                     Object {
                                         width: iods.width,
                                         height: iods.height,
-                                        lines: rle_decompress(&data)?,
+                                        lines: rle::decompress_with_width(&data, iods.width)?,
                                     },
                                 );
                                 initial_object = None;
@@ -398,6 +576,9 @@ impl DisplaySet {
                     }
                     es = Some(this_es);
                 }
+                Segment::Unknown(us) => {
+                    unknown_segments.push(us.clone());
+                }
             }
         }
 
@@ -405,12 +586,31 @@ impl DisplaySet {
             return Err(ParseError::MissingEndSegment)
         }
 
+        let defines_windows = pcs.composition_state != CompositionState::Normal;
+
         for co in &pcs.composition_objects {
+            if options.strict {
+                if defines_windows && !windows.contains_key(&co.window_id) {
+                    return Err(
+                        ParseError::CompositionReferencesUnknownWindow { window_id: co.window_id }
+                    )
+                }
+                if let (Some(crop), Some(object)) = (&co.crop, latest(&objects, co.object_id)) {
+                    if crop.x as u32 + crop.width as u32 > object.width as u32
+                        || crop.y as u32 + crop.height as u32 > object.height as u32 {
+                        return Err(ParseError::InvalidCropRegion)
+                    }
+                }
+            }
+            let cid = Cid {
+                object_id: co.object_id,
+                window_id: co.window_id,
+            };
+            if !order.contains(&cid) {
+                order.push(cid.clone());
+            }
             composition_objects.insert(
-                Cid {
-                    object_id: co.object_id,
-                    window_id: co.window_id,
-                },
+                cid,
                 CompositionObject {
                     x: co.x,
                     y: co.y,
@@ -424,12 +624,16 @@ impl DisplaySet {
             number: pcs.composition_number,
             state: pcs.composition_state,
             objects: composition_objects,
+            order,
         };
 
-        if pcs.palette_update_only {
-            if !palettes.keys().any(|vid| vid.id == pcs.palette_id) {
-                return Err(ParseError::PaletteUpdateReferencesUnknownPaletteId)
-            }
+        if pcs.palette_update_only && !palettes.keys().any(|vid| vid.id == pcs.palette_id) {
+            return Err(ParseError::PaletteUpdateReferencesUnknownPaletteId)
+        }
+
+        if options.strict && pcs.palette_update_only
+            && (palettes.len() != 1 || !windows.is_empty() || !objects.is_empty()) {
+            return Err(ParseError::MalformedPaletteUpdate)
         }
 
         Ok(
@@ -445,97 +649,21 @@ impl DisplaySet {
                 palettes,
                 objects,
                 composition,
+                unknown_segments,
             }
         )
     }
 }
 
-fn rle_decompress(input: &[u8]) -> ParseResult<Vec<Vec<u8>>> {
+/// Decompresses RLE-encoded object data as far as possible, returning every line that was
+/// completely decoded along with the error, if any, that stopped decoding. Unlike normal reading,
+/// which discards an object entirely upon encountering damaged data, this allows a repair tool
+/// to recover the intact lines and then pad the object out to its declared height.
+pub fn decompress_lenient(input: &[u8]) -> (Vec<Vec<u8>>, Option<ParseError>) {
 
     let mut output = Vec::<Vec<u8>>::new();
-    let mut line = vec![];
-    let mut iter = input.iter();
-
-    loop {
-        match iter.next() {
-            Some(byte_1) => {
-                if *byte_1 == 0x00 {
-                    match iter.next() {
-                        Some(byte_2) => {
-                            if *byte_2 == 0x00 {
-                                output.push(line);
-                                line = vec![];
-                            } else if *byte_2 >> 6 == 0 {
-                                for _ in 0..(*byte_2 & 0x3F) {
-                                    line.push(0);
-                                }
-                            } else if *byte_2 >> 6 == 1 {
-                                match iter.next() {
-                                    Some(byte_3) => {
-                                        for _ in 0..(
-                                            (*byte_2 as u16 & 0x3F) << 8
-                                            | *byte_3 as u16
-                                        ) {
-                                            line.push(0);
-                                        }
-                                    }
-                                    None => {
-                                        return Err(ParseError::IncompleteRleSequence)
-                                    }
-                                }
-                            } else if *byte_2 >> 6 == 2 {
-                                match iter.next() {
-                                    Some(byte_3) => {
-                                        for _ in 0..(*byte_2 & 0x3F) {
-                                            line.push(*byte_3);
-                                        }
-                                    }
-                                    None => {
-                                        return Err(ParseError::IncompleteRleSequence)
-                                    }
-                                }
-                            } else if *byte_2 >> 6 == 3 {
-                                match iter.next() {
-                                    Some(byte_3) => {
-                                        match iter.next() {
-                                            Some(byte_4) => {
-                                                for _ in 0..(
-                                                    (*byte_2 as u16 & 0x3F) << 8
-                                                    | *byte_3 as u16
-                                                ) {
-                                                    line.push(*byte_4);
-                                                }
-                                            }
-                                            None => {
-                                                return Err(ParseError::IncompleteRleSequence)
-                                            }
-                                        }
-                                    }
-                                    None => {
-                                        return Err(ParseError::IncompleteRleSequence)
-                                    }
-                                }
-                            } else {
-                                return Err(ParseError::InvalidRleSequence)
-                            }
-                        }
-                        None => {
-                            return Err(ParseError::IncompleteRleSequence)
-                        }
-                    }
-                } else {
-                    line.push(*byte_1);
-                }
-            }
-            None => {
-                break
-            }
-        }
-    }
+    let result = rle::decompress_into(input, 0, &mut output);
 
-    if !line.is_empty() {
-        return Err(ParseError::IncompleteRleLine)
-    }
-
-    Ok(output)
+    (output, result.err().map(ParseError::from))
 }
+
@@ -10,6 +10,7 @@
 
 use super::{
     DisplaySet,
+    super::rle,
     super::segment::{
         CompositionObject,
         EndSegment,
@@ -20,6 +21,7 @@ use super::{
         PaletteEntry,
         PresentationCompositionSegment,
         SingleObjectDefinitionSegment,
+        UnknownSegment,
         WindowDefinition,
         WindowDefinitionSegment,
         WriteError as SegmentWriteError,
@@ -52,12 +54,62 @@ pub enum WriteError {
     /// pixels.
     #[error("object line too long")]
     ObjectLineTooLong,
+    /// Segments are to be [aligned](WriteOptions::align) using `0x50` as the
+    /// [pad byte](WriteOptions::pad_byte). As this begins the magic number of each segment, a
+    /// reader skipping padding would mistake it for the start of a segment.
+    #[error("invalid pad byte {pad_byte:#04X}")]
+    InvalidPadByte {
+        /// The pad byte that was specified.
+        pad_byte: u8,
+    },
+}
+
+/// Defines options that control how display sets are written.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct WriteOptions {
+    /// Forces the DTS of every generated segment to zero, regardless of the DTS stored in the
+    /// display set. Some players misbehave when encountering a nonzero DTS.
+    pub zero_dts: bool,
+    /// Splits objects that are too large for a single object definition segment (ODS) only
+    /// where an RLE line ends, so that each portion can be decoded independently of those
+    /// following it. This matches how some hardware decoders stream objects. Should a single
+    /// line be too large to fit within one portion, it is split wherever necessary.
+    pub split_on_line_boundary: bool,
+    /// Pads each segment with [pad bytes](Self::pad_byte) so that its length is a multiple of
+    /// the specified number of bytes. Segments written to a stream that begins aligned are
+    /// therefore aligned as well. This accommodates certain players and muxers that expect such
+    /// alignment, but the output is nonstandard and must be read with
+    /// [skip_padding](super::super::segment::ReadOptions::skip_padding) enabled. An alignment
+    /// of zero or one adds no padding.
+    pub align: Option<usize>,
+    /// The byte value used when [aligning segments](Self::align). This is `0xFF` by default and
+    /// must not be `0x50`, which begins the magic number of each segment; writing aligned
+    /// segments with it fails with [WriteError::InvalidPadByte].
+    pub pad_byte: u8,
+}
+
+impl Default for WriteOptions {
+
+    fn default() -> Self {
+        WriteOptions {
+            zero_dts: false,
+            split_on_line_boundary: false,
+            align: None,
+            pad_byte: 0xFF,
+        }
+    }
 }
 
 /// Allows writing display sets to a sink.
 pub trait WriteDisplaySetExt {
     /// Writes the next display set to a sink.
     fn write_display_set(&mut self, display_set: DisplaySet) -> WriteResult<()>;
+    /// Writes the next display set to a sink using the specified options.
+    fn write_display_set_with_options(
+        &mut self,
+        display_set: DisplaySet,
+        options: &WriteOptions,
+    ) -> WriteResult<()>;
 }
 
 impl<T> WriteDisplaySetExt for T where
@@ -65,11 +117,33 @@ impl<T> WriteDisplaySetExt for T where
 {
 
     fn write_display_set(&mut self, display_set: DisplaySet) -> WriteResult<()> {
+        self.write_display_set_with_options(display_set, &WriteOptions::default())
+    }
+
+    fn write_display_set_with_options(
+        &mut self,
+        display_set: DisplaySet,
+        options: &WriteOptions,
+    ) -> WriteResult<()> {
+
+        if matches!(options.align, Some(align) if align > 1) && options.pad_byte == 0x50 {
+            return Err(WriteError::InvalidPadByte { pad_byte: options.pad_byte })
+        }
 
-        let segments = display_set.to_segments()?;
+        let segments = display_set.to_segments(options)?;
 
         for segment in segments.into_iter() {
-            self.write_segment(&segment)?;
+            match options.align {
+                Some(align) if align > 1 => {
+                    let mut buffer = vec![];
+                    buffer.write_segment(&segment)?;
+                    buffer.resize(buffer.len().next_multiple_of(align), options.pad_byte);
+                    self.write_all(&buffer).map_err(SegmentWriteError::from)?;
+                }
+                _ => {
+                    self.write_segment(&segment)?;
+                }
+            }
         }
 
         Ok(())
@@ -78,14 +152,15 @@ impl<T> WriteDisplaySetExt for T where
 
 impl DisplaySet {
 
-    fn to_segments(&self) -> WriteResult<Vec<Segment>> {
+    fn to_segments(&self, options: &WriteOptions) -> WriteResult<Vec<Segment>> {
 
+        let dts = if options.zero_dts { 0 } else { self.dts };
         let mut segments = Vec::<Segment>::new();
 
         segments.push(Segment::PresentationComposition(
             PresentationCompositionSegment {
                 pts: self.pts,
-                dts: self.dts,
+                dts,
                 width: self.width,
                 height: self.height,
                 frame_rate: self.frame_rate,
@@ -93,7 +168,7 @@ impl DisplaySet {
                 composition_state: self.composition.state,
                 palette_update_only: self.palete_update_only,
                 palette_id: self.palette_id,
-                composition_objects: self.composition.objects.iter().map(|(cid, co)|
+                composition_objects: self.composition.ordered_objects().into_iter().map(|(cid, co)|
                     CompositionObject {
                         object_id: cid.object_id,
                         window_id: cid.window_id,
@@ -110,7 +185,7 @@ impl DisplaySet {
             segments.push(Segment::WindowDefinition(
                 WindowDefinitionSegment {
                     pts: self.pts,
-                    dts: self.dts,
+                    dts,
                     windows: self.windows.iter().map(|(&window_id, window)|
                         WindowDefinition {
                             id: window_id,
@@ -128,7 +203,7 @@ impl DisplaySet {
             segments.push(Segment::PaletteDefinition(
                 PaletteDefinitionSegment {
                     pts: self.pts,
-                    dts: self.dts,
+                    dts,
                     id: vid.id,
                     version: vid.version,
                     entries: palette.entries.iter().map(|(&id, entry)|
@@ -146,42 +221,45 @@ impl DisplaySet {
 
         for (vid, object) in &self.objects {
 
-            let data = rle_compress(&object.lines)?;
+            let (data, line_ends) = rle::compress_with_line_ends(&object.lines)
+                .map_err(|_| WriteError::ObjectLineTooLong)?;
             let mut index = 0;
             let mut size = data.len();
 
             if size > IODS_DATA_SIZE {
+                let end = split_point(&line_ends, index, IODS_DATA_SIZE, options);
                 segments.push(Segment::InitialObjectDefinition(
                     InitialObjectDefinitionSegment {
                         pts: self.pts,
-                        dts: self.dts,
+                        dts,
                         id: vid.id,
                         version: vid.version,
                         width: object.width,
                         height: object.height,
                         length: data.len() + 4,
-                        data: Vec::from(&data[..IODS_DATA_SIZE]),
+                        data: Vec::from(&data[..end]),
                     }
                 ));
-                index += IODS_DATA_SIZE;
-                size -= IODS_DATA_SIZE;
+                size -= end - index;
+                index = end;
                 while size > MODS_DATA_SIZE {
+                    let end = split_point(&line_ends, index, MODS_DATA_SIZE, options);
                     segments.push(Segment::MiddleObjectDefinition(
                         MiddleObjectDefinitionSegment {
                             pts: self.pts,
-                            dts: self.dts,
+                            dts,
                             id: vid.id,
                             version: vid.version,
-                            data: Vec::from(&data[index..(index + MODS_DATA_SIZE)]),
+                            data: Vec::from(&data[index..end]),
                         }
                     ));
-                    index += MODS_DATA_SIZE;
-                    size -= MODS_DATA_SIZE;
+                    size -= end - index;
+                    index = end;
                 }
                 segments.push(Segment::FinalObjectDefinition(
                     FinalObjectDefinitionSegment {
                         pts: self.pts,
-                        dts: self.dts,
+                        dts,
                         id: vid.id,
                         version: vid.version,
                         data: Vec::from(&data[index..]),
@@ -191,7 +269,7 @@ impl DisplaySet {
                 segments.push(Segment::SingleObjectDefinition(
                     SingleObjectDefinitionSegment {
                         pts: self.pts,
-                        dts: self.dts,
+                        dts,
                         id: vid.id,
                         version: vid.version,
                         width: object.width,
@@ -202,10 +280,20 @@ impl DisplaySet {
             }
         }
 
+        for us in &self.unknown_segments {
+            segments.push(Segment::Unknown(
+                UnknownSegment {
+                    pts: self.pts,
+                    dts,
+                    ..us.clone()
+                }
+            ));
+        }
+
         segments.push(Segment::End(
             EndSegment {
                 pts: self.pts,
-                dts: self.dts,
+                dts,
             }
         ));
 
@@ -213,85 +301,20 @@ impl DisplaySet {
     }
 }
 
-fn rle_compress(input: &Vec<Vec<u8>>) -> WriteResult<Vec<u8>> {
-
-    let mut output = Vec::<u8>::new();
-    let mut byte = 0_u8;
-    let mut count = 0_usize;
+/// Determines where the portion of object data beginning at `index` should end, given the
+/// maximum size of that portion. Line boundaries are only honored when requested.
+fn split_point(line_ends: &[usize], index: usize, max_size: usize, options: &WriteOptions)
+    -> usize {
 
-    for line in input {
+    let end = index + max_size;
 
-        for next_byte in line {
-            if *next_byte == byte {
-                count += 1;
-            } else {
-                if count > 0 {
-                    output_rle_sequence(&mut output, byte, count)?;
-                }
-                byte = *next_byte;
-                count = 1;
-            }
-        }
-
-        output_rle_sequence(&mut output, byte, count)?;
-        byte = 0;
-        count = 0;
-
-        output.push(0x00);
-        output.push(0x00);
-    }
-
-    Ok(output)
-}
-
-fn output_rle_sequence(output: &mut Vec<u8>, byte: u8, count: usize) -> WriteResult<()> {
-
-    if byte == 0x00 {
-        match count {
-            0 => {
-                //panic!("attempted to handle zero-byte sequence in PGS line")
-            }
-            1 ..= 63 => {
-                output.push(0x00);
-                output.push(count as u8);
-            }
-            64 ..= 16_383 => {
-                output.push(0x00);
-                output.push(0x40 | (count >> 8) as u8);
-                output.push((count & 0xFF) as u8);
-            }
-            _ => {
-                return Err(WriteError::ObjectLineTooLong)
-            }
-        }
+    if options.split_on_line_boundary {
+        line_ends.iter()
+            .rev()
+            .find(|&&line_end| line_end > index && line_end <= end)
+            .copied()
+            .unwrap_or(end)
     } else {
-        match count {
-            0 => {
-                //panic!("attempted to handle zero-byte sequence in PGS line")
-            }
-            1 => {
-                output.push(byte);
-            }
-            2 => {
-                output.push(byte);
-                output.push(byte);
-            }
-            3 ..= 63 => {
-                output.push(0x00);
-                output.push(0x80 | count as u8);
-                output.push(byte);
-            }
-            64 ..= 16_383 => {
-                output.push(0x00);
-                output.push(0xC0 | (count >> 8) as u8);
-                output.push((count & 0xFF) as u8);
-                output.push(byte);
-            }
-            _ => {
-                return Err(WriteError::ObjectLineTooLong)
-            }
-        }
+        end
     }
-
-    Ok(())
 }
@@ -0,0 +1,272 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * To the extent possible under law, the person who associated CC0 with this file has waived all
+ * copyright and related or neighboring rights to this file.
+ *
+ * You should have received a copy of the CC0 legalcode along with this work. If not, see
+ * <http://creativecommons.org/publicdomain/zero/1.0/>.
+ *
+ * SPDX-License-Identifier: CC0-1.0
+ */
+
+//! Verifies the handling of object data lengths, which PGS records with four extra bytes:
+//!
+//! - A single-part ODS implicitly declares a data length of its data plus four, which the reader
+//!   checks against the segment size.
+//! - An initial ODS explicitly declares the data length of all portions combined plus four.
+//! - Middle and final ODS's declare no data length at all, so their data is sized solely by the
+//!   segment size.
+//!
+//! In every case, the extra four bytes account for the object's dimensions.
+
+use super::{
+    *,
+    super::segment::{
+        EndSegment,
+        FinalObjectDefinitionSegment,
+        InitialObjectDefinitionSegment,
+        MiddleObjectDefinitionSegment,
+        PresentationCompositionSegment,
+        ReadSegmentExt,
+        Segment,
+        SingleObjectDefinitionSegment,
+        WriteSegmentExt,
+        recompute_iods_length,
+    },
+    displaysetread::ReadDisplaySetExt,
+    displaysetwrite::WriteDisplaySetExt,
+};
+use std::io::Cursor;
+use rand::{thread_rng, Rng, RngCore};
+
+fn written(segment: &Segment) -> Vec<u8> {
+
+    let mut buffer = vec![];
+
+    buffer.write_segment(segment).unwrap();
+
+    buffer
+}
+
+fn field(segment: &Segment, buffer: &[u8], name: &str) -> u32 {
+
+    let layout = segment.describe_layout();
+    let field = layout.iter().find(|field| field.name == name).unwrap();
+
+    buffer[field.offset..field.offset + field.length].iter()
+        .fold(0, |value, &byte| value << 8 | byte as u32)
+}
+
+fn random_data(length: usize) -> Vec<u8> {
+
+    let mut data = vec![0x00_u8; length];
+
+    thread_rng().fill_bytes(&mut data);
+
+    data
+}
+
+#[test]
+fn test_sods_data_length() {
+
+    for length in [0, 1, 100, 65_508] {
+
+        let segment = Segment::SingleObjectDefinition(
+            SingleObjectDefinitionSegment {
+                id: 1,
+                width: 8,
+                height: 8,
+                data: random_data(length),
+                ..Default::default()
+            }
+        );
+        let buffer = written(&segment);
+
+        assert_eq!(field(&segment, &buffer, "size"), length as u32 + 11);
+        assert_eq!(field(&segment, &buffer, "data_length"), length as u32 + 4);
+        assert_eq!(Cursor::new(buffer).read_segment().unwrap(), segment);
+    }
+}
+
+#[test]
+fn test_iods_data_length() {
+
+    for (length, declared_length) in [(0, 4), (100, 304), (65_508, 200_004)] {
+
+        let segment = Segment::InitialObjectDefinition(
+            InitialObjectDefinitionSegment {
+                id: 1,
+                length: declared_length,
+                width: 8,
+                height: 8,
+                data: random_data(length),
+                ..Default::default()
+            }
+        );
+        let buffer = written(&segment);
+
+        assert_eq!(field(&segment, &buffer, "size"), length as u32 + 11);
+        assert_eq!(field(&segment, &buffer, "data_length"), declared_length as u32);
+        assert_eq!(Cursor::new(buffer).read_segment().unwrap(), segment);
+    }
+}
+
+#[test]
+fn test_mods_fods_size() {
+
+    for length in [0, 1, 100, 65_515] {
+
+        let segments = [
+            Segment::MiddleObjectDefinition(
+                MiddleObjectDefinitionSegment {
+                    id: 1,
+                    data: random_data(length),
+                    ..Default::default()
+                }
+            ),
+            Segment::FinalObjectDefinition(
+                FinalObjectDefinitionSegment {
+                    id: 1,
+                    data: random_data(length),
+                    ..Default::default()
+                }
+            ),
+        ];
+
+        for segment in segments {
+
+            let buffer = written(&segment);
+
+            assert_eq!(field(&segment, &buffer, "size"), length as u32 + 4);
+            assert_eq!(Cursor::new(buffer).read_segment().unwrap(), segment);
+        }
+    }
+}
+
+#[test]
+fn test_multi_part_object_length() {
+
+    let mut rng = thread_rng();
+    let mut display_set = DisplaySet {
+        width: 1920,
+        height: 1080,
+        frame_rate: 0x10,
+        ..Default::default()
+    };
+
+    display_set.objects.insert(
+        Vid {
+            id: 3,
+            version: 1,
+        },
+        Object {
+            width: 1_000,
+            height: 200,
+            lines: (0..200)
+                .map(|_| (0..1_000).map(|_| rng.gen_range(1..=255)).collect())
+                .collect(),
+        },
+    );
+
+    let mut buffer = vec![];
+
+    buffer.write_display_set(display_set.clone()).unwrap();
+
+    let mut cursor = Cursor::new(&buffer);
+    let mut iods = None::<InitialObjectDefinitionSegment>;
+    let mut mods = Vec::<MiddleObjectDefinitionSegment>::new();
+    let mut fods = None::<FinalObjectDefinitionSegment>;
+
+    while (cursor.position() as usize) < buffer.len() {
+        match cursor.read_segment().unwrap() {
+            Segment::InitialObjectDefinition(segment) => iods = Some(segment),
+            Segment::MiddleObjectDefinition(segment) => mods.push(segment),
+            Segment::FinalObjectDefinition(segment) => fods = Some(segment),
+            _ => {}
+        }
+    }
+
+    let iods = iods.unwrap();
+    let fods = fods.unwrap();
+    let total_length = iods.data.len()
+        + mods.iter().map(|mods| mods.data.len()).sum::<usize>()
+        + fods.data.len();
+    let mut recomputed = InitialObjectDefinitionSegment {
+        length: 0,
+        ..iods.clone()
+    };
+
+    recompute_iods_length(&mut recomputed, &mods, &fods);
+
+    assert!(!mods.is_empty());
+    assert_eq!(iods.length, total_length + 4);
+    assert_eq!(recomputed, iods);
+    assert_eq!(Cursor::new(buffer).read_display_set().unwrap(), display_set);
+}
+
+#[test]
+fn test_multi_part_object_length_mismatch() {
+
+    for (declared_length, valid) in [(10, true), (6, false), (11, false)] {
+
+        let mut buffer = vec![];
+
+        for segment in [
+            Segment::PresentationComposition(
+                PresentationCompositionSegment {
+                    width: 1920,
+                    height: 1080,
+                    frame_rate: 0x10,
+                    ..Default::default()
+                }
+            ),
+            Segment::InitialObjectDefinition(
+                InitialObjectDefinitionSegment {
+                    id: 7,
+                    length: declared_length,
+                    width: 4,
+                    height: 1,
+                    data: vec![0x01, 0x01, 0x01, 0x01],
+                    ..Default::default()
+                }
+            ),
+            Segment::FinalObjectDefinition(
+                FinalObjectDefinitionSegment {
+                    id: 7,
+                    data: vec![0x00, 0x00],
+                    ..Default::default()
+                }
+            ),
+            Segment::End(EndSegment::default()),
+        ] {
+            buffer.write_segment(&segment).unwrap();
+        }
+
+        let strict = ReadOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let lenient = Cursor::new(&buffer).read_display_set().unwrap();
+        let result = Cursor::new(&buffer).read_display_set_with_options(&strict);
+
+        assert_eq!(lenient.objects[&Vid { id: 7, version: 0 }].lines, vec![vec![1, 1, 1, 1]]);
+
+        if valid {
+            assert_eq!(
+                result.unwrap().objects[&Vid { id: 7, version: 0 }].lines,
+                vec![vec![1, 1, 1, 1]],
+            );
+        } else {
+            assert!(matches!(
+                result,
+                Err(ReadError::ParseError {
+                    source: ParseError::InconsistentObjectDataLength {
+                        declared_length: _,
+                        actual_length: 10,
+                    },
+                }),
+            ));
+        }
+    }
+}
@@ -12,12 +12,23 @@
 
 use super::{
     *,
-    super::segment::{CompositionState, Crop},
+    super::segment::{
+        CompositionState,
+        Crop,
+        InitialObjectDefinitionSegment,
+        PresentationCompositionSegment,
+        ReadError as SegmentReadError,
+        ReadOptions as SegmentReadOptions,
+        ReadSegmentExt,
+        Segment,
+        WriteSegmentExt,
+    },
+    super::{epoch::{Epoch, EpochContext}, render::render_frame},
     displaysetread::ReadDisplaySetExt,
     displaysetwrite::WriteDisplaySetExt,
 };
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashSet},
     io::Cursor,
 };
 use rand::{thread_rng, Rng};
@@ -43,7 +54,9 @@ fn test_ds_cycle_empty() {
             number: rng.gen(),
             state: CompositionState::EpochStart,
             objects: BTreeMap::<Cid, CompositionObject>::new(),
+            order: vec![],
         },
+        unknown_segments: vec![],
     };
 
     buffer.write_display_set(display_set.clone()).unwrap();
@@ -52,6 +65,7 @@ fn test_ds_cycle_empty() {
     let cycled_display_set = cursor.read_display_set().unwrap();
 
     assert_eq!(cycled_display_set, display_set);
+    assert_eq!(roundtrip(&display_set).unwrap(), display_set);
 }
 
 #[test]
@@ -311,7 +325,9 @@ fn test_ds_cycle_not_empty() {
             number: rng.gen(),
             state: CompositionState::EpochStart,
             objects: composition_objects,
+            order: vec![],
         },
+        unknown_segments: vec![],
     };
 
     buffer.write_display_set(display_set.clone()).unwrap();
@@ -321,3 +337,1920 @@ fn test_ds_cycle_not_empty() {
 
     assert_eq!(cycled_display_set, display_set);
 }
+
+#[test]
+fn test_ds_unknown_segments_preserved() {
+
+    let mut display_set = DisplaySet {
+        pts: 90_000,
+        dts: 45_000,
+        width: 1920,
+        height: 1080,
+        frame_rate: 0x10,
+        ..Default::default()
+    };
+
+    display_set.unknown_segments.push(
+        UnknownSegment {
+            pts: 90_000,
+            dts: 45_000,
+            kind: 0x42,
+            data: vec![0x01, 0x02, 0x03],
+        }
+    );
+
+    let mut buffer = vec![];
+
+    buffer.write_display_set(display_set.clone()).unwrap();
+
+    let options = ReadOptions {
+        segment: SegmentReadOptions {
+            preserve_unknown: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let cycled_display_set = Cursor::new(&buffer).read_display_set_with_options(&options).unwrap();
+    let mut cycled_buffer = vec![];
+
+    cycled_buffer.write_display_set(cycled_display_set.clone()).unwrap();
+
+    assert_eq!(cycled_display_set, display_set);
+    assert_eq!(cycled_buffer, buffer);
+    assert!(Cursor::new(&buffer).read_display_set().is_err());
+}
+
+#[test]
+fn test_object_content_eq_ignores_version() {
+
+    let mut objects = BTreeMap::<Vid<u16>, Object>::new();
+
+    objects.insert(
+        Vid {
+            id: 1,
+            version: 1,
+        },
+        Object {
+            width: 3,
+            height: 2,
+            lines: vec![vec![1, 1, 1], vec![0, 2, 0]],
+        },
+    );
+    objects.insert(
+        Vid {
+            id: 1,
+            version: 2,
+        },
+        Object {
+            width: 3,
+            height: 2,
+            lines: vec![vec![1, 1, 1], vec![0, 2, 0]],
+        },
+    );
+    objects.insert(
+        Vid {
+            id: 1,
+            version: 3,
+        },
+        Object {
+            width: 3,
+            height: 2,
+            lines: vec![vec![1, 1, 1], vec![0, 3, 0]],
+        },
+    );
+
+    let first = &objects[&Vid { id: 1, version: 1 }];
+    let second = &objects[&Vid { id: 1, version: 2 }];
+    let third = &objects[&Vid { id: 1, version: 3 }];
+
+    assert!(object_content_eq(first, second));
+    assert!(!object_content_eq(first, third));
+}
+
+#[test]
+fn test_palette_timeline() {
+
+    let palette = |y: u8| {
+        let mut entries = BTreeMap::<u8, PaletteEntry>::new();
+        entries.insert(
+            1,
+            PaletteEntry {
+                y,
+                cr: 128,
+                cb: 128,
+                alpha: 255,
+            },
+        );
+        Palette { entries }
+    };
+    let mut start = DisplaySet {
+        pts: 1_000,
+        ..Default::default()
+    };
+    let mut update = DisplaySet {
+        pts: 2_000,
+        palete_update_only: true,
+        palette_id: 0,
+        ..Default::default()
+    };
+    let clear = DisplaySet {
+        pts: 3_000,
+        ..Default::default()
+    };
+
+    start.palettes.insert(Vid { id: 1, version: 0 }, palette(16));
+    start.palettes.insert(Vid { id: 0, version: 0 }, palette(235));
+    update.palettes.insert(Vid { id: 0, version: 1 }, palette(128));
+
+    let timeline = palette_timeline(&[update.clone(), start.clone(), clear]);
+
+    assert_eq!(
+        timeline,
+        vec![
+            (1_000, 0, palette(235)),
+            (1_000, 1, palette(16)),
+            (2_000, 0, palette(128)),
+        ],
+    );
+}
+
+#[test]
+fn test_screen_clear_kind() {
+
+    let mut display_set = DisplaySet {
+        composition: Composition {
+            state: CompositionState::Normal,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    assert_eq!(display_set.screen_clear_kind(), Some(ScreenClearKind::NoWindows));
+    assert!(display_set.clears_screen());
+
+    display_set.windows.insert(
+        0,
+        Window {
+            x: 0,
+            y: 0,
+            width: 100,
+            height: 100,
+        },
+    );
+
+    assert_eq!(display_set.screen_clear_kind(), Some(ScreenClearKind::CompositionEmpty));
+    assert!(display_set.clears_screen());
+
+    display_set.composition.objects.insert(
+        Cid {
+            object_id: 0,
+            window_id: 0,
+        },
+        CompositionObject::default(),
+    );
+
+    assert_eq!(display_set.screen_clear_kind(), None);
+    assert!(!display_set.clears_screen());
+
+    display_set.composition.objects.clear();
+    display_set.palete_update_only = true;
+
+    assert_eq!(display_set.screen_clear_kind(), None);
+    assert!(!display_set.clears_screen());
+}
+
+fn cropped_display_set(crop: Crop, include_object: bool) -> DisplaySet {
+
+    let mut display_set = DisplaySet {
+        width: 1920,
+        height: 1080,
+        ..Default::default()
+    };
+
+    display_set.windows.insert(
+        0,
+        Window {
+            x: 0,
+            y: 0,
+            width: 100,
+            height: 100,
+        },
+    );
+    display_set.composition.objects.insert(
+        Cid {
+            object_id: 1,
+            window_id: 0,
+        },
+        CompositionObject {
+            x: 0,
+            y: 0,
+            forced: false,
+            crop: Some(crop),
+        },
+    );
+    display_set.composition.order.push(Cid { object_id: 1, window_id: 0 });
+
+    if include_object {
+        display_set.objects.insert(
+            Vid {
+                id: 1,
+                version: 0,
+            },
+            Object {
+                width: 4,
+                height: 2,
+                lines: vec![vec![1, 1, 1, 1], vec![1, 1, 1, 1]],
+            },
+        );
+    }
+
+    display_set
+}
+
+fn simple_display_set() -> DisplaySet {
+    cropped_display_set(Crop { x: 0, y: 0, width: 4, height: 2 }, true)
+}
+
+#[test]
+fn test_ds_strict_crop_within_object() {
+
+    let mut buffer = vec![];
+    let display_set = cropped_display_set(Crop { x: 1, y: 1, width: 3, height: 1 }, true);
+
+    buffer.write_display_set(display_set.clone()).unwrap();
+
+    let mut cursor = Cursor::new(buffer);
+    let options = ReadOptions { strict: true, ..Default::default() };
+
+    assert_eq!(cursor.read_display_set_with_options(&options).unwrap(), display_set);
+}
+
+#[test]
+fn test_ds_strict_crop_out_of_bounds() {
+
+    let mut buffer = vec![];
+    let display_set = cropped_display_set(Crop { x: 2, y: 0, width: 3, height: 2 }, true);
+
+    buffer.write_display_set(display_set.clone()).unwrap();
+
+    let options = ReadOptions { strict: true, ..Default::default() };
+
+    assert!(matches!(
+        Cursor::new(buffer.clone()).read_display_set_with_options(&options),
+        Err(ReadError::ParseError { source: ParseError::InvalidCropRegion }),
+    ));
+    assert_eq!(Cursor::new(buffer).read_display_set().unwrap(), display_set);
+}
+
+#[test]
+fn test_ds_strict_crop_object_not_in_ds() {
+
+    let mut buffer = vec![];
+    let display_set = cropped_display_set(Crop { x: 2, y: 0, width: 3, height: 2 }, false);
+
+    buffer.write_display_set(display_set.clone()).unwrap();
+
+    let mut cursor = Cursor::new(buffer);
+    let options = ReadOptions { strict: true, ..Default::default() };
+
+    assert_eq!(cursor.read_display_set_with_options(&options).unwrap(), display_set);
+}
+
+fn palette_update_display_set() -> DisplaySet {
+
+    let mut display_set = DisplaySet {
+        width: 1920,
+        height: 1080,
+        palete_update_only: true,
+        palette_id: 1,
+        ..Default::default()
+    };
+
+    display_set.palettes.insert(Vid { id: 1, version: 3 }, Palette::default());
+    display_set
+}
+
+#[test]
+fn test_ds_strict_palette_update() {
+
+    let mut buffer = vec![];
+    let display_set = palette_update_display_set();
+
+    buffer.write_display_set(display_set.clone()).unwrap();
+
+    let options = ReadOptions { strict: true, ..Default::default() };
+
+    assert_eq!(
+        Cursor::new(buffer).read_display_set_with_options(&options).unwrap(),
+        display_set,
+    );
+}
+
+#[test]
+fn test_ds_strict_palette_update_with_object() {
+
+    let mut buffer = vec![];
+    let mut display_set = palette_update_display_set();
+
+    display_set.objects.insert(
+        Vid {
+            id: 1,
+            version: 0,
+        },
+        Object {
+            width: 4,
+            height: 2,
+            lines: vec![vec![1; 4]; 2],
+        },
+    );
+    buffer.write_display_set(display_set.clone()).unwrap();
+
+    let options = ReadOptions { strict: true, ..Default::default() };
+
+    assert!(matches!(
+        Cursor::new(buffer.clone()).read_display_set_with_options(&options),
+        Err(ReadError::ParseError { source: ParseError::MalformedPaletteUpdate }),
+    ));
+    assert_eq!(Cursor::new(buffer).read_display_set().unwrap(), display_set);
+}
+
+#[test]
+fn test_ds_strict_known_window() {
+
+    let mut buffer = vec![];
+    let mut display_set = simple_display_set();
+
+    display_set.composition.state = CompositionState::AcquisitionPoint;
+    buffer.write_display_set(display_set.clone()).unwrap();
+
+    let options = ReadOptions { strict: true, ..Default::default() };
+    let cycled = Cursor::new(buffer).read_display_set_with_options(&options).unwrap();
+
+    assert_eq!(cycled, display_set);
+    assert!(cycled.unresolved_windows().is_empty());
+}
+
+#[test]
+fn test_ds_strict_unknown_window() {
+
+    let mut buffer = vec![];
+    let mut display_set = simple_display_set();
+
+    display_set.windows.clear();
+    display_set.windows.insert(3, Window::default());
+    buffer.write_display_set(display_set.clone()).unwrap();
+
+    let options = ReadOptions { strict: true, ..Default::default() };
+
+    assert!(matches!(
+        Cursor::new(buffer.clone()).read_display_set_with_options(&options),
+        Err(ReadError::ParseError {
+            source: ParseError::CompositionReferencesUnknownWindow { window_id: 0 },
+        }),
+    ));
+    assert_eq!(display_set.unresolved_windows(), vec![0]);
+
+    display_set.composition.state = CompositionState::Normal;
+    buffer.clear();
+    buffer.write_display_set(display_set.clone()).unwrap();
+
+    let cycled = Cursor::new(buffer).read_display_set_with_options(&options).unwrap();
+
+    assert_eq!(cycled, display_set);
+    assert_eq!(cycled.unresolved_windows(), vec![0]);
+}
+
+#[test]
+fn test_ds_read_at() {
+
+    let mut buffer = vec![];
+    let first = simple_display_set();
+    let second = DisplaySet {
+        pts: 90_000,
+        width: 1920,
+        height: 1080,
+        ..Default::default()
+    };
+
+    buffer.write_display_set(first.clone()).unwrap();
+
+    let second_offset = buffer.len() as u64;
+
+    buffer.write_display_set(second.clone()).unwrap();
+
+    let mut cursor = Cursor::new(buffer);
+
+    assert_eq!(cursor.read_display_set_at().unwrap(), (0, first));
+    assert_eq!(cursor.read_display_set_at().unwrap(), (second_offset, second));
+}
+
+#[test]
+fn test_display_sets_iterator() {
+
+    let mut buffer = vec![];
+    let first = simple_display_set();
+    let second = DisplaySet {
+        pts: 90_000,
+        width: 1920,
+        height: 1080,
+        ..Default::default()
+    };
+
+    buffer.write_display_set(first.clone()).unwrap();
+    buffer.write_display_set(second.clone()).unwrap();
+
+    let read = display_sets(buffer.as_slice())
+        .collect::<ReadResult<Vec<DisplaySet>>>()
+        .unwrap();
+
+    assert_eq!(read, vec![first, second]);
+    assert_eq!(display_sets(&[][..]).count(), 0);
+}
+
+#[test]
+fn test_display_sets_iterator_truncated() {
+
+    let mut buffer = vec![];
+    let display_set = simple_display_set();
+
+    buffer.write_display_set(display_set.clone()).unwrap();
+    buffer.write_display_set(display_set.clone()).unwrap();
+    buffer.truncate(buffer.len() - 5);
+
+    let mut iterator = display_sets(buffer.as_slice());
+
+    assert_eq!(iterator.next().unwrap().unwrap(), display_set);
+    assert!(
+        matches!(
+            iterator.next(),
+            Some(Err(ReadError::ReadError { source: SegmentReadError::IoError { .. } })),
+        )
+    );
+    assert!(iterator.next().is_none());
+}
+
+#[test]
+fn test_ds_write_zero_dts() {
+
+    let mut buffer = vec![];
+    let mut display_set = simple_display_set();
+
+    display_set.pts = 90_000;
+    display_set.dts = 45_000;
+    buffer.write_display_set_with_options(
+        display_set.clone(),
+        &WriteOptions { zero_dts: true, ..Default::default() },
+    ).unwrap();
+
+    let mut cursor = Cursor::new(buffer);
+    let mut count = 0;
+
+    while let Ok(segment) = cursor.read_segment() {
+        let dts = match segment {
+            Segment::PresentationComposition(pcs) => pcs.dts,
+            Segment::WindowDefinition(wds) => wds.dts,
+            Segment::PaletteDefinition(pds) => pds.dts,
+            Segment::SingleObjectDefinition(sods) => sods.dts,
+            Segment::InitialObjectDefinition(iods) => iods.dts,
+            Segment::MiddleObjectDefinition(mods) => mods.dts,
+            Segment::FinalObjectDefinition(fods) => fods.dts,
+            Segment::End(es) => es.dts,
+            Segment::Unknown(us) => us.dts,
+        };
+        assert_eq!(dts, 0);
+        count += 1;
+    }
+
+    assert_eq!(count, 4);
+}
+
+#[test]
+fn test_ds_write_align() {
+
+    let mut buffer = vec![];
+    let display_set = simple_display_set();
+    let options = WriteOptions { align: Some(4), ..Default::default() };
+
+    buffer.write_display_set_with_options(display_set.clone(), &options).unwrap();
+    buffer.write_display_set_with_options(display_set.clone(), &options).unwrap();
+
+    let mut unpadded = vec![];
+
+    unpadded.write_display_set(display_set.clone()).unwrap();
+
+    assert_eq!(buffer.len() % 4, 0);
+    assert!(buffer.len() > 2 * unpadded.len());
+    assert!(Cursor::new(&buffer).read_display_set().is_err());
+
+    let mut cursor = Cursor::new(buffer);
+    let read_options = ReadOptions {
+        segment: SegmentReadOptions {
+            skip_padding: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    assert_eq!(cursor.read_display_set_with_options(&read_options).unwrap(), display_set);
+    assert_eq!(cursor.read_display_set_with_options(&read_options).unwrap(), display_set);
+    assert!(cursor.read_display_set_with_options(&read_options).is_err());
+
+    let mut buffer = vec![];
+    let options = WriteOptions { align: Some(4), pad_byte: 0x50, ..Default::default() };
+
+    assert!(matches!(
+        buffer.write_display_set_with_options(display_set, &options),
+        Err(WriteError::InvalidPadByte { pad_byte: 0x50 }),
+    ));
+    assert!(buffer.is_empty());
+}
+
+#[test]
+fn test_ds_write_split_on_line_boundary() {
+
+    let mut rng = thread_rng();
+    let mut buffer = vec![];
+    let mut display_set = simple_display_set();
+    let object = display_set.objects.values_mut().next().unwrap();
+
+    // Nonzero pixels ensure that 0x00 0x00 only appears where a line ends.
+    object.width = 1_000;
+    object.height = 200;
+    object.lines = (0..200).map(|_|
+        (0..1_000).map(|_| rng.gen_range(1..=255)).collect()
+    ).collect();
+    buffer.write_display_set_with_options(
+        display_set.clone(),
+        &WriteOptions { split_on_line_boundary: true, ..Default::default() },
+    ).unwrap();
+
+    let mut cursor = Cursor::new(buffer);
+    let mut portions = vec![];
+
+    while let Ok(segment) = cursor.read_segment() {
+        match segment {
+            Segment::InitialObjectDefinition(iods) => portions.push(iods.data),
+            Segment::MiddleObjectDefinition(mods) => portions.push(mods.data),
+            Segment::FinalObjectDefinition(fods) => portions.push(fods.data),
+            _ => (),
+        }
+    }
+
+    assert!(portions.len() > 2);
+
+    for portion in portions {
+        assert!(portion.ends_with(&[0x00, 0x00]));
+    }
+
+    cursor.set_position(0);
+
+    assert_eq!(cursor.read_display_set().unwrap(), display_set);
+}
+
+#[test]
+fn test_window_fill_ratios() {
+
+    let mut display_set = DisplaySet::default();
+    let mut palettes = BTreeMap::<Vid<u8>, Palette>::new();
+    let mut entries = BTreeMap::<u8, PaletteEntry>::new();
+
+    entries.insert(0, PaletteEntry { y: 16, cr: 128, cb: 128, alpha: 0 });
+    entries.insert(1, PaletteEntry { y: 235, cr: 128, cb: 128, alpha: 255 });
+    palettes.insert(Vid { id: 0, version: 0 }, Palette { entries });
+    display_set.windows.insert(0, Window { x: 10, y: 10, width: 4, height: 4 });
+    display_set.windows.insert(1, Window { x: 100, y: 100, width: 4, height: 4 });
+    display_set.objects.insert(
+        Vid {
+            id: 0,
+            version: 0,
+        },
+        Object {
+            width: 4,
+            height: 2,
+            lines: vec![vec![1, 1, 0, 0], vec![1, 1, 1, 1]],
+        },
+    );
+    display_set.composition.objects.insert(
+        Cid {
+            object_id: 0,
+            window_id: 0,
+        },
+        CompositionObject {
+            x: 10,
+            y: 11,
+            forced: false,
+            crop: None,
+        },
+    );
+
+    let ratios = display_set.window_fill_ratios(&palettes);
+
+    assert_eq!(ratios.len(), 2);
+    assert_eq!(ratios[&0], 6.0 / 16.0);
+    assert_eq!(ratios[&1], 0.0);
+
+    display_set.windows.insert(0, Window { x: 0, y: 0, width: u16::MAX, height: u16::MAX });
+
+    let ratios = display_set.window_fill_ratios(&palettes);
+
+    assert_eq!(ratios[&0], 6.0 / (u16::MAX as f64 * u16::MAX as f64));
+}
+
+fn render_indexes(
+    display_set: &DisplaySet,
+    palettes: &BTreeMap<Vid<u8>, Palette>,
+) -> BTreeMap<(u32, u32), u8> {
+
+    let palette = latest(palettes, display_set.palette_id);
+    let mut pixels = BTreeMap::<(u32, u32), u8>::new();
+
+    for (cid, co) in display_set.composition.ordered_objects() {
+        let window = &display_set.windows[&cid.window_id];
+        let object = latest(&display_set.objects, cid.object_id).unwrap();
+        for (x, y, index) in visible_pixels(co, object) {
+            if is_opaque(palette, index) && window.contains(x, y) {
+                pixels.insert((x, y), index);
+            }
+        }
+    }
+
+    pixels
+}
+
+#[test]
+fn test_shrink_windows_to_content() {
+
+    let mut display_set = DisplaySet::default();
+    let mut palettes = BTreeMap::<Vid<u8>, Palette>::new();
+    let mut entries = BTreeMap::<u8, PaletteEntry>::new();
+
+    entries.insert(0, PaletteEntry { y: 16, cr: 128, cb: 128, alpha: 0 });
+    entries.insert(1, PaletteEntry { y: 235, cr: 128, cb: 128, alpha: 255 });
+    entries.insert(2, PaletteEntry { y: 128, cr: 128, cb: 128, alpha: 128 });
+    palettes.insert(Vid { id: 0, version: 0 }, Palette { entries });
+    display_set.windows.insert(0, Window { x: 0, y: 0, width: 20, height: 20 });
+    display_set.windows.insert(1, Window { x: 50, y: 50, width: 10, height: 10 });
+    display_set.objects.insert(
+        Vid {
+            id: 0,
+            version: 0,
+        },
+        Object {
+            width: 6,
+            height: 4,
+            lines: vec![
+                vec![0, 0, 0, 0, 0, 0],
+                vec![0, 0, 1, 2, 0, 0],
+                vec![0, 1, 1, 1, 0, 0],
+                vec![0, 0, 0, 0, 0, 0],
+            ],
+        },
+    );
+    display_set.objects.insert(
+        Vid {
+            id: 1,
+            version: 0,
+        },
+        Object {
+            width: 3,
+            height: 3,
+            lines: vec![vec![0, 0, 0], vec![0, 0, 1], vec![0, 0, 0]],
+        },
+    );
+    display_set.composition.objects.insert(
+        Cid {
+            object_id: 0,
+            window_id: 0,
+        },
+        CompositionObject {
+            x: 2,
+            y: 3,
+            forced: false,
+            crop: None,
+        },
+    );
+    display_set.composition.objects.insert(
+        Cid {
+            object_id: 1,
+            window_id: 0,
+        },
+        CompositionObject {
+            x: 10,
+            y: 10,
+            forced: false,
+            crop: Some(Crop { x: 1, y: 0, width: 2, height: 3 }),
+        },
+    );
+
+    let rendered = render_indexes(&display_set, &palettes);
+
+    display_set.shrink_windows_to_content(&palettes, false);
+
+    assert_eq!(display_set.windows[&0], Window { x: 3, y: 4, width: 9, height: 8 });
+    assert_eq!(display_set.windows[&1], Window { x: 50, y: 50, width: 10, height: 10 });
+    assert_eq!(render_indexes(&display_set, &palettes), rendered);
+    assert_eq!(display_set.window_fill_ratios(&palettes)[&0], 6.0 / 72.0);
+
+    display_set.shrink_windows_to_content(&palettes, true);
+
+    assert!(!display_set.windows.contains_key(&1));
+    assert_eq!(render_indexes(&display_set, &palettes), rendered);
+}
+
+#[test]
+fn test_ds_composition_order() {
+
+    let mut buffer = vec![];
+    let mut display_set = DisplaySet::default();
+    let back = Cid {
+        object_id: 2,
+        window_id: 0,
+    };
+    let front = Cid {
+        object_id: 1,
+        window_id: 0,
+    };
+
+    display_set.windows.insert(0, Window { x: 0, y: 0, width: 4, height: 1 });
+    display_set.objects.insert(
+        Vid {
+            id: 1,
+            version: 0,
+        },
+        Object {
+            width: 2,
+            height: 1,
+            lines: vec![vec![1, 1]],
+        },
+    );
+    display_set.objects.insert(
+        Vid {
+            id: 2,
+            version: 0,
+        },
+        Object {
+            width: 2,
+            height: 1,
+            lines: vec![vec![2, 2]],
+        },
+    );
+    display_set.composition.objects.insert(front.clone(), CompositionObject::default());
+    display_set.composition.objects.insert(
+        back.clone(),
+        CompositionObject {
+            x: 1,
+            ..Default::default()
+        },
+    );
+    display_set.composition.order = vec![back.clone(), front.clone()];
+
+    assert_eq!(
+        display_set.composition.ordered_objects().into_iter().map(|(cid, _)| cid.clone())
+            .collect::<Vec<Cid>>(),
+        vec![back.clone(), front.clone()],
+    );
+
+    buffer.write_display_set(display_set.clone()).unwrap();
+
+    let mut cursor = Cursor::new(buffer);
+    let cycled_display_set = cursor.read_display_set().unwrap();
+
+    assert_eq!(cycled_display_set.composition.order, vec![back.clone(), front.clone()]);
+    assert_eq!(cycled_display_set, display_set);
+
+    let mut explicit = display_set.composition.clone();
+    let mut implicit = display_set.composition.clone();
+
+    explicit.order = vec![front, back];
+    implicit.order.clear();
+    assert_ne!(explicit, display_set.composition);
+    assert_ne!(implicit, display_set.composition);
+    assert_eq!(implicit, explicit);
+
+    let mut palettes = BTreeMap::<Vid<u8>, Palette>::new();
+    let mut entries = BTreeMap::<u8, PaletteEntry>::new();
+
+    entries.insert(1, PaletteEntry { y: 235, cr: 128, cb: 128, alpha: 255 });
+    entries.insert(2, PaletteEntry { y: 16, cr: 128, cb: 128, alpha: 255 });
+    palettes.insert(Vid { id: 0, version: 0 }, Palette { entries });
+
+    let rendered = render_indexes(&display_set, &palettes);
+
+    assert_eq!(rendered[&(1, 0)], 1);
+    assert_eq!(rendered[&(2, 0)], 2);
+}
+
+#[test]
+fn test_roundtrip_write_error() {
+
+    let mut display_set = DisplaySet::default();
+
+    display_set.objects.insert(
+        Vid {
+            id: 0,
+            version: 0,
+        },
+        Object {
+            width: 16_384,
+            height: 1,
+            lines: vec![vec![1; 16_384]],
+        },
+    );
+
+    assert!(matches!(
+        roundtrip(&display_set),
+        Err(RoundtripError::WriteError { source: WriteError::ObjectLineTooLong }),
+    ));
+}
+
+#[test]
+fn test_object_shared_across_windows() {
+
+    let mut display_set = DisplaySet::default();
+    let mut palettes = BTreeMap::<Vid<u8>, Palette>::new();
+    let mut entries = BTreeMap::<u8, PaletteEntry>::new();
+
+    entries.insert(1, PaletteEntry { y: 235, cr: 128, cb: 128, alpha: 255 });
+    palettes.insert(Vid { id: 0, version: 0 }, Palette { entries });
+    display_set.windows.insert(0, Window { x: 0, y: 0, width: 2, height: 1 });
+    display_set.windows.insert(1, Window { x: 10, y: 10, width: 2, height: 1 });
+    display_set.objects.insert(
+        Vid {
+            id: 7,
+            version: 0,
+        },
+        Object {
+            width: 2,
+            height: 1,
+            lines: vec![vec![1, 1]],
+        },
+    );
+    display_set.composition.objects.insert(
+        Cid {
+            object_id: 7,
+            window_id: 0,
+        },
+        CompositionObject::default(),
+    );
+    display_set.composition.objects.insert(
+        Cid {
+            object_id: 7,
+            window_id: 1,
+        },
+        CompositionObject {
+            x: 10,
+            y: 10,
+            forced: false,
+            crop: Some(Crop { x: 1, y: 0, width: 1, height: 1 }),
+        },
+    );
+
+    let rendered = render_indexes(&display_set, &palettes);
+
+    assert_eq!(rendered.len(), 3);
+    assert_eq!(rendered[&(0, 0)], 1);
+    assert_eq!(rendered[&(1, 0)], 1);
+    assert_eq!(rendered[&(10, 10)], 1);
+
+    let ratios = display_set.window_fill_ratios(&palettes);
+
+    assert_eq!(ratios[&0], 1.0);
+    assert_eq!(ratios[&1], 0.5);
+
+    let cycled_display_set = roundtrip(&display_set).unwrap();
+
+    assert_eq!(cycled_display_set.objects.len(), 1);
+    assert_eq!(cycled_display_set.composition.objects.len(), 2);
+    assert_eq!(render_indexes(&cycled_display_set, &palettes), rendered);
+}
+
+fn cropped_shared_display_set() -> (DisplaySet, BTreeMap<Vid<u8>, Palette>) {
+
+    let mut display_set = DisplaySet::default();
+    let mut palettes = BTreeMap::<Vid<u8>, Palette>::new();
+    let mut entries = BTreeMap::<u8, PaletteEntry>::new();
+
+    entries.insert(1, PaletteEntry { y: 235, cr: 128, cb: 128, alpha: 255 });
+    entries.insert(2, PaletteEntry { y: 16, cr: 128, cb: 128, alpha: 255 });
+    palettes.insert(Vid { id: 0, version: 0 }, Palette { entries });
+    display_set.windows.insert(0, Window { x: 0, y: 0, width: 10, height: 10 });
+    display_set.windows.insert(1, Window { x: 20, y: 0, width: 10, height: 10 });
+    display_set.objects.insert(
+        Vid {
+            id: 0,
+            version: 0,
+        },
+        Object {
+            width: 3,
+            height: 2,
+            lines: vec![vec![1, 2, 1], vec![2, 1, 2]],
+        },
+    );
+    display_set.objects.insert(
+        Vid {
+            id: 1,
+            version: 0,
+        },
+        Object {
+            width: 2,
+            height: 2,
+            lines: vec![vec![1, 2], vec![2, 2]],
+        },
+    );
+    display_set.composition.objects.insert(
+        Cid {
+            object_id: 0,
+            window_id: 0,
+        },
+        CompositionObject {
+            x: 2,
+            y: 2,
+            forced: false,
+            crop: Some(Crop { x: 1, y: 0, width: 2, height: 2 }),
+        },
+    );
+    display_set.composition.objects.insert(
+        Cid {
+            object_id: 0,
+            window_id: 1,
+        },
+        CompositionObject {
+            x: 20,
+            y: 0,
+            forced: false,
+            crop: None,
+        },
+    );
+    display_set.composition.objects.insert(
+        Cid {
+            object_id: 1,
+            window_id: 0,
+        },
+        CompositionObject {
+            x: 5,
+            y: 5,
+            forced: true,
+            crop: Some(Crop { x: 0, y: 1, width: 2, height: 1 }),
+        },
+    );
+
+    (display_set, palettes)
+}
+
+#[test]
+fn test_remove_crops_naive() {
+
+    let (mut display_set, palettes) = cropped_shared_display_set();
+    let objects = display_set.objects.clone();
+    let rendered = render_indexes(&display_set, &palettes);
+
+    display_set.remove_crops();
+
+    assert!(display_set.composition.objects.values().all(|co| co.crop.is_none()));
+    assert_eq!(display_set.objects, objects);
+    assert_ne!(render_indexes(&display_set, &palettes), rendered);
+}
+
+#[test]
+fn test_remove_crops_baked() {
+
+    let (display_set, palettes) = cropped_shared_display_set();
+    let rendered = render_indexes(&display_set, &palettes);
+    let mut epoch = Epoch {
+        display_sets: vec![display_set],
+    };
+
+    epoch.bake_crops();
+
+    let mut display_set = epoch.display_sets.remove(0);
+
+    display_set.remove_crops();
+
+    assert!(display_set.composition.objects.values().all(|co| co.crop.is_none()));
+    assert_eq!(display_set.objects.len(), 3);
+    assert!(!display_set.objects.contains_key(&Vid { id: 1, version: 0 }));
+    assert_eq!(
+        display_set.objects[&Vid { id: 3, version: 0 }],
+        Object {
+            width: 2,
+            height: 1,
+            lines: vec![vec![2, 2]],
+        },
+    );
+    assert_eq!(render_indexes(&display_set, &palettes), rendered);
+    assert_eq!(render_indexes(&roundtrip(&display_set).unwrap(), &palettes), rendered);
+}
+
+#[test]
+fn test_tile_oversized_objects() {
+
+    let mut display_set = DisplaySet::default();
+    let mut palettes = BTreeMap::<Vid<u8>, Palette>::new();
+    let mut entries = BTreeMap::<u8, PaletteEntry>::new();
+    let cid = Cid { object_id: 2, window_id: 0 };
+
+    entries.insert(1, PaletteEntry { y: 235, cr: 128, cb: 128, alpha: 255 });
+    entries.insert(2, PaletteEntry { y: 16, cr: 128, cb: 128, alpha: 255 });
+    palettes.insert(Vid { id: 0, version: 0 }, Palette { entries });
+    display_set.windows.insert(0, Window { x: 0, y: 0, width: 40, height: 20 });
+    display_set.objects.insert(
+        Vid {
+            id: 2,
+            version: 0,
+        },
+        Object {
+            width: 20,
+            height: 10,
+            lines: (0..10).map(|y| (0..20).map(|x| 1 + (x * y % 2)).collect()).collect(),
+        },
+    );
+    display_set.composition.objects.insert(
+        cid.clone(),
+        CompositionObject {
+            x: 4,
+            y: 5,
+            forced: true,
+            crop: None,
+        },
+    );
+    display_set.composition.order.push(cid);
+
+    let original = display_set.clone();
+    let rendered = render_indexes(&display_set, &palettes);
+
+    assert_eq!(
+        display_set.tile_oversized_objects(6),
+        Err(TileError::TileTooSmall { object_id: 2, width: 5, height: 5 }),
+    );
+    assert_eq!(display_set, original);
+
+    display_set.tile_oversized_objects(16).unwrap();
+
+    assert!(display_set.objects.values().all(|object| object.width == 10 && object.height == 10));
+    assert_eq!(display_set.objects.len(), 2);
+    assert_eq!(display_set.composition.order.len(), 2);
+    assert!(display_set.composition.objects.values().all(|co| co.forced && co.crop.is_none()));
+    assert_eq!(render_indexes(&display_set, &palettes), rendered);
+    assert_eq!(render_indexes(&roundtrip(&display_set).unwrap(), &palettes), rendered);
+
+    let mut display_set = original;
+
+    display_set.objects.insert(
+        Vid {
+            id: 0,
+            version: 0,
+        },
+        Object {
+            width: 8,
+            height: 8,
+            lines: vec![vec![1; 8]; 8],
+        },
+    );
+    display_set.composition.objects.insert(
+        Cid {
+            object_id: 0,
+            window_id: 0,
+        },
+        CompositionObject::default(),
+    );
+
+    let original = display_set.clone();
+
+    assert_eq!(
+        display_set.tile_oversized_objects(16),
+        Err(TileError::TooManyCompositionObjects { count: 3 }),
+    );
+    assert_eq!(display_set, original);
+}
+
+#[test]
+fn test_compact_ids() {
+
+    let object = |width: u16| Object {
+        width,
+        height: 1,
+        lines: vec![vec![1; width as usize]],
+    };
+    let mut display_set = DisplaySet {
+        palette_id: 40,
+        ..Default::default()
+    };
+
+    display_set.windows.insert(9, Window { x: 0, y: 0, width: 10, height: 10 });
+    display_set.windows.insert(200, Window { x: 20, y: 0, width: 10, height: 10 });
+    display_set.palettes.insert(Vid { id: 7, version: 0 }, Palette::default());
+    display_set.palettes.insert(Vid { id: 40, version: 3 }, Palette::default());
+    display_set.objects.insert(Vid { id: 5, version: 0 }, object(1));
+    display_set.objects.insert(Vid { id: 100, version: 2 }, object(2));
+    display_set.objects.insert(Vid { id: 4000, version: 0 }, object(3));
+
+    for (object_id, window_id) in [(4000, 200), (5, 9), (100, 9)] {
+        display_set.composition.objects.insert(
+            Cid {
+                object_id,
+                window_id,
+            },
+            CompositionObject {
+                x: object_id,
+                ..Default::default()
+            },
+        );
+        display_set.composition.order.push(Cid { object_id, window_id });
+    }
+
+    // A stale entry is permitted within the composition order, and is dropped.
+    display_set.composition.order.push(Cid { object_id: 7_000, window_id: 50 });
+    display_set.compact_object_ids();
+    display_set.compact_window_ids();
+    display_set.compact_palette_ids();
+
+    assert_eq!(display_set.windows.keys().copied().collect::<Vec<_>>(), vec![0, 1]);
+    assert_eq!(
+        display_set.palettes.keys().cloned().collect::<Vec<_>>(),
+        vec![Vid { id: 0, version: 0 }, Vid { id: 1, version: 3 }],
+    );
+    assert_eq!(display_set.palette_id, 1);
+    assert_eq!(
+        display_set.objects.keys().cloned().collect::<Vec<_>>(),
+        vec![Vid { id: 0, version: 0 }, Vid { id: 1, version: 2 }, Vid { id: 2, version: 0 }],
+    );
+    assert_eq!(
+        display_set.composition.order,
+        vec![
+            Cid { object_id: 2, window_id: 1 },
+            Cid { object_id: 0, window_id: 0 },
+            Cid { object_id: 1, window_id: 0 },
+        ],
+    );
+
+    for (cid, co) in &display_set.composition.objects {
+        assert!(display_set.windows.contains_key(&cid.window_id));
+        assert_eq!(latest(&display_set.objects, cid.object_id), Some(&object(match co.x {
+            5 => 1,
+            100 => 2,
+            _ => 3,
+        })));
+    }
+
+    assert!(display_set.active_palette().is_some());
+}
+
+#[test]
+fn test_active_palette() {
+
+    let palette = |y: u8| {
+        let mut entries = BTreeMap::<u8, PaletteEntry>::new();
+        entries.insert(0, PaletteEntry { y, cr: 128, cb: 128, alpha: 255 });
+        Palette { entries }
+    };
+    let mut display_set = DisplaySet {
+        palette_id: 1,
+        ..Default::default()
+    };
+
+    assert_eq!(display_set.active_palette(), None);
+
+    display_set.palettes.insert(Vid { id: 0, version: 5 }, palette(16));
+    display_set.palettes.insert(Vid { id: 1, version: 0 }, palette(128));
+    display_set.palettes.insert(Vid { id: 1, version: 1 }, palette(235));
+
+    assert_eq!(display_set.active_palette(), Some(&palette(235)));
+
+    display_set.palette_id = 0;
+
+    assert_eq!(display_set.active_palette(), Some(&palette(16)));
+
+    display_set.palette_id = 2;
+
+    assert_eq!(display_set.active_palette(), None);
+}
+
+#[test]
+fn test_run_statistics() {
+
+    let object = Object {
+        width: 6,
+        height: 2,
+        lines: vec![
+            vec![0, 0, 0, 1, 1, 2],
+            vec![3, 3, 3, 0, 0, 0],
+        ],
+    };
+    let stats = object.run_statistics();
+    let mut length_distribution = BTreeMap::<usize, usize>::new();
+
+    length_distribution.insert(1, 1);
+    length_distribution.insert(2, 1);
+    length_distribution.insert(3, 3);
+
+    assert_eq!(
+        stats,
+        RunStats {
+            run_count: 5,
+            zero_run_count: 2,
+            average_run_length: 2.4,
+            length_distribution,
+        },
+    );
+    assert_eq!(Object::default().run_statistics(), RunStats::default());
+}
+
+#[test]
+fn test_needs_windows() {
+
+    let mut display_set = simple_display_set();
+
+    assert!(!display_set.needs_windows());
+
+    display_set.windows.clear();
+
+    assert!(display_set.needs_windows());
+
+    display_set.composition.state = CompositionState::AcquisitionPoint;
+
+    assert!(display_set.needs_windows());
+
+    display_set.composition.state = CompositionState::Normal;
+
+    assert!(!display_set.needs_windows());
+}
+
+#[test]
+fn test_content_key() {
+
+    let first = simple_display_set();
+    let mut second = first.clone();
+    let mut third = first.clone();
+    let mut keys = HashSet::<ContentKey>::new();
+
+    second.pts = 90_000;
+    second.dts = 45_000;
+    second.composition.number = 1;
+    second.composition.state = CompositionState::Normal;
+    third.windows.get_mut(&0).unwrap().x = 1;
+
+    let mut bumped = first.clone();
+
+    bumped.palettes = bumped.palettes.into_iter()
+        .map(|(vid, palette)| (Vid { version: vid.version + 1, ..vid }, palette))
+        .collect();
+    bumped.objects = bumped.objects.into_iter()
+        .map(|(vid, object)| (Vid { version: vid.version + 3, ..vid }, object))
+        .collect();
+
+    let mut renumbered = first.clone();
+    let (vid, object) = renumbered.objects.pop_first().unwrap();
+
+    renumbered.objects.insert(Vid { id: vid.id + 1, ..vid }, object);
+
+    assert!(keys.insert(ContentKey(&first)));
+    assert!(!keys.insert(ContentKey(&second)));
+    assert!(keys.insert(ContentKey(&third)));
+    assert!(!keys.insert(ContentKey(&bumped)));
+    assert!(keys.insert(ContentKey(&renumbered)));
+    assert_eq!(keys.len(), 3);
+}
+
+#[test]
+fn test_apply_color_lut() {
+
+    let mut rng = thread_rng();
+    let mut display_set = DisplaySet::default();
+    let mut entries = BTreeMap::<u8, PaletteEntry>::new();
+
+    for id in 0..=255 {
+        entries.insert(
+            id,
+            PaletteEntry {
+                y: rng.gen_range(16..235),
+                cr: rng.gen(),
+                cb: rng.gen(),
+                alpha: rng.gen(),
+            },
+        );
+    }
+    display_set.palettes.insert(Vid::default(), Palette { entries });
+
+    let original = display_set.clone();
+
+    display_set.apply_color_lut(&|red, green, blue| (red, green, blue));
+
+    assert_eq!(display_set, original);
+
+    display_set.apply_color_lut(&|_, _, _| (0.0, 0.0, 0.0));
+
+    for entry in display_set.palettes[&Vid::default()].entries.values() {
+        assert_eq!((entry.y, entry.cb, entry.cr), (16, 128, 128));
+    }
+}
+
+#[test]
+fn test_composition_number_counter() {
+
+    let mut counter = CompositionNumberCounter::default();
+    let numbers = (0..3)
+        .map(|_| counter.assign(DisplaySet::default()).composition.number)
+        .collect::<Vec<u16>>();
+
+    assert_eq!(numbers, vec![0, 1, 2]);
+    assert_eq!(DisplaySet::default().with_composition_number(7).composition.number, 7);
+
+    let mut counter = CompositionNumberCounter::new(u16::MAX);
+
+    assert_eq!(counter.next_number(), u16::MAX);
+    assert_eq!(counter.next_number(), 0);
+}
+
+#[test]
+fn test_ds_truncated_object_sequence() {
+
+    let mut buffer = vec![];
+
+    buffer.write_segment(&Segment::PresentationComposition(
+        PresentationCompositionSegment {
+            width: 1920,
+            height: 1080,
+            frame_rate: 0x10,
+            ..Default::default()
+        }
+    )).unwrap();
+    buffer.write_segment(&Segment::InitialObjectDefinition(
+        InitialObjectDefinitionSegment {
+            id: 7,
+            length: 12,
+            width: 8,
+            height: 1,
+            data: vec![0x01, 0x01, 0x01, 0x01],
+            ..Default::default()
+        }
+    )).unwrap();
+
+    assert!(matches!(
+        Cursor::new(buffer).read_display_set(),
+        Err(ReadError::TruncatedObjectSequence { object_id: 7 }),
+    ));
+}
+
+#[test]
+fn test_decompress_lenient() {
+
+    let lines = vec![vec![1, 1, 1, 1], vec![0, 0, 2, 2], vec![3, 3, 3, 3]];
+    let mut buffer = vec![];
+    let mut display_set = DisplaySet::default();
+
+    display_set.objects.insert(
+        Vid::default(),
+        Object {
+            width: 4,
+            height: 3,
+            lines: lines.clone(),
+        },
+    );
+    buffer.write_display_set(display_set).unwrap();
+
+    let mut cursor = Cursor::new(buffer);
+    let data = loop {
+        if let Segment::SingleObjectDefinition(sods) = cursor.read_segment().unwrap() {
+            break sods.data
+        }
+    };
+
+    let (complete, err) = decompress_lenient(&data);
+
+    assert_eq!(complete, lines);
+    assert!(err.is_none());
+
+    let (partial, err) = decompress_lenient(&data[..data.len() - 2]);
+
+    assert_eq!(partial, lines[..2]);
+    assert!(matches!(err, Some(ParseError::IncompleteRleLine)));
+
+    let (partial, err) = decompress_lenient(&data[..data.len() - 1]);
+
+    assert_eq!(partial, lines[..2]);
+    assert!(matches!(err, Some(ParseError::IncompleteRleSequence)));
+}
+
+#[test]
+fn test_apply_safe_area() {
+
+    let mut display_set = DisplaySet {
+        width: 1920,
+        height: 1080,
+        ..Default::default()
+    };
+    let cid = Cid {
+        object_id: 0,
+        window_id: 0,
+    };
+
+    display_set.windows.insert(0, Window { x: 0, y: 980, width: 600, height: 100 });
+    display_set.composition.objects.insert(
+        cid.clone(),
+        CompositionObject {
+            x: 0,
+            y: 1000,
+            ..Default::default()
+        },
+    );
+    display_set.apply_safe_area(90.0);
+
+    assert_eq!(display_set.windows[&0], Window { x: 96, y: 936, width: 600, height: 100 });
+    assert_eq!(display_set.composition.objects[&cid].x, 96);
+    assert_eq!(display_set.composition.objects[&cid].y, 954);
+
+    display_set.apply_safe_area(100.0);
+
+    assert_eq!(display_set.windows[&0], Window { x: 96, y: 936, width: 600, height: 100 });
+}
+
+#[test]
+fn test_palette_updates() {
+
+    let mut sets = vec![DisplaySet::default(); 4];
+
+    assert!(!uses_palette_updates(&sets));
+    assert_eq!(palette_update_count(&sets), 0);
+
+    sets[1].palete_update_only = true;
+    sets[2].palete_update_only = true;
+
+    assert!(uses_palette_updates(&sets));
+    assert_eq!(palette_update_count(&sets), 2);
+    assert!(!uses_palette_updates(&[]));
+}
+
+#[test]
+fn test_validate_crops() {
+
+    let (mut display_set, _) = cropped_shared_display_set();
+    let cid = Cid {
+        object_id: 1,
+        window_id: 0,
+    };
+
+    assert_eq!(display_set.validate_crops(), Ok(()));
+
+    display_set.composition.objects.get_mut(&cid).unwrap().crop = Some(
+        Crop {
+            x: 1,
+            y: 0,
+            width: 2,
+            height: 1,
+        }
+    );
+
+    assert_eq!(
+        display_set.validate_crops(),
+        Err(ValidationError::CropExceedsObject { object_id: 1 }),
+    );
+
+    display_set.composition.objects.get_mut(&cid).unwrap().crop = Some(
+        Crop {
+            x: 0,
+            y: 0,
+            width: 2,
+            height: 3,
+        }
+    );
+
+    assert_eq!(
+        display_set.validate_crops(),
+        Err(ValidationError::CropExceedsObject { object_id: 1 }),
+    );
+
+    display_set.objects.clear();
+
+    assert_eq!(display_set.validate_crops(), Ok(()));
+}
+
+#[test]
+fn test_palette_adjust() {
+
+    let mut rng = thread_rng();
+    let mut entries = BTreeMap::<u8, PaletteEntry>::new();
+
+    for id in 0..=255 {
+        entries.insert(
+            id,
+            PaletteEntry {
+                y: rng.gen_range(16..235),
+                cr: rng.gen(),
+                cb: rng.gen(),
+                alpha: rng.gen(),
+            },
+        );
+    }
+
+    let mut palette = Palette { entries };
+    let original = palette.clone();
+
+    palette.adjust(1.0, 1.0, 1.0);
+
+    assert_eq!(palette, original);
+
+    palette.adjust(1.0, 1.0, 0.0);
+
+    for entry in palette.entries.values() {
+        assert!(entry.cb.abs_diff(128) <= 1 && entry.cr.abs_diff(128) <= 1);
+    }
+
+    palette.adjust(1.0, 0.0, 1.0);
+
+    for entry in palette.entries.values() {
+        assert_eq!(entry.y, palette.entries[&0].y);
+    }
+
+    let gray = palette.clone();
+
+    for (brightness, y) in [(0.5, 16), (0.75, 71), (1.5, 235)] {
+
+        let mut palette = gray.clone();
+
+        palette.adjust(brightness, 1.0, 1.0);
+
+        for entry in palette.entries.values() {
+            assert!(entry.y.abs_diff(y) <= 1);
+        }
+    }
+}
+
+#[test]
+fn test_clamp_studio_range() {
+
+    let mut entries = BTreeMap::<u8, PaletteEntry>::new();
+
+    entries.insert(0, PaletteEntry { y: 4, cr: 8, cb: 250, alpha: 255 });
+    entries.insert(1, PaletteEntry { y: 250, cr: 128, cb: 128, alpha: 0 });
+
+    let mut palette = Palette { entries };
+
+    palette.clamp_studio_range(false);
+
+    assert_eq!(palette.entries[&0], PaletteEntry { y: 16, cr: 8, cb: 250, alpha: 255 });
+    assert_eq!(palette.entries[&1], PaletteEntry { y: 235, cr: 128, cb: 128, alpha: 0 });
+
+    palette.clamp_studio_range(true);
+
+    assert_eq!(palette.entries[&0], PaletteEntry { y: 16, cr: 16, cb: 240, alpha: 255 });
+    assert_eq!(palette.entries[&1], PaletteEntry { y: 235, cr: 128, cb: 128, alpha: 0 });
+}
+
+#[test]
+fn test_overlapping_compositions() {
+
+    let mut display_set = simple_display_set();
+    let first = Cid { object_id: 1, window_id: 0 };
+    let second = Cid { object_id: 2, window_id: 1 };
+    let third = Cid { object_id: 3, window_id: 0 };
+
+    display_set.objects.insert(
+        Vid {
+            id: 2,
+            version: 0,
+        },
+        Object {
+            width: 8,
+            height: 8,
+            lines: vec![vec![1; 8]; 8],
+        },
+    );
+    display_set.composition.objects.insert(
+        second.clone(),
+        CompositionObject {
+            x: 3,
+            y: 1,
+            ..Default::default()
+        },
+    );
+    display_set.composition.objects.insert(
+        third.clone(),
+        CompositionObject::default(),
+    );
+
+    assert_eq!(display_set.overlapping_compositions(), vec![(first.clone(), second.clone())]);
+
+    display_set.composition.objects.get_mut(&first).unwrap().crop =
+        Some(Crop { x: 0, y: 0, width: 3, height: 2 });
+
+    assert!(display_set.overlapping_compositions().is_empty());
+}
+
+#[test]
+fn test_to_ascii() {
+
+    let mut palette = Palette::default();
+
+    palette.entries.insert(1, PaletteEntry { y: 235, cr: 128, cb: 128, alpha: 255 });
+    palette.entries.insert(2, PaletteEntry { y: 16, cr: 128, cb: 128, alpha: 255 });
+    palette.entries.insert(3, PaletteEntry { y: 235, cr: 128, cb: 128, alpha: 0 });
+
+    let object = Object {
+        width: 4,
+        height: 2,
+        lines: vec![
+            vec![0, 1, 2, 3],
+            vec![2, 1, 1, 0],
+        ],
+    };
+
+    assert_eq!(object.to_ascii(&palette), " @. \n.@@ \n");
+
+    let wide = Object {
+        width: 160,
+        height: 2,
+        lines: vec![vec![1; 160], vec![2; 160]],
+    };
+
+    assert_eq!(wide.to_ascii(&palette), format!("{}\n", "@".repeat(80)));
+}
+
+#[test]
+fn test_to_rgba_image() {
+
+    let mut palette = Palette::default();
+
+    palette.entries.insert(1, PaletteEntry { y: 235, cr: 128, cb: 128, alpha: 255 });
+    palette.entries.insert(2, PaletteEntry { y: 16, cr: 128, cb: 128, alpha: 128 });
+
+    let object = Object {
+        width: 3,
+        height: 3,
+        lines: vec![
+            vec![1, 2, 1],
+            vec![2],
+        ],
+    };
+    let rgba = object.to_rgba_image(&palette).unwrap();
+
+    assert_eq!(rgba.len(), 3 * 3 * 4);
+    assert_eq!(&rgba[0..12], &[255, 255, 255, 255, 0, 0, 0, 128, 255, 255, 255, 255]);
+    assert_eq!(&rgba[12..16], &[0, 0, 0, 128]);
+    assert!(rgba[16..].iter().all(|&sample| sample == 0));
+
+    let missing = Object {
+        width: 2,
+        height: 1,
+        lines: vec![vec![1, 7]],
+    };
+
+    assert_eq!(missing.to_rgba_image(&palette), Err(MissingPaletteEntryError { index: 7 }));
+}
+
+#[test]
+fn test_recenter() {
+
+    let cid = Cid { object_id: 1, window_id: 0 };
+    let mut display_set = simple_display_set();
+
+    display_set.windows.get_mut(&0).unwrap().x = 100;
+    display_set.windows.get_mut(&0).unwrap().y = 900;
+    display_set.composition.objects.get_mut(&cid).unwrap().x = 120;
+    display_set.composition.objects.get_mut(&cid).unwrap().y = 950;
+
+    display_set.recenter_horizontal().unwrap();
+
+    assert_eq!((display_set.windows[&0].x, display_set.windows[&0].y), (910, 900));
+    assert_eq!(
+        (display_set.composition.objects[&cid].x, display_set.composition.objects[&cid].y),
+        (930, 950),
+    );
+
+    display_set.recenter_vertical().unwrap();
+
+    assert_eq!((display_set.windows[&0].x, display_set.windows[&0].y), (910, 490));
+    assert_eq!(
+        (display_set.composition.objects[&cid].x, display_set.composition.objects[&cid].y),
+        (930, 540),
+    );
+
+    display_set.width = 50;
+
+    assert_eq!(
+        display_set.recenter_horizontal(),
+        Err(RecenterError::ContentTooLarge { content_size: 100, screen_size: 50 }),
+    );
+    assert_eq!(display_set.windows[&0].x, 910);
+}
+
+#[test]
+fn test_unshare_objects() {
+
+    let mut display_set = simple_display_set();
+    let mut palette = Palette::default();
+
+    palette.entries.insert(1, PaletteEntry { y: 235, cr: 128, cb: 128, alpha: 255 });
+    display_set.palettes.insert(Vid { id: 0, version: 0 }, palette);
+    display_set.windows.insert(
+        1,
+        Window {
+            x: 200,
+            y: 200,
+            width: 100,
+            height: 100,
+        },
+    );
+    display_set.composition.objects.insert(
+        Cid {
+            object_id: 1,
+            window_id: 1,
+        },
+        CompositionObject {
+            x: 200,
+            y: 200,
+            forced: false,
+            crop: None,
+        },
+    );
+    display_set.composition.order.push(Cid { object_id: 1, window_id: 1 });
+
+    let frame = render_frame(&display_set, &EpochContext::default()).unwrap();
+
+    assert!(frame.pixels.iter().any(|pixel| pixel.alpha > 0));
+
+    display_set.unshare_objects();
+
+    assert_eq!(
+        display_set.composition.objects.keys().cloned().collect::<Vec<_>>(),
+        vec![Cid { object_id: 0, window_id: 1 }, Cid { object_id: 1, window_id: 0 }],
+    );
+    assert_eq!(
+        display_set.composition.order,
+        vec![Cid { object_id: 1, window_id: 0 }, Cid { object_id: 0, window_id: 1 }],
+    );
+    assert_eq!(
+        display_set.objects[&Vid { id: 0, version: 0 }],
+        display_set.objects[&Vid { id: 1, version: 0 }],
+    );
+    assert_eq!(render_frame(&display_set, &EpochContext::default()).unwrap(), frame);
+}
+
+#[test]
+fn test_clamp_crops() {
+
+    let cid = Cid { object_id: 1, window_id: 0 };
+    let mut display_set =
+        cropped_display_set(Crop { x: 1, y: 1, width: 10, height: 5 }, true);
+
+    assert!(display_set.validate_crops().is_err());
+
+    display_set.clamp_crops();
+
+    assert_eq!(
+        display_set.composition.objects[&cid].crop,
+        Some(Crop { x: 1, y: 1, width: 3, height: 1 }),
+    );
+    assert_eq!(display_set.validate_crops(), Ok(()));
+
+    display_set.composition.objects.get_mut(&cid).unwrap().crop =
+        Some(Crop { x: 6, y: 0, width: 2, height: 2 });
+    display_set.clamp_crops();
+
+    assert_eq!(
+        display_set.composition.objects[&cid].crop,
+        Some(Crop { x: 4, y: 0, width: 0, height: 2 }),
+    );
+}
+
+#[test]
+fn test_auto_window() {
+
+    let mut display_set = DisplaySet {
+        width: 1920,
+        height: 1080,
+        ..Default::default()
+    };
+
+    display_set.windows.insert(
+        3,
+        Window {
+            x: 0,
+            y: 0,
+            width: 1920,
+            height: 1080,
+        },
+    );
+
+    for (object_id, x, y) in [(1, 10, 0), (2, 0, 100), (3, 0, 0)] {
+        display_set.objects.insert(
+            Vid {
+                id: object_id,
+                version: 0,
+            },
+            Object {
+                width: 4,
+                height: 2,
+                lines: vec![vec![1; 4]; 2],
+            },
+        );
+        display_set.composition.objects.insert(
+            Cid {
+                object_id,
+                window_id: 3,
+            },
+            CompositionObject {
+                x,
+                y,
+                forced: false,
+                crop: None,
+            },
+        );
+        display_set.composition.order.push(Cid { object_id, window_id: 3 });
+    }
+
+    let original = display_set.clone();
+
+    // A stale entry is permitted within the composition order, and is dropped.
+    display_set.composition.order.push(Cid { object_id: 9, window_id: 3 });
+
+    display_set.auto_window(2).unwrap();
+
+    assert_eq!(
+        display_set.windows,
+        BTreeMap::from([
+            (0, Window { x: 0, y: 0, width: 14, height: 2 }),
+            (1, Window { x: 0, y: 100, width: 4, height: 2 }),
+        ]),
+    );
+    assert_eq!(
+        display_set.composition.order,
+        vec![
+            Cid { object_id: 1, window_id: 0 },
+            Cid { object_id: 2, window_id: 1 },
+            Cid { object_id: 3, window_id: 0 },
+        ],
+    );
+
+    let mut display_set = original;
+    let first = Cid { object_id: 1, window_id: 3 };
+    let second = Cid { object_id: 2, window_id: 3 };
+
+    display_set.composition.objects.get_mut(&first).unwrap().y = 100;
+    display_set.composition.objects.insert(
+        second,
+        CompositionObject {
+            x: 3,
+            y: 1,
+            forced: false,
+            crop: Some(Crop { x: 0, y: 0, width: 2, height: 2 }),
+        },
+    );
+
+    let overlapping = display_set.clone();
+
+    assert_eq!(display_set.auto_window(1), Err(AutoWindowError::OverlappingObjects));
+    assert_eq!(display_set, overlapping);
+
+    display_set.auto_window(2).unwrap();
+
+    assert_eq!(
+        display_set.windows,
+        BTreeMap::from([
+            (0, Window { x: 0, y: 0, width: 5, height: 3 }),
+            (1, Window { x: 10, y: 100, width: 4, height: 2 }),
+        ]),
+    );
+}
+
+#[test]
+fn test_object_checksums() {
+
+    let mut display_set = simple_display_set();
+    let vid = Vid { id: 1, version: 0 };
+    let checksums = display_set.object_checksums();
+
+    // Adler-32 of 00 04 00 02 followed by eight bytes of 01.
+    assert_eq!(checksums, BTreeMap::from([(vid.clone(), 0x006E_000F)]));
+    assert!(display_set.verify_object_checksums(&checksums).is_empty());
+
+    display_set.objects.get_mut(&vid).unwrap().lines[1][2] = 2;
+
+    assert_ne!(display_set.object_checksums()[&vid], checksums[&vid]);
+    assert_eq!(display_set.verify_object_checksums(&checksums), vec![vid.clone()]);
+
+    display_set.objects.clear();
+
+    assert_eq!(display_set.verify_object_checksums(&checksums), vec![vid]);
+}
+
+#[test]
+fn test_decode_complexity() {
+
+    let mut simple = simple_display_set();
+    let mut complex = simple.clone();
+    let mut counted = simple.clone();
+
+    complex.objects.get_mut(&Vid { id: 1, version: 0 }).unwrap().lines = vec![
+        vec![1, 2, 1, 2],
+        vec![3, 3, 0, 4],
+    ];
+    counted.objects.clear();
+
+    assert_eq!(simple.decode_complexity(), 4);
+    assert_eq!(complex.decode_complexity(), 10);
+    assert_eq!(counted.decode_complexity(), 0);
+
+    simple.objects.get_mut(&Vid { id: 1, version: 0 }).unwrap().lines[0] = vec![0; 4];
+
+    assert_eq!(simple.decode_complexity(), 4);
+}
@@ -0,0 +1,1088 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a
+ * copy of the MPL was not distributed with this file, You can obtain one at
+ * https://mozilla.org/MPL/2.0/.
+ *
+ * SPDX-License-Identifier: MPL-2.0
+ */
+
+//! Operates on sequences of display sets (DS's) grouped into epochs.
+//!
+//! # Overview
+//!
+//! An epoch begins with a DS whose composition state is
+//! [`EpochStart`](CompositionState::EpochStart) and continues until the next such DS. Windows,
+//! objects, and palettes are scoped to the epoch they are defined in, so any analysis that
+//! spans multiple display sets must first determine where each epoch begins and ends.
+//!
+//! Any display sets preceding the first `EpochStart` are treated as belonging to an epoch of
+//! their own.
+//!
+//! A stream that is read from a seek point may instead begin partway through an epoch. See
+//! [read_from_midstream] for handling these the way a player would.
+
+#[cfg(test)]
+mod tests;
+
+use super::{
+    ts_to_timecode,
+    displayset::{
+        content_extent,
+        object_content_eq,
+        union_extents,
+        visible_region,
+        Cid,
+        Composition,
+        ContentKey,
+        DisplaySet,
+        Object,
+        Palette,
+        ReadDisplaySetExt,
+        ReadError,
+        ReadOptions,
+        RecenterError,
+        TileError,
+        Vid,
+        Window,
+        WriteDisplaySetExt,
+        WriteError,
+    },
+    segment::{CompositionState, FrameRate, ReadError as SegmentReadError},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{Error as IoError, ErrorKind, Read, Write},
+    iter::Peekable,
+    mem,
+    ops::Range,
+};
+use thiserror::Error as ThisError;
+
+/// The error type for [process].
+#[derive(ThisError, Debug)]
+pub enum ProcessError {
+    /// A display set could not be read from the input.
+    #[error("display set read error")]
+    ReadError {
+        /// The underlying display set read error.
+        #[from]
+        source: ReadError,
+    },
+    /// A display set could not be written to the output.
+    #[error("display set write error")]
+    WriteError {
+        /// The underlying display set write error.
+        #[from]
+        source: WriteError,
+    },
+}
+
+/// The error type for [check_object_version_monotonicity].
+#[derive(ThisError, Debug, Eq, PartialEq)]
+pub enum VersioningError {
+    /// An object is defined with a lower version than it was earlier within the epoch.
+    #[error("object {object_id} regresses from version {previous_version} to {version}")]
+    VersionRegression {
+        /// The ID of the object.
+        object_id: u16,
+        /// The version of the earlier definition.
+        previous_version: u8,
+        /// The version of the later definition.
+        version: u8,
+    },
+    /// An object is redefined with the same version as earlier within the epoch, but with
+    /// different content.
+    #[error("object {object_id} is redefined with different content at version {version}")]
+    ConflictingDuplicate {
+        /// The ID of the object.
+        object_id: u16,
+        /// The version shared by both definitions.
+        version: u8,
+    },
+}
+
+/// The error type for [unify_palettes](Epoch::unify_palettes).
+#[derive(ThisError, Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[error("merged palette would require {entry_count} entries")]
+pub struct PaletteOverflowError {
+    /// The number of entries the merged palette would require, which exceeds 256.
+    pub entry_count: usize,
+}
+
+/// Describes a problem with the timing of a caption, as reported by [timing_issues].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TimingIssue {
+    /// A caption begins before the one preceding it in the stream has ended, so the two
+    /// contend for the screen and may flicker.
+    Overlap {
+        /// The PTS at which the earlier caption is shown.
+        a: u32,
+        /// The PTS at which the later caption is shown.
+        b: u32,
+    },
+    /// A caption is ended by a display set whose PTS precedes its own, which leaves no time for
+    /// it to be shown.
+    NegativeGap {
+        /// The PTS at which the caption is shown.
+        a: u32,
+        /// The PTS of the display set ending the caption.
+        end: u32,
+    },
+    /// A caption begins so soon after the preceding one ends that the screen is only briefly
+    /// cleared between them, which is likely to be seen as a flicker. Only reported by
+    /// [timing_issues_with_min_gap].
+    ShortGap {
+        /// The PTS at which the earlier caption ends.
+        end: u32,
+        /// The PTS at which the later caption is shown.
+        b: u32,
+    },
+}
+
+/// Represents a complete epoch.
+#[derive(Clone, Debug, Default, Hash, PartialEq)]
+pub struct Epoch {
+    /// The display sets making up this epoch, in presentation order. The first one will
+    /// typically have a composition state of [`EpochStart`](CompositionState::EpochStart).
+    pub display_sets: Vec<DisplaySet>,
+}
+
+impl Epoch {
+
+    /// Determines the largest number of windows that are defined at any single point within
+    /// this epoch. Windows persist until they are redefined by a later window definition, so a
+    /// DS that defines no windows leaves the previous set in place.
+    pub fn peak_window_count(&self) -> u8 {
+
+        let mut active = 0;
+        let mut peak = 0;
+
+        for ds in &self.display_sets {
+            if !ds.windows.is_empty() {
+                active = ds.windows.len().min(u8::MAX as usize) as u8;
+            }
+            peak = peak.max(active);
+        }
+
+        peak
+    }
+
+    /// Determines the largest number of composition objects that are shown on the screen at
+    /// any single point within this epoch.
+    pub fn peak_object_count(&self) -> usize {
+        self.display_sets.iter().map(|ds| ds.composition.objects.len()).max().unwrap_or(0)
+    }
+
+    /// Merges every palette used throughout this epoch into a single palette having an ID of
+    /// zero, for players that only support one palette.
+    ///
+    /// Each entry of each source palette is assigned its own entry within the merged palette,
+    /// ordered by palette ID and then by entry ID. Every display set that defines palettes
+    /// instead defines a new version of the merged palette, reflecting the latest version of
+    /// each source palette as of that display set, so palette updates continue to animate. The
+    /// pixels of each object are remapped according to the palette that was active within the
+    /// display set defining it, and pixels referring to undefined entries are mapped to an
+    /// entry that remains undefined.
+    ///
+    /// Should the merged palette require more than 256 entries, an error is returned and the
+    /// epoch is left unchanged. The palettes would then need to be quantized first.
+    pub fn unify_palettes(&mut self) -> Result<(), PaletteOverflowError> {
+
+        let mut sources = BTreeSet::<(u8, u8)>::new();
+
+        for ds in &self.display_sets {
+            for (vid, palette) in &ds.palettes {
+                sources.extend(palette.entries.keys().map(|&entry_id| (vid.id, entry_id)));
+            }
+        }
+
+        let undefined_needed = self.display_sets.iter().any(|ds|
+            ds.objects.values().any(|object|
+                object.lines.iter().flatten().any(|&pixel|
+                    !sources.contains(&(ds.palette_id, pixel))
+                )
+            )
+        );
+        let entry_count = sources.len() + undefined_needed as usize;
+
+        if entry_count > 256 {
+            return Err(PaletteOverflowError { entry_count })
+        }
+
+        let indices = sources.iter()
+            .enumerate()
+            .map(|(index, &source)| (source, index as u8))
+            .collect::<BTreeMap<(u8, u8), u8>>();
+        let undefined = sources.len() as u8;
+        let mut current = BTreeMap::<u8, Palette>::new();
+        let mut version = 0_u8;
+
+        for ds in &mut self.display_sets {
+
+            if !ds.palettes.is_empty() {
+
+                let mut merged = Palette::default();
+
+                for (vid, palette) in mem::take(&mut ds.palettes) {
+                    current.insert(vid.id, palette);
+                }
+                for (&palette_id, palette) in &current {
+                    for (&entry_id, entry) in &palette.entries {
+                        merged.entries.insert(indices[&(palette_id, entry_id)], entry.clone());
+                    }
+                }
+
+                ds.palettes.insert(Vid { id: 0, version }, merged);
+                version = version.wrapping_add(1);
+            }
+
+            for object in ds.objects.values_mut() {
+                for pixel in object.lines.iter_mut().flatten() {
+                    *pixel = indices.get(&(ds.palette_id, *pixel)).copied().unwrap_or(undefined);
+                }
+            }
+
+            ds.palette_id = 0;
+        }
+
+        Ok(())
+    }
+
+    /// Shifts every window and composition object within this epoch horizontally by the same
+    /// amount, so that the combined bounding box of everything shown throughout the epoch is
+    /// centered on the screen. The bounding box covers the windows in effect as of each display
+    /// set along with the visible area of each composition object, resolving anything inherited
+    /// from earlier display sets, so the captions of an epoch keep their relative positions.
+    /// Nothing is changed if neither are present, or if the content is wider than the screen,
+    /// in which case an error is returned.
+    pub fn recenter_horizontal(&mut self) -> Result<(), RecenterError> {
+        self.recenter(true)
+    }
+
+    /// Shifts every window and composition object within this epoch vertically so that their
+    /// combined bounding box is centered on the screen, as
+    /// [recenter_horizontal](Self::recenter_horizontal) does horizontally. An error is returned
+    /// if the content is taller than the screen.
+    pub fn recenter_vertical(&mut self) -> Result<(), RecenterError> {
+        self.recenter(false)
+    }
+
+    fn recenter(&mut self, horizontal: bool) -> Result<(), RecenterError> {
+
+        let mut context = EpochContext::default();
+        let mut extent = None::<(u32, u32)>;
+        let mut screen_size = u16::MAX;
+
+        for ds in &self.display_sets {
+            context.update(ds);
+            screen_size = screen_size.min(if horizontal { ds.width } else { ds.height });
+            extent = union_extents(
+                extent,
+                content_extent(
+                    horizontal,
+                    &context.windows,
+                    &context.composition,
+                    &context.objects,
+                ),
+            );
+        }
+
+        let Some(extent) = extent else {
+            return Ok(())
+        };
+
+        for ds in &mut self.display_sets {
+            ds.recenter_extent(horizontal, extent, screen_size)?;
+        }
+
+        Ok(())
+    }
+
+    /// Replaces every cropped composition object within this epoch with an uncropped one that
+    /// shows a copy of the visible region, for players that mishandle crops.
+    ///
+    /// Each distinct region of an object that is shown is copied into an object of its own,
+    /// which is assigned an ID that is not used anywhere else within the epoch. Objects are
+    /// resolved against the state inherited from earlier display sets, so a display set that
+    /// crops an object defined earlier in the epoch is handled as well. A copy is defined by
+    /// the first display set that shows it, again by any later acquisition point, and again as
+    /// a new version whenever the original object is redefined. Original objects that are no
+    /// longer composed anywhere within the epoch are dropped. Should the epoch run out of
+    /// object IDs, any remaining crops are left in place.
+    pub fn bake_crops(&mut self) {
+
+        let mut used_ids = self.object_ids();
+        let mut context = EpochContext::default();
+        let mut copies = BTreeMap::<(u16, (u16, u16, u16, u16)), (Vid<u16>, Vid<u16>)>::new();
+        let mut originals = BTreeSet::<u16>::new();
+
+        for ds in &mut self.display_sets {
+
+            context.update(ds);
+
+            let mut map = BTreeMap::<Cid, Cid>::new();
+
+            for (cid, co) in ds.composition.objects.iter_mut() {
+
+                if co.crop.is_none() {
+                    continue
+                }
+
+                let Some((vid, object)) = context.objects.iter()
+                    .rev()
+                    .find(|(vid, _)| vid.id == cid.object_id) else {
+                    continue
+                };
+                let region = visible_region(co, object);
+                let key = (cid.object_id, region);
+                let define = match copies.get_mut(&key) {
+                    Some(_) if ds.palete_update_only => {
+                        false
+                    }
+                    Some((copy, source)) if source != vid => {
+                        *source = vid.clone();
+                        copy.version = copy.version.wrapping_add(1);
+                        true
+                    }
+                    Some(_) => {
+                        ds.composition.state != CompositionState::Normal
+                    }
+                    None if ds.palete_update_only => {
+                        continue
+                    }
+                    None => {
+                        let Some(id) = (0..=u16::MAX).find(|id| !used_ids.contains(id)) else {
+                            continue
+                        };
+                        used_ids.insert(id);
+                        copies.insert(key, (Vid { id, version: 0 }, vid.clone()));
+                        true
+                    }
+                };
+                let copy = copies[&key].0.clone();
+
+                if define {
+                    let (x, y, width, height) = region;
+                    ds.objects.insert(copy.clone(), object.region(x, y, width, height));
+                }
+
+                map.insert(cid.clone(), Cid { object_id: copy.id, window_id: cid.window_id });
+                originals.insert(cid.object_id);
+                co.crop = None;
+            }
+
+            ds.remap_cids(|cid| map.get(cid).cloned().unwrap_or_else(|| cid.clone()));
+        }
+
+        for id in originals {
+            if !self.display_sets.iter().any(|ds|
+                ds.composition.objects.keys().any(|cid| cid.object_id == id)
+            ) {
+                for ds in &mut self.display_sets {
+                    ds.objects.retain(|vid, _| vid.id != id);
+                }
+            }
+        }
+    }
+
+    /// Tiles oversized objects within every display set of this epoch as
+    /// [DisplaySet::tile_oversized_objects] does, except that new object IDs are chosen so as not
+    /// to collide with any ID used anywhere within the epoch. Should any display set fail to be
+    /// tiled, its error is returned and this epoch is left unchanged.
+    pub fn tile_oversized_objects(&mut self, max_dim: u16) -> Result<(), TileError> {
+
+        let mut used_ids = self.object_ids();
+        let mut display_sets = self.display_sets.clone();
+
+        for ds in &mut display_sets {
+            ds.tile_oversized_objects_avoiding(max_dim, &mut used_ids)?;
+        }
+
+        self.display_sets = display_sets;
+
+        Ok(())
+    }
+
+    /// Gives each composition object within every display set of this epoch its own object as
+    /// [DisplaySet::unshare_objects] does, except that copies are given IDs that are not used
+    /// anywhere within the epoch.
+    pub fn unshare_objects(&mut self) {
+
+        let mut used_ids = self.object_ids();
+
+        for ds in &mut self.display_sets {
+            ds.unshare_objects_avoiding(&mut used_ids);
+        }
+    }
+
+    /// Collects every object ID that is either defined or composed within this epoch.
+    fn object_ids(&self) -> BTreeSet<u16> {
+        self.display_sets.iter().flat_map(|ds| ds.object_ids()).collect()
+    }
+}
+
+/// The state that a display set inherits from the display sets preceding it within its epoch.
+#[derive(Clone, Debug, Default, Hash, PartialEq)]
+pub struct EpochContext {
+    /// The windows currently defined, keyed by ID. A window definition replaces every earlier
+    /// window, including those having IDs it does not define.
+    pub windows: BTreeMap<u8, Window>,
+    /// Every palette defined so far.
+    pub palettes: BTreeMap<Vid<u8>, Palette>,
+    /// Every object defined so far.
+    pub objects: BTreeMap<Vid<u16>, Object>,
+    /// The composition most recently shown by a display set that was not limited to updating
+    /// palettes. A palette update continues to show this composition.
+    pub composition: Composition,
+}
+
+impl EpochContext {
+
+    /// Incorporates the definitions made by a display set. If the display set begins a new
+    /// epoch, all previously inherited state is discarded first.
+    pub fn update(&mut self, ds: &DisplaySet) {
+
+        if ds.composition.state == CompositionState::EpochStart {
+            *self = EpochContext::default();
+        }
+
+        if !ds.windows.is_empty() {
+            self.windows = ds.windows.clone();
+        }
+
+        self.palettes.extend(ds.palettes.clone());
+        self.objects.extend(ds.objects.clone());
+
+        if !ds.palete_update_only {
+            self.composition = ds.composition.clone();
+        }
+    }
+
+    /// Builds an acquisition point at the specified PTS that replays what is needed to show the
+    /// current composition, which allows a player to begin showing it without any earlier
+    /// display set. The screen properties, palette ID, and composition number are taken from
+    /// `previous`, which should be the display set most recently incorporated. Every window is
+    /// included, along with the latest version of the active palette and of each object being
+    /// shown. Other palettes and objects defined earlier in the epoch are not replayed; see
+    /// [epoch_start](Self::epoch_start) for that. The DTS is zero.
+    pub fn acquisition_point(&self, previous: &DisplaySet, pts: u32) -> DisplaySet {
+
+        let mut ds = DisplaySet {
+            pts,
+            dts: 0,
+            width: previous.width,
+            height: previous.height,
+            frame_rate: previous.frame_rate,
+            palete_update_only: false,
+            palette_id: previous.palette_id,
+            windows: self.windows.clone(),
+            composition: Composition {
+                number: previous.composition.number,
+                state: CompositionState::AcquisitionPoint,
+                ..self.composition.clone()
+            },
+            ..Default::default()
+        };
+
+        for (vid, palette) in self.palettes.iter().rev() {
+            if vid.id == previous.palette_id {
+                ds.palettes.insert(vid.clone(), palette.clone());
+                break
+            }
+        }
+
+        for cid in ds.composition.objects.keys() {
+            if let Some((vid, object)) = self.objects.iter()
+                .rev()
+                .find(|(vid, _)| vid.id == cid.object_id) {
+                ds.objects.insert(vid.clone(), object.clone());
+            }
+        }
+
+        ds
+    }
+
+    /// Builds an [EpochStart](CompositionState::EpochStart) at the specified PTS that replays
+    /// all of this state, which allows later display sets of the epoch to be shown without any
+    /// earlier one. The screen properties, palette ID, and composition number are taken from
+    /// `previous` as they are for [acquisition_point](Self::acquisition_point). Every window is
+    /// included, along with the latest version of every palette and object defined so far,
+    /// whether or not it is currently in use. The DTS is zero.
+    pub fn epoch_start(&self, previous: &DisplaySet, pts: u32) -> DisplaySet {
+
+        let mut ds = self.acquisition_point(previous, pts);
+
+        ds.composition.state = CompositionState::EpochStart;
+        ds.palettes = latest_versions(&self.palettes);
+        ds.objects = latest_versions(&self.objects);
+
+        ds
+    }
+}
+
+/// Keeps only the latest version of each ID within a versioned collection.
+fn latest_versions<T: Copy + Ord, V: Clone>(map: &BTreeMap<Vid<T>, V>) -> BTreeMap<Vid<T>, V> {
+    map.iter()
+        .map(|(vid, value)| (vid.id, (vid, value)))
+        .collect::<BTreeMap<T, (&Vid<T>, &V)>>()
+        .into_values()
+        .map(|(vid, value)| (vid.clone(), value.clone()))
+        .collect()
+}
+
+/// Begins reading a stream from a seek point that may lie partway through an epoch, as a
+/// player does after seeking.
+///
+/// Ordinarily, a stream is expected to begin with an
+/// [EpochStart](CompositionState::EpochStart), as every later display set in an epoch relies on
+/// the windows, palettes, and objects that it defines. This instead discards display sets until
+/// one is found that can be shown on its own, which is either an `EpochStart` or an
+/// [AcquisitionPoint](CompositionState::AcquisitionPoint). That display set is returned along
+/// with the epoch state it establishes, which treats an opening `AcquisitionPoint` as defining
+/// the current state just as an `EpochStart` would. Subsequent display sets can then be read as
+/// usual and incorporated into the returned context with [update](EpochContext::update).
+pub fn read_from_midstream<R: Read>(
+    input: &mut R,
+    options: &ReadOptions,
+) -> Result<(DisplaySet, EpochContext), ReadError> {
+
+    loop {
+
+        let ds = input.read_display_set_with_options(options)?;
+
+        if ds.composition.state != CompositionState::Normal {
+
+            let mut context = EpochContext::default();
+
+            context.update(&ds);
+
+            return Ok((ds, context))
+        }
+    }
+}
+
+/// Reads every display set from an input, passes each one to a closure along with the state
+/// it inherits from its epoch, and then writes it to an output. The closure may modify the
+/// display set before it is written. Reading stops once the end of the input is reached.
+pub fn process<R, W, F>(mut input: R, mut output: W, mut f: F) -> Result<(), ProcessError> where
+    R: Read,
+    W: Write,
+    F: FnMut(&mut DisplaySet, &EpochContext),
+{
+    let mut context = EpochContext::default();
+
+    loop {
+
+        let mut ds = match input.read_display_set() {
+            Ok(ds) => {
+                ds
+            }
+            Err(ReadError::ReadError { source: SegmentReadError::IoError { source } })
+                if source.kind() == ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(err) => {
+                return Err(err.into())
+            }
+        };
+
+        if ds.composition.state == CompositionState::EpochStart {
+            context = EpochContext::default();
+        }
+
+        f(&mut ds, &context);
+        context.update(&ds);
+        output.write_display_set(ds)?;
+    }
+
+    Ok(())
+}
+
+/// Determines when each caption is shown and for how long, as `(show_pts, duration)` with the
+/// duration in 90 kHz ticks. This is useful for flagging captions that are shown too briefly to
+/// be read.
+///
+/// A caption begins with each DS that composes objects. It lasts until the next DS that either
+/// clears the screen or composes a different set of objects. Palette updates and acquisition
+/// points that repeat the same composition do not end a caption. A caption that is still being
+/// shown when the sequence ends has no known duration and is omitted.
+pub fn caption_durations(sets: &[DisplaySet]) -> Vec<(u32, u32)> {
+    caption_spans(sets).into_iter().map(|(start, end)| (start, end.wrapping_sub(start))).collect()
+}
+
+/// Reports authoring mistakes in caption timing that would otherwise only be noticed during
+/// playback. Captions are paired with the display sets that end them as they are for
+/// [caption_durations], and are reported in stream order. Short gaps between captions are not
+/// reported; see [timing_issues_with_min_gap].
+pub fn timing_issues(sets: &[DisplaySet]) -> Vec<TimingIssue> {
+    timing_issues_with_min_gap(sets, 0)
+}
+
+/// Reports caption timing issues as [timing_issues] does, additionally reporting a
+/// [ShortGap](TimingIssue::ShortGap) wherever the screen is cleared for fewer than
+/// `min_gap_ticks` (in 90 kHz ticks) between two captions. Captions that directly replace one
+/// another leave no gap and are not reported.
+pub fn timing_issues_with_min_gap(sets: &[DisplaySet], min_gap_ticks: u32) -> Vec<TimingIssue> {
+
+    let spans = caption_spans(sets);
+    let mut issues = Vec::<TimingIssue>::new();
+
+    for (index, &(start, end)) in spans.iter().enumerate() {
+        if end < start {
+            issues.push(TimingIssue::NegativeGap { a: start, end });
+        }
+        if let Some(&(next_start, _)) = spans.get(index + 1) {
+            if next_start < end {
+                issues.push(TimingIssue::Overlap { a: start, b: next_start });
+            } else if next_start > end && next_start - end < min_gap_ticks {
+                issues.push(TimingIssue::ShortGap { end, b: next_start });
+            }
+        }
+    }
+
+    issues
+}
+
+/// Writes the in and out points of each caption as SMPTE timecodes, for importing into an
+/// editing timeline as markers. Each caption is written on its own line as a three-digit
+/// sequence number followed by its in and out timecodes, separated by two spaces. Captions are
+/// delimited as they are for [caption_durations] and timecodes are produced by
+/// [ts_to_timecode], so they are drop-frame at 29.97 and 59.94 frames per second.
+pub fn export_edl_markers<W: Write>(
+    sets: &[DisplaySet],
+    rate: FrameRate,
+    out: &mut W,
+) -> Result<(), IoError> {
+
+    for (index, (start, end)) in caption_spans(sets).into_iter().enumerate() {
+        writeln!(
+            out,
+            "{:03}  {}  {}",
+            index + 1,
+            ts_to_timecode(start, rate),
+            ts_to_timecode(end, rate),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Pairs each caption with the display set that ends it, as `(show_pts, end_pts)`. See
+/// [caption_durations] for how captions are delimited.
+fn caption_spans(sets: &[DisplaySet]) -> Vec<(u32, u32)> {
+
+    let mut spans = Vec::<(u32, u32)>::new();
+    let mut current = None::<&DisplaySet>;
+
+    for ds in sets {
+
+        if ds.palete_update_only {
+            continue
+        }
+
+        if let Some(shown) = current {
+            if ds.composition.state == CompositionState::AcquisitionPoint
+                && ds.composition.objects == shown.composition.objects {
+                continue
+            }
+            if ds.clears_screen() || !ds.composition.objects.is_empty() {
+                spans.push((shown.pts, ds.pts));
+                current = None;
+            }
+        }
+
+        if !ds.composition.objects.is_empty() {
+            current = Some(ds);
+        }
+    }
+
+    spans
+}
+
+/// Merges consecutive epochs that show identical content with only a brief clear between
+/// them, which otherwise causes a caption to flicker on some players.
+///
+/// An epoch is merged into the one before it when the display set preceding its
+/// [EpochStart](CompositionState::EpochStart) clears the screen for less than `max_gap_ticks`,
+/// and what is shown before that clear has the same [visual content](ContentKey) as the
+/// `EpochStart`. The clearing display set is removed and the `EpochStart` is demoted to an
+/// [AcquisitionPoint](CompositionState::AcquisitionPoint), so the caption remains on screen
+/// continuously while still being refreshed for players that seek to it.
+pub fn coalesce_identical_epochs(sets: Vec<DisplaySet>, max_gap_ticks: u32) -> Vec<DisplaySet> {
+    remove_brief_clears(sets, max_gap_ticks, |_, reshow|
+        reshow.composition.state == CompositionState::EpochStart
+    )
+}
+
+/// Removes clearing display sets that are immediately followed by a re-show of the same
+/// composition, which otherwise causes a caption to flicker.
+///
+/// A display set that [clears the screen](DisplaySet::clears_screen) is removed when the
+/// display set following it is presented less than `max_gap_ticks` later and shows the same
+/// [visual content](ContentKey) as was shown before the clear. What each display set shows is
+/// resolved against the state it inherits from its epoch, so the screen dimensions, the active
+/// palette, the windows, and the latest version of each composed object must all match. Clears
+/// that define palettes or objects are always kept, as later display sets may rely on them. A
+/// re-show that begins a new epoch is demoted to an
+/// [AcquisitionPoint](CompositionState::AcquisitionPoint), just as
+/// [coalesce_identical_epochs] does.
+pub fn collapse_flicker(sets: Vec<DisplaySet>, max_gap_ticks: u32) -> Vec<DisplaySet> {
+    remove_brief_clears(sets, max_gap_ticks, |clear, _|
+        clear.palettes.is_empty() && clear.objects.is_empty()
+    )
+}
+
+/// Removes each display set that clears the screen for less than `max_gap_ticks` between two
+/// display sets showing the same content, provided that `removable` allows it for the clear and
+/// the display set following it. Should the following display set begin a new epoch, it is
+/// demoted to an acquisition point, as the epoch now continues through it.
+fn remove_brief_clears(
+    sets: Vec<DisplaySet>,
+    max_gap_ticks: u32,
+    removable: impl Fn(&DisplaySet, &DisplaySet) -> bool,
+) -> Vec<DisplaySet> {
+
+    let mut output = Vec::<DisplaySet>::with_capacity(sets.len());
+    let mut context = EpochContext::default();
+    let mut shown_before = None::<DisplaySet>;
+    let mut shown_last = None::<DisplaySet>;
+
+    for mut ds in sets {
+
+        context.update(&ds);
+
+        let shown = (!context.composition.objects.is_empty())
+            .then(|| context.acquisition_point(&ds, 0));
+
+        if let (Some(clear), Some(before), Some(after)) = (output.last(), &shown_before, &shown) {
+            if clear.clears_screen()
+                && ds.pts.wrapping_sub(clear.pts) < max_gap_ticks
+                && removable(clear, &ds)
+                && ContentKey(before) == ContentKey(after) {
+                output.pop();
+                shown_last = shown_before.take();
+                if ds.composition.state == CompositionState::EpochStart {
+                    ds.composition.state = CompositionState::AcquisitionPoint;
+                }
+            }
+        }
+
+        output.push(ds);
+        shown_before = mem::replace(&mut shown_last, shown);
+    }
+
+    output
+}
+
+/// Inserts acquisition points into long epochs so that players seeking into them can begin
+/// showing the current caption sooner.
+///
+/// Whenever more than `interval_ticks` would otherwise pass without an
+/// [EpochStart](CompositionState::EpochStart) or
+/// [AcquisitionPoint](CompositionState::AcquisitionPoint), an acquisition point is inserted
+/// `interval_ticks` after the previous one. Each inserted display set replays the windows most
+/// recently defined along with the palette and objects needed by the composition currently on
+/// screen, and reuses the composition number of the display set preceding it. Nothing is
+/// inserted while the screen is clear.
+pub fn insert_acquisition_points(sets: Vec<DisplaySet>, interval_ticks: u32) -> Vec<DisplaySet> {
+
+    let mut output = Vec::<DisplaySet>::with_capacity(sets.len());
+    let mut context = EpochContext::default();
+    let mut last_refresh = None::<u32>;
+
+    for ds in sets {
+
+        if let (Some(previous), Some(mut refreshed)) = (output.last(), last_refresh) {
+
+            let mut inserted = Vec::<DisplaySet>::new();
+
+            while interval_ticks > 0
+                && ds.composition.state != CompositionState::EpochStart
+                && !context.composition.objects.is_empty()
+                && ds.pts.saturating_sub(refreshed) > interval_ticks {
+                refreshed = match refreshed.checked_add(interval_ticks) {
+                    Some(refreshed) => refreshed,
+                    None => break,
+                };
+                inserted.push(context.acquisition_point(previous, refreshed));
+            }
+
+            output.extend(inserted);
+            last_refresh = Some(refreshed);
+        }
+
+        if ds.composition.state != CompositionState::Normal {
+            last_refresh = Some(ds.pts);
+        }
+
+        context.update(&ds);
+        output.push(ds);
+    }
+
+    output
+}
+
+/// Builds a fade-out of the palette having the specified ID, as defined by the latest version
+/// of it within `base`.
+///
+/// This produces `steps` display sets that only update that palette, evenly spaced over
+/// `duration_ticks` following the PTS of `base`. Each step ramps the alpha value of every
+/// entry from its value within `base` toward zero, which the final step reaches at the end of
+/// the duration. Each step continues the palette version and composition number of `base` and
+/// repeats its composition objects. Nothing is produced if `base` does not define the palette.
+///
+/// As each step must define a distinct palette version, `steps` is clamped to `255`.
+pub fn fade_out(
+    base: &DisplaySet,
+    palette_id: u8,
+    duration_ticks: u32,
+    steps: u32,
+) -> Vec<DisplaySet> {
+
+    let Some((vid, palette)) = base.palettes.iter().rev().find(|(vid, _)| vid.id == palette_id)
+    else {
+        return vec![]
+    };
+
+    let steps = steps.min(u8::MAX as u32);
+
+    (1..=steps).map(|step| {
+
+        let remaining = (steps - step) as f64 / steps as f64;
+        let mut faded = palette.clone();
+
+        for entry in faded.entries.values_mut() {
+            entry.alpha = (entry.alpha as f64 * remaining).round() as u8;
+        }
+
+        DisplaySet {
+            pts: base.pts.wrapping_add((duration_ticks as u64 * step as u64 / steps as u64) as u32),
+            dts: 0,
+            width: base.width,
+            height: base.height,
+            frame_rate: base.frame_rate,
+            palete_update_only: true,
+            palette_id,
+            palettes: BTreeMap::from([(
+                Vid {
+                    id: palette_id,
+                    version: vid.version.wrapping_add(step as u8),
+                },
+                faded,
+            )]),
+            composition: Composition {
+                number: base.composition.number.wrapping_add(step as u16),
+                state: CompositionState::Normal,
+                ..base.composition.clone()
+            },
+            ..Default::default()
+        }
+    }).collect()
+}
+
+/// Re-times a sequence of display sets by scaling each timestamp by `numerator / denominator`,
+/// rounding to the nearest tick. This is typically used for framerate conversions, such as a
+/// PAL speedup from 23.976 to 25 frames per second, which scales timestamps by
+/// `24_000 / 25_025`. Scaled timestamps that exceed the range of a PTS saturate. Nothing is
+/// changed if `denominator` is zero.
+pub fn retime(sets: &mut [DisplaySet], numerator: u32, denominator: u32) {
+
+    if denominator == 0 {
+        return
+    }
+
+    let scale = |ts: u32| {
+        let scaled = (ts as u64 * numerator as u64 + denominator as u64 / 2) / denominator as u64;
+        scaled.min(u32::MAX as u64) as u32
+    };
+
+    for ds in sets.iter_mut() {
+        ds.pts = scale(ds.pts);
+        ds.dts = scale(ds.dts);
+    }
+}
+
+/// Groups a sequence of display sets into epochs.
+pub fn group_epochs(sets: &[DisplaySet]) -> Vec<Epoch> {
+    epoch_ranges(sets).into_iter().map(|range|
+        Epoch {
+            display_sets: sets[range].to_vec(),
+        }
+    ).collect()
+}
+
+/// Lazily groups display sets into epochs as they are produced by an iterator, which allows
+/// whole captions to be processed while a stream is still being read. Each epoch begins at an
+/// [EpochStart](CompositionState::EpochStart) and continues until the next one. Should the
+/// first display set have any other composition state, as happens when a stream is cut partway
+/// through an epoch, it still begins a leading epoch.
+pub fn epochs<I: Iterator<Item = DisplaySet>>(iter: I) -> Epochs<I> {
+    Epochs { iter: iter.peekable() }
+}
+
+/// Lazily groups display sets into epochs. This is returned by [epochs].
+pub struct Epochs<I: Iterator<Item = DisplaySet>> {
+    iter: Peekable<I>,
+}
+
+impl<I: Iterator<Item = DisplaySet>> Iterator for Epochs<I> {
+
+    type Item = Epoch;
+
+    fn next(&mut self) -> Option<Self::Item> {
+
+        let mut display_sets = vec![self.iter.next()?];
+
+        while let Some(ds) = self.iter.next_if(|ds|
+            ds.composition.state != CompositionState::EpochStart
+        ) {
+            display_sets.push(ds);
+        }
+
+        Some(Epoch { display_sets })
+    }
+}
+
+/// Determines which epochs contain forced captions, so that a forced-only track can be
+/// extracted by keeping those epochs whole. Each epoch having at least one
+/// [forced](super::displayset::CompositionObject::forced) composition object in any of its
+/// display sets is identified by the index of its first display set within `sets`.
+pub fn forced_epochs(sets: &[DisplaySet]) -> Vec<usize> {
+    epoch_ranges(sets).into_iter()
+        .filter(|range| sets[range.clone()].iter()
+            .any(|ds| ds.composition.objects.values().any(|co| co.forced))
+        )
+        .map(|range| range.start)
+        .collect()
+}
+
+/// Locates the epoch that covers the specified PTS, so that a player can seek to a display set
+/// it is able to render rather than landing partway through an epoch. The epoch is identified by
+/// the index of its [EpochStart](CompositionState::EpochStart) within `sets`, and is taken to
+/// cover every PTS from its own until that of the next epoch. The last epoch covers every later
+/// PTS. Display sets are expected to be in order of increasing PTS. Nothing is returned if the
+/// PTS precedes the first epoch.
+pub fn epoch_index_at(sets: &[DisplaySet], pts: u32) -> Option<usize> {
+    epoch_ranges(sets).into_iter()
+        .map(|range| range.start)
+        .filter(|&start| sets[start].composition.state == CompositionState::EpochStart)
+        .take_while(|&start| sets[start].pts <= pts)
+        .last()
+}
+
+/// Verifies that the version of each object ID never decreases within an epoch.
+///
+/// Redefining an object under its current version is allowed, as acquisition points do this
+/// to replay the epoch, but only if its content is unchanged. Otherwise, a player that already
+/// holds that version may continue showing the stale object. As versions are not expected to
+/// wrap within a single epoch, a wrap from `255` back to `0` is also reported as a regression.
+pub fn check_object_version_monotonicity(epoch: &Epoch) -> Result<(), VersioningError> {
+
+    let mut latest = BTreeMap::<u16, (u8, &Object)>::new();
+
+    for ds in &epoch.display_sets {
+        for (vid, object) in &ds.objects {
+            if let Some((previous_version, previous_object)) = latest.get(&vid.id) {
+                if vid.version < *previous_version {
+                    return Err(
+                        VersioningError::VersionRegression {
+                            object_id: vid.id,
+                            previous_version: *previous_version,
+                            version: vid.version,
+                        }
+                    )
+                }
+                if vid.version == *previous_version
+                    && !object_content_eq(object, previous_object) {
+                    return Err(
+                        VersioningError::ConflictingDuplicate {
+                            object_id: vid.id,
+                            version: vid.version,
+                        }
+                    )
+                }
+            }
+            latest.insert(vid.id, (vid.version, object));
+        }
+    }
+
+    Ok(())
+}
+
+/// Describes a palette whose ID and version have been redefined by a later epoch with
+/// different entries.
+///
+/// Palette IDs are scoped to an epoch, so reusing one in a later epoch is allowed. Reusing the
+/// same version with conflicting content, however, can confuse a player that has seeked into
+/// the stream and may still be holding the earlier definition.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct PaletteScopeWarning {
+    /// The PTS of the display set containing the conflicting definition.
+    pub pts: u32,
+    /// The PTS of the display set containing the earlier definition.
+    pub previous_pts: u32,
+    /// The ID of the palette.
+    pub palette_id: u8,
+    /// The version of the palette.
+    pub version: u8,
+}
+
+/// Flags palette IDs that are redefined in a new epoch with the same version as in an earlier
+/// epoch, but with different entries.
+pub fn audit_palette_scoping(sets: &[DisplaySet]) -> Vec<PaletteScopeWarning> {
+
+    let mut warnings = Vec::<PaletteScopeWarning>::new();
+    let mut previous = BTreeMap::<(u8, u8), (u32, &Palette)>::new();
+
+    for range in epoch_ranges(sets) {
+
+        let mut current = BTreeMap::<(u8, u8), (u32, &Palette)>::new();
+
+        for ds in &sets[range] {
+            for (vid, palette) in &ds.palettes {
+                let key = (vid.id, vid.version);
+                if let Some((previous_pts, previous_palette)) = previous.get(&key) {
+                    if *previous_palette != palette
+                        && !warnings.iter().any(|w| (w.palette_id, w.version) == key
+                            && w.previous_pts == *previous_pts) {
+                        warnings.push(
+                            PaletteScopeWarning {
+                                pts: ds.pts,
+                                previous_pts: *previous_pts,
+                                palette_id: vid.id,
+                                version: vid.version,
+                            }
+                        );
+                    }
+                }
+                current.insert(key, (ds.pts, palette));
+            }
+        }
+
+        previous.extend(current);
+    }
+
+    warnings
+}
+
+/// Determines the range of display sets belonging to each epoch.
+fn epoch_ranges(sets: &[DisplaySet]) -> Vec<Range<usize>> {
+
+    let mut ranges = Vec::<Range<usize>>::new();
+    let mut start = 0;
+
+    for (index, ds) in sets.iter().enumerate() {
+        if ds.composition.state == CompositionState::EpochStart && index > start {
+            ranges.push(start..index);
+            start = index;
+        }
+    }
+
+    if start < sets.len() {
+        ranges.push(start..sets.len());
+    }
+
+    ranges
+}
@@ -0,0 +1,1005 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * To the extent possible under law, the person who associated CC0 with this file has waived all
+ * copyright and related or neighboring rights to this file.
+ *
+ * You should have received a copy of the CC0 legalcode along with this work. If not, see
+ * <http://creativecommons.org/publicdomain/zero/1.0/>.
+ *
+ * SPDX-License-Identifier: CC0-1.0
+ */
+
+use super::{
+    *,
+    super::{
+        displayset::{Cid, CompositionObject, PaletteEntry},
+        segment::{Crop, FrameRate},
+        render::render_frame,
+    },
+};
+
+fn display_set(pts: u32, state: CompositionState) -> DisplaySet {
+    DisplaySet {
+        pts,
+        composition: Composition {
+            state,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn palette(y: u8) -> Palette {
+
+    let mut entries = BTreeMap::<u8, PaletteEntry>::new();
+
+    entries.insert(
+        0,
+        PaletteEntry {
+            y,
+            cr: 128,
+            cb: 128,
+            alpha: 255,
+        },
+    );
+
+    Palette { entries }
+}
+
+fn caption(pts: u32, state: CompositionState, object_id: u16) -> DisplaySet {
+
+    let mut ds = display_set(pts, state);
+
+    ds.windows.insert(0, Window::default());
+    ds.composition.objects.insert(
+        Cid {
+            object_id,
+            window_id: 0,
+        },
+        CompositionObject::default(),
+    );
+
+    ds
+}
+
+#[test]
+fn test_epoch_ranges() {
+
+    let sets = vec![
+        display_set(0, CompositionState::Normal),
+        display_set(1, CompositionState::EpochStart),
+        display_set(2, CompositionState::AcquisitionPoint),
+        display_set(3, CompositionState::Normal),
+        display_set(4, CompositionState::EpochStart),
+    ];
+
+    assert_eq!(epoch_ranges(&sets), vec![0..1, 1..4, 4..5]);
+    assert!(epoch_ranges(&[]).is_empty());
+}
+
+#[test]
+fn test_audit_palette_scoping() {
+
+    let mut first = display_set(1_000, CompositionState::EpochStart);
+    let mut acquisition = display_set(2_000, CompositionState::AcquisitionPoint);
+    let mut second = display_set(3_000, CompositionState::EpochStart);
+    let mut third = display_set(4_000, CompositionState::EpochStart);
+
+    first.palettes.insert(Vid { id: 0, version: 0 }, palette(16));
+    first.palettes.insert(Vid { id: 1, version: 0 }, palette(16));
+    acquisition.palettes.insert(Vid { id: 0, version: 0 }, palette(16));
+    second.palettes.insert(Vid { id: 0, version: 0 }, palette(235));
+    second.palettes.insert(Vid { id: 1, version: 0 }, palette(16));
+    third.palettes.insert(Vid { id: 0, version: 1 }, palette(128));
+
+    let warnings = audit_palette_scoping(&[first, acquisition, second, third]);
+
+    assert_eq!(
+        warnings,
+        vec![
+            PaletteScopeWarning {
+                pts: 3_000,
+                previous_pts: 2_000,
+                palette_id: 0,
+                version: 0,
+            },
+        ],
+    );
+}
+
+#[test]
+fn test_group_epochs() {
+
+    let sets = vec![
+        display_set(1, CompositionState::EpochStart),
+        display_set(2, CompositionState::Normal),
+        display_set(3, CompositionState::EpochStart),
+    ];
+    let epochs = group_epochs(&sets);
+
+    assert_eq!(epochs.len(), 2);
+    assert_eq!(epochs[0].display_sets, sets[0..2]);
+    assert_eq!(epochs[1].display_sets, sets[2..3]);
+}
+
+#[test]
+fn test_epochs_iterator() {
+
+    let sets = vec![
+        display_set(1, CompositionState::AcquisitionPoint),
+        display_set(2, CompositionState::Normal),
+        display_set(3, CompositionState::EpochStart),
+        display_set(4, CompositionState::AcquisitionPoint),
+        display_set(5, CompositionState::Normal),
+        display_set(6, CompositionState::EpochStart),
+    ];
+    let grouped = epochs(sets.clone().into_iter()).collect::<Vec<Epoch>>();
+
+    assert_eq!(grouped.len(), 3);
+    assert_eq!(grouped[0].display_sets, sets[0..2]);
+    assert_eq!(grouped[1].display_sets, sets[2..5]);
+    assert_eq!(grouped[2].display_sets, sets[5..6]);
+    assert_eq!(grouped, group_epochs(&sets));
+    assert_eq!(epochs(std::iter::empty()).count(), 0);
+}
+
+#[test]
+fn test_epoch_context_replaces_windows() {
+
+    let mut first = display_set(0, CompositionState::EpochStart);
+    let second = display_set(90_000, CompositionState::Normal);
+    let mut third = display_set(180_000, CompositionState::Normal);
+    let window = |x| Window { x, y: 0, width: 10, height: 10 };
+    let mut context = EpochContext::default();
+
+    first.windows.insert(0, window(0));
+    first.windows.insert(1, window(100));
+    third.windows.insert(1, window(200));
+
+    context.update(&first);
+    context.update(&second);
+
+    assert_eq!(context.windows, first.windows);
+
+    context.update(&third);
+
+    assert_eq!(context.windows, third.windows);
+}
+
+#[test]
+fn test_epoch_context_epoch_start() {
+
+    let mut first = caption(1_000, CompositionState::EpochStart, 0);
+    let second = caption(2_000, CompositionState::Normal, 1);
+    let mut context = EpochContext::default();
+
+    first.palettes.insert(Vid { id: 0, version: 0 }, palette(16));
+    first.palettes.insert(Vid { id: 0, version: 1 }, palette(235));
+    first.palettes.insert(Vid { id: 1, version: 0 }, palette(128));
+    first.objects.insert(Vid { id: 0, version: 0 }, Object::default());
+    context.update(&first);
+    context.update(&second);
+
+    let ds = context.epoch_start(&second, 2_500);
+
+    assert_eq!(ds.pts, 2_500);
+    assert_eq!(ds.composition.state, CompositionState::EpochStart);
+    assert_eq!(ds.composition.objects, second.composition.objects);
+    assert_eq!(ds.windows, context.windows);
+    assert_eq!(
+        ds.palettes.keys().cloned().collect::<Vec<_>>(),
+        vec![Vid { id: 0, version: 1 }, Vid { id: 1, version: 0 }],
+    );
+    assert_eq!(ds.objects.keys().cloned().collect::<Vec<_>>(), vec![Vid { id: 0, version: 0 }]);
+    assert!(context.acquisition_point(&second, 2_500).objects.is_empty());
+}
+
+#[test]
+fn test_epoch_peak_counts() {
+
+    let mut start = display_set(1_000, CompositionState::EpochStart);
+    let mut update = display_set(2_000, CompositionState::Normal);
+    let clear = display_set(3_000, CompositionState::Normal);
+
+    for window_id in 0..2 {
+        start.windows.insert(window_id, Window::default());
+        start.composition.objects.insert(
+            Cid {
+                object_id: window_id as u16,
+                window_id,
+            },
+            CompositionObject::default(),
+        );
+    }
+
+    update.windows.insert(0, Window::default());
+
+    for object_id in 0..3 {
+        update.composition.objects.insert(
+            Cid {
+                object_id,
+                window_id: 0,
+            },
+            CompositionObject::default(),
+        );
+    }
+
+    let epoch = Epoch {
+        display_sets: vec![start, update, clear],
+    };
+
+    assert_eq!(epoch.peak_window_count(), 2);
+    assert_eq!(epoch.peak_object_count(), 3);
+    assert_eq!(Epoch::default().peak_window_count(), 0);
+    assert_eq!(Epoch::default().peak_object_count(), 0);
+}
+
+#[test]
+fn test_process() {
+
+    let mut first = display_set(1_000, CompositionState::EpochStart);
+    let mut second = display_set(2_000, CompositionState::Normal);
+    let third = display_set(3_000, CompositionState::EpochStart);
+    let mut input = vec![];
+    let mut output = vec![];
+    let mut inherited = vec![];
+
+    first.windows.insert(0, Window::default());
+    first.palettes.insert(Vid::default(), palette(16));
+    second.palettes.insert(Vid { id: 0, version: 1 }, palette(235));
+
+    for ds in [first, second, third] {
+        input.write_display_set(ds).unwrap();
+    }
+
+    process(input.as_slice(), &mut output, |ds, context| {
+        inherited.push((context.windows.len(), context.palettes.len()));
+        ds.pts += 90_000;
+    }).unwrap();
+
+    assert_eq!(inherited, vec![(0, 0), (1, 1), (0, 0)]);
+
+    let mut output = output.as_slice();
+    let pts = (0..3).map(|_| output.read_display_set().unwrap().pts).collect::<Vec<_>>();
+
+    assert_eq!(pts, vec![91_000, 92_000, 93_000]);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_caption_durations() {
+
+    let mut fade = display_set(3_000, CompositionState::Normal);
+
+    fade.palete_update_only = true;
+
+    let sets = vec![
+        caption(1_000, CompositionState::EpochStart, 0),
+        caption(2_000, CompositionState::AcquisitionPoint, 0),
+        fade,
+        display_set(5_000, CompositionState::Normal),
+        caption(6_000, CompositionState::EpochStart, 1),
+        caption(7_000, CompositionState::Normal, 2),
+        display_set(9_000, CompositionState::Normal),
+        caption(10_000, CompositionState::EpochStart, 3),
+    ];
+
+    assert_eq!(caption_durations(&sets), vec![(1_000, 4_000), (6_000, 1_000), (7_000, 2_000)]);
+    assert!(caption_durations(&[]).is_empty());
+}
+
+#[test]
+fn test_coalesce_identical_epochs() {
+
+    let sets = vec![
+        caption(1_000, CompositionState::EpochStart, 0),
+        display_set(5_000, CompositionState::Normal),
+        caption(5_100, CompositionState::EpochStart, 0),
+        display_set(9_000, CompositionState::Normal),
+        caption(10_000, CompositionState::EpochStart, 0),
+        display_set(12_000, CompositionState::Normal),
+        caption(12_050, CompositionState::EpochStart, 1),
+        display_set(15_000, CompositionState::Normal),
+    ];
+    let coalesced = coalesce_identical_epochs(sets.clone(), 500);
+
+    assert_eq!(
+        coalesced.iter().map(|ds| (ds.pts, ds.composition.state)).collect::<Vec<_>>(),
+        vec![
+            (1_000, CompositionState::EpochStart),
+            (5_100, CompositionState::AcquisitionPoint),
+            (9_000, CompositionState::Normal),
+            (10_000, CompositionState::EpochStart),
+            (12_000, CompositionState::Normal),
+            (12_050, CompositionState::EpochStart),
+            (15_000, CompositionState::Normal),
+        ],
+    );
+    assert_eq!(group_epochs(&coalesced).len(), 3);
+    assert_eq!(coalesce_identical_epochs(sets.clone(), 0), sets);
+}
+
+#[test]
+fn test_collapse_flicker() {
+
+    let show = |pts, state, y| {
+        let mut ds = display_set(pts, state);
+        ds.width = 8;
+        ds.height = 8;
+        ds.palettes.insert(Vid { id: 0, version: y }, palette(y));
+        ds.composition.objects.insert(
+            Cid {
+                object_id: 0,
+                window_id: 0,
+            },
+            CompositionObject::default(),
+        );
+        ds
+    };
+    let mut first = show(1_000, CompositionState::EpochStart, 16);
+
+    first.windows.insert(0, Window::default());
+    first.objects.insert(Vid::default(), Object::default());
+
+    let sets = vec![
+        first,
+        display_set(5_000, CompositionState::Normal),
+        show(5_010, CompositionState::Normal, 16),
+        display_set(9_000, CompositionState::Normal),
+        show(9_010, CompositionState::Normal, 235),
+        display_set(12_000, CompositionState::Normal),
+    ];
+    let collapsed = collapse_flicker(sets.clone(), 100);
+
+    assert_eq!(
+        collapsed.iter().map(|ds| ds.pts).collect::<Vec<_>>(),
+        vec![1_000, 5_010, 9_000, 9_010, 12_000],
+    );
+    assert_eq!(collapse_flicker(sets.clone(), 10), sets);
+}
+
+#[test]
+fn test_insert_acquisition_points() {
+
+    let caption = |pts, state, object_id: u16| {
+        let mut ds = display_set(pts, state);
+        ds.width = 8;
+        ds.height = 8;
+        ds.windows.insert(
+            0,
+            Window {
+                x: 0,
+                y: 0,
+                width: 8,
+                height: 8,
+            },
+        );
+        ds.palettes.insert(Vid::default(), palette(235));
+        ds.objects.insert(
+            Vid {
+                id: object_id,
+                version: 0,
+            },
+            Object {
+                width: 8,
+                height: 8,
+                lines: vec![vec![0; 8]; object_id as usize + 1],
+            },
+        );
+        ds.composition.objects.insert(
+            Cid {
+                object_id,
+                window_id: 0,
+            },
+            CompositionObject::default(),
+        );
+        ds
+    };
+    let mut clear = display_set(2_500, CompositionState::Normal);
+
+    clear.width = 8;
+    clear.height = 8;
+
+    let sets = vec![
+        caption(0, CompositionState::EpochStart, 0),
+        caption(1_000, CompositionState::Normal, 1),
+        clear,
+        display_set(5_000, CompositionState::Normal),
+    ];
+    let output = insert_acquisition_points(sets.clone(), 900);
+
+    assert_eq!(
+        output.iter().map(|ds| (ds.pts, ds.composition.state)).collect::<Vec<_>>(),
+        vec![
+            (0, CompositionState::EpochStart),
+            (900, CompositionState::AcquisitionPoint),
+            (1_000, CompositionState::Normal),
+            (1_800, CompositionState::AcquisitionPoint),
+            (2_500, CompositionState::Normal),
+            (5_000, CompositionState::Normal),
+        ],
+    );
+
+    let mut context = EpochContext::default();
+
+    for (index, ds) in output.iter().enumerate() {
+        if ds.composition.state == CompositionState::AcquisitionPoint {
+            assert_eq!(
+                render_frame(ds, &EpochContext::default()).unwrap(),
+                render_frame(&output[index - 1], &context).unwrap(),
+            );
+            assert!(ds.objects.keys().all(|vid| ds.composition.objects.keys()
+                .any(|cid| cid.object_id == vid.id)));
+        }
+        context.update(ds);
+    }
+
+    assert_eq!(insert_acquisition_points(sets.clone(), 0), sets);
+
+    let sets = vec![
+        caption(u32::MAX - 1_000, CompositionState::EpochStart, 0),
+        display_set(u32::MAX, CompositionState::Normal),
+    ];
+    let output = insert_acquisition_points(sets, 900);
+
+    assert_eq!(
+        output.iter().map(|ds| (ds.pts, ds.composition.state)).collect::<Vec<_>>(),
+        vec![
+            (u32::MAX - 1_000, CompositionState::EpochStart),
+            (u32::MAX - 100, CompositionState::AcquisitionPoint),
+            (u32::MAX, CompositionState::Normal),
+        ],
+    );
+}
+
+#[test]
+fn test_insert_acquisition_points_after_window_change() {
+
+    let cid = Cid { object_id: 0, window_id: 0 };
+    let window = |x| Window { x, y: 0, width: 8, height: 8 };
+    let mut first = display_set(0, CompositionState::EpochStart);
+    let mut second = display_set(1_000, CompositionState::Normal);
+
+    for ds in [&mut first, &mut second] {
+        ds.width = 32;
+        ds.height = 8;
+        ds.composition.objects.insert(cid.clone(), CompositionObject::default());
+    }
+
+    first.windows.insert(0, window(0));
+    first.windows.insert(1, window(16));
+    first.palettes.insert(Vid::default(), palette(235));
+    first.objects.insert(
+        Vid::default(),
+        Object {
+            width: 8,
+            height: 8,
+            lines: vec![vec![0; 8]; 8],
+        },
+    );
+    second.windows.insert(0, window(8));
+    second.composition.objects.get_mut(&cid).unwrap().x = 8;
+
+    let mut context = EpochContext::default();
+
+    context.update(&first);
+
+    let rendered = render_frame(&second, &context).unwrap();
+    let sets = vec![first, second.clone(), display_set(2_500, CompositionState::Normal)];
+    let output = insert_acquisition_points(sets, 900);
+
+    assert_eq!(output.len(), 5);
+    assert_eq!(output[1].windows.len(), 2);
+    assert_eq!(output[3].pts, 1_800);
+    assert_eq!(output[3].windows, second.windows);
+    assert_eq!(render_frame(&output[3], &EpochContext::default()).unwrap(), rendered);
+}
+
+#[test]
+fn test_read_from_midstream() {
+
+    let mut orphaned = display_set(1_000, CompositionState::Normal);
+    let mut acquisition = display_set(2_000, CompositionState::AcquisitionPoint);
+    let clear = display_set(3_000, CompositionState::Normal);
+    let cid = Cid {
+        object_id: 0,
+        window_id: 0,
+    };
+    let mut input = vec![];
+
+    orphaned.composition.objects.insert(cid.clone(), CompositionObject::default());
+    acquisition.width = 4;
+    acquisition.height = 4;
+    acquisition.windows.insert(0, Window { x: 0, y: 0, width: 4, height: 4 });
+    acquisition.palettes.insert(Vid::default(), palette(235));
+    acquisition.objects.insert(
+        Vid::default(),
+        Object {
+            width: 2,
+            height: 1,
+            lines: vec![vec![0, 0]],
+        },
+    );
+    acquisition.composition.objects.insert(cid, CompositionObject { x: 1, ..Default::default() });
+
+    for ds in [orphaned, acquisition.clone(), clear.clone()] {
+        input.write_display_set(ds).unwrap();
+    }
+
+    let mut input = input.as_slice();
+    let (ds, mut context) = read_from_midstream(&mut input, &ReadOptions::default()).unwrap();
+    let frame = render_frame(&ds, &context).unwrap();
+
+    assert_eq!(ds.pts, acquisition.pts);
+    assert_eq!(ds.composition.objects, acquisition.composition.objects);
+    assert_eq!(context.windows, acquisition.windows);
+    assert_eq!(context.objects, acquisition.objects);
+    assert_eq!(frame.pixel(0, 0), Some(&PaletteEntry::default()));
+    assert_eq!(frame.pixel(1, 0), Some(&palette(235).entries[&0]));
+
+    let ds = input.read_display_set().unwrap();
+
+    context.update(&ds);
+
+    assert_eq!(ds.pts, clear.pts);
+    assert!(context.composition.objects.is_empty());
+    assert!(input.is_empty());
+}
+
+#[test]
+fn test_fade_out() {
+
+    let mut base = display_set(1_000, CompositionState::EpochStart);
+    let mut half = palette(235);
+
+    half.entries.get_mut(&0).unwrap().alpha = 128;
+    base.palettes.insert(Vid { id: 2, version: 3 }, palette(235));
+    base.palettes.insert(Vid { id: 2, version: 4 }, half);
+    base.composition.number = 10;
+    base.composition.objects.insert(Cid::default(), CompositionObject::default());
+
+    let steps = fade_out(&base, 2, 900, 4);
+
+    assert_eq!(steps.len(), 4);
+    assert_eq!(steps.iter().map(|ds| ds.pts).collect::<Vec<_>>(), vec![1_225, 1_450, 1_675, 1_900]);
+    assert_eq!(
+        steps.iter().map(|ds| ds.composition.number).collect::<Vec<_>>(),
+        vec![11, 12, 13, 14],
+    );
+    assert_eq!(
+        steps.iter().enumerate().map(|(index, ds)|
+            ds.palettes[&Vid { id: 2, version: 5 + index as u8 }].entries[&0].alpha
+        ).collect::<Vec<_>>(),
+        vec![96, 64, 32, 0],
+    );
+    assert!(steps.iter().all(|ds|
+        ds.palete_update_only
+            && ds.palette_id == 2
+            && ds.composition.state == CompositionState::Normal
+            && ds.composition.objects == base.composition.objects
+    ));
+    assert!(steps.last().unwrap().palettes.values()
+        .flat_map(|palette| palette.entries.values())
+        .all(|entry| entry.alpha == 0)
+    );
+    assert!(fade_out(&base, 1, 900, 4).is_empty());
+    assert!(fade_out(&base, 2, 900, 0).is_empty());
+
+    let steps = fade_out(&base, 2, 900, 1_000);
+    let versions = steps.iter()
+        .flat_map(|ds| ds.palettes.keys().map(|vid| vid.version))
+        .chain([4])
+        .collect::<BTreeSet<u8>>();
+
+    assert_eq!(steps.len(), 255);
+    assert_eq!(versions.len(), 256);
+    assert_eq!(steps.last().unwrap().pts, 1_900);
+}
+
+#[test]
+fn test_check_object_version_monotonicity() {
+
+    let object = |width: u16| Object {
+        width,
+        height: 1,
+        lines: vec![vec![0; width as usize]],
+    };
+    let mut first = display_set(1_000, CompositionState::EpochStart);
+    let mut second = display_set(2_000, CompositionState::AcquisitionPoint);
+    let mut third = display_set(3_000, CompositionState::Normal);
+
+    first.objects.insert(Vid { id: 1, version: 0 }, object(4));
+    first.objects.insert(Vid { id: 2, version: 3 }, object(4));
+    second.objects.insert(Vid { id: 1, version: 0 }, object(4));
+    second.objects.insert(Vid { id: 2, version: 4 }, object(8));
+    third.objects.insert(Vid { id: 2, version: 1 }, object(4));
+
+    let mut epoch = Epoch {
+        display_sets: vec![first, second],
+    };
+
+    assert_eq!(check_object_version_monotonicity(&epoch), Ok(()));
+
+    epoch.display_sets.push(third);
+
+    assert_eq!(
+        check_object_version_monotonicity(&epoch),
+        Err(VersioningError::VersionRegression { object_id: 2, previous_version: 4, version: 1 }),
+    );
+
+    epoch.display_sets[2].objects.clear();
+    epoch.display_sets[2].objects.insert(Vid { id: 1, version: 0 }, object(2));
+
+    assert_eq!(
+        check_object_version_monotonicity(&epoch),
+        Err(VersioningError::ConflictingDuplicate { object_id: 1, version: 0 }),
+    );
+}
+
+#[test]
+fn test_retime_23_976_to_25() {
+
+    let (src_num, src_den) = FrameRate::Fps23_976.ratio();
+    let (dst_num, dst_den) = FrameRate::Fps25.ratio();
+    let mut sets = vec![
+        display_set(0, CompositionState::EpochStart),
+        display_set(25_025, CompositionState::Normal),
+        display_set(90_000, CompositionState::Normal),
+        display_set(324_000_000, CompositionState::Normal),
+        display_set(u32::MAX, CompositionState::Normal),
+    ];
+
+    retime(&mut sets, src_num * dst_den, src_den * dst_num);
+
+    assert_eq!(
+        sets.iter().map(|ds| ds.pts).collect::<Vec<_>>(),
+        vec![0, 24_000, 86_314, 310_729_271, 4_119_049_554],
+    );
+}
+
+#[test]
+fn test_unify_palettes() {
+
+    let large_palette = |y: u8| Palette {
+        entries: (0..128).map(|id| (id, PaletteEntry { y, cr: id, cb: 128, alpha: 255 })).collect(),
+    };
+    let object = || Object {
+        width: 2,
+        height: 1,
+        lines: vec![vec![0, 127]],
+    };
+    let mut first = display_set(0, CompositionState::EpochStart);
+    let mut second = display_set(90_000, CompositionState::Normal);
+
+    first.palettes.insert(Vid { id: 0, version: 0 }, large_palette(16));
+    first.objects.insert(Vid { id: 1, version: 0 }, object());
+    second.palette_id = 1;
+    second.palettes.insert(Vid { id: 1, version: 0 }, large_palette(235));
+    second.objects.insert(Vid { id: 2, version: 0 }, object());
+
+    let mut epoch = Epoch {
+        display_sets: vec![first, second],
+    };
+
+    epoch.unify_palettes().unwrap();
+
+    let first = &epoch.display_sets[0];
+    let second = &epoch.display_sets[1];
+    let merged = &second.palettes[&Vid { id: 0, version: 1 }];
+
+    assert_eq!(first.palettes[&Vid { id: 0, version: 0 }].entries.len(), 128);
+    assert_eq!(first.objects[&Vid { id: 1, version: 0 }].lines, vec![vec![0, 127]]);
+    assert_eq!(second.palette_id, 0);
+    assert_eq!(second.objects[&Vid { id: 2, version: 0 }].lines, vec![vec![128, 255]]);
+    assert_eq!(merged.entries.len(), 256);
+    assert_eq!(merged.entries[&127], PaletteEntry { y: 16, cr: 127, cb: 128, alpha: 255 });
+    assert_eq!(merged.entries[&255], PaletteEntry { y: 235, cr: 127, cb: 128, alpha: 255 });
+
+    let unchanged = epoch.clone();
+
+    epoch.display_sets[1].palettes.insert(Vid { id: 2, version: 0 }, palette(128));
+
+    assert_eq!(epoch.unify_palettes(), Err(PaletteOverflowError { entry_count: 257 }));
+    assert_eq!(epoch.display_sets[0], unchanged.display_sets[0]);
+}
+
+#[test]
+fn test_tile_oversized_objects() {
+
+    let object = |width: u16| Object {
+        width,
+        height: 8,
+        lines: vec![vec![1; width as usize]; 8],
+    };
+    let mut first = caption(1_000, CompositionState::EpochStart, 0);
+    let mut second = caption(2_000, CompositionState::Normal, 1);
+
+    first.objects.insert(Vid { id: 0, version: 0 }, object(32));
+    second.objects.insert(Vid { id: 1, version: 0 }, object(8));
+
+    let mut epoch = Epoch {
+        display_sets: vec![first, second],
+    };
+
+    epoch.tile_oversized_objects(16).unwrap();
+
+    assert_eq!(
+        epoch.display_sets[0].objects.keys().cloned().collect::<Vec<_>>(),
+        vec![Vid { id: 0, version: 0 }, Vid { id: 2, version: 0 }],
+    );
+    assert_eq!(
+        epoch.display_sets[1].objects.keys().cloned().collect::<Vec<_>>(),
+        vec![Vid { id: 1, version: 0 }],
+    );
+
+    let tiled = epoch.clone();
+
+    assert!(matches!(
+        epoch.tile_oversized_objects(4),
+        Err(TileError::TileTooSmall { .. }) | Err(TileError::TooManyCompositionObjects { .. }),
+    ));
+    assert_eq!(epoch, tiled);
+}
+
+#[test]
+fn test_unshare_objects() {
+
+    let mut first = caption(1_000, CompositionState::EpochStart, 0);
+    let mut second = caption(2_000, CompositionState::Normal, 1);
+
+    first.windows.insert(1, Window::default());
+    first.objects.insert(Vid { id: 0, version: 0 }, Object::default());
+    first.composition.objects.insert(
+        Cid {
+            object_id: 0,
+            window_id: 1,
+        },
+        CompositionObject::default(),
+    );
+    second.objects.insert(Vid { id: 1, version: 0 }, Object::default());
+
+    let mut epoch = Epoch {
+        display_sets: vec![first, second],
+    };
+
+    epoch.unshare_objects();
+
+    assert_eq!(
+        epoch.display_sets[0].composition.objects.keys().cloned().collect::<Vec<_>>(),
+        vec![Cid { object_id: 0, window_id: 0 }, Cid { object_id: 2, window_id: 1 }],
+    );
+    assert_eq!(
+        epoch.display_sets[1].composition.objects.keys().cloned().collect::<Vec<_>>(),
+        vec![Cid { object_id: 1, window_id: 0 }],
+    );
+}
+
+#[test]
+fn test_bake_crops() {
+
+    let mut first = display_set(0, CompositionState::EpochStart);
+    let mut second = display_set(90_000, CompositionState::Normal);
+    let mut third = display_set(180_000, CompositionState::Normal);
+    let crop = |x, width| CompositionObject {
+        crop: Some(Crop { x, y: 0, width, height: 1 }),
+        ..Default::default()
+    };
+    let cid = |object_id| Cid { object_id, window_id: 0 };
+
+    for ds in [&mut first, &mut second, &mut third] {
+        ds.width = 4;
+        ds.height = 1;
+    }
+
+    first.windows.insert(0, Window { x: 0, y: 0, width: 4, height: 1 });
+    first.palettes.insert(Vid { id: 0, version: 0 }, palette(235));
+    first.objects.insert(
+        Vid { id: 0, version: 0 },
+        Object {
+            width: 4,
+            height: 1,
+            lines: vec![vec![0, 1, 0, 1]],
+        },
+    );
+    first.composition.objects.insert(cid(0), crop(0, 2));
+    second.composition.objects.insert(cid(0), crop(1, 3));
+    third.composition.objects.insert(cid(0), crop(0, 2));
+
+    let mut epoch = Epoch {
+        display_sets: vec![first, second, third],
+    };
+    let frames = |epoch: &Epoch| {
+        let mut context = EpochContext::default();
+        epoch.display_sets.iter()
+            .map(|ds| {
+                let frame = render_frame(ds, &context).unwrap();
+                context.update(ds);
+                frame
+            })
+            .collect::<Vec<_>>()
+    };
+    let rendered = frames(&epoch);
+
+    epoch.bake_crops();
+
+    let [first, second, third] = &epoch.display_sets[..] else {
+        panic!("display set count changed");
+    };
+
+    assert!(epoch.display_sets.iter()
+        .all(|ds| ds.composition.objects.values().all(|co| co.crop.is_none())));
+    assert_eq!(first.composition.objects.keys().collect::<Vec<_>>(), vec![&cid(1)]);
+    assert_eq!(second.composition.objects.keys().collect::<Vec<_>>(), vec![&cid(2)]);
+    assert_eq!(third.composition.objects.keys().collect::<Vec<_>>(), vec![&cid(1)]);
+    assert_eq!(first.objects.keys().collect::<Vec<_>>(), vec![&Vid { id: 1, version: 0 }]);
+    assert_eq!(second.objects.keys().collect::<Vec<_>>(), vec![&Vid { id: 2, version: 0 }]);
+    assert!(third.objects.is_empty());
+    assert_eq!(second.objects[&Vid { id: 2, version: 0 }].lines, vec![vec![1, 0, 1]]);
+    assert_eq!(frames(&epoch), rendered);
+}
+
+#[test]
+fn test_recenter() {
+
+    let cid = Cid { object_id: 1, window_id: 0 };
+    let mut first = display_set(0, CompositionState::EpochStart);
+    let mut second = display_set(90_000, CompositionState::Normal);
+
+    for ds in [&mut first, &mut second] {
+        ds.width = 1920;
+        ds.height = 1080;
+    }
+
+    first.windows.insert(0, Window { x: 100, y: 900, width: 100, height: 100 });
+    first.objects.insert(
+        Vid { id: 1, version: 0 },
+        Object {
+            width: 4,
+            height: 2,
+            lines: vec![vec![0; 4]; 2],
+        },
+    );
+    first.composition.objects.insert(
+        cid.clone(),
+        CompositionObject {
+            x: 120,
+            y: 950,
+            ..Default::default()
+        },
+    );
+    second.composition.objects.insert(
+        cid.clone(),
+        CompositionObject {
+            x: 198,
+            y: 998,
+            crop: Some(Crop { x: 2, y: 0, width: 2, height: 2 }),
+            ..Default::default()
+        },
+    );
+
+    let mut epoch = Epoch {
+        display_sets: vec![first, second],
+    };
+    let positions = |epoch: &Epoch| {
+        let window = &epoch.display_sets[0].windows[&0];
+        let first = &epoch.display_sets[0].composition.objects[&cid];
+        let second = &epoch.display_sets[1].composition.objects[&cid];
+        [(window.x, window.y), (first.x, first.y), (second.x, second.y)]
+    };
+
+    epoch.recenter_horizontal().unwrap();
+
+    assert_eq!(positions(&epoch), [(910, 900), (930, 950), (1_008, 998)]);
+
+    epoch.recenter_vertical().unwrap();
+
+    assert_eq!(positions(&epoch), [(910, 490), (930, 540), (1_008, 588)]);
+
+    epoch.display_sets[0].width = 50;
+
+    assert_eq!(
+        epoch.recenter_horizontal(),
+        Err(RecenterError::ContentTooLarge { content_size: 100, screen_size: 50 }),
+    );
+    assert_eq!(positions(&epoch), [(910, 490), (930, 540), (1_008, 588)]);
+}
+
+#[test]
+fn test_timing_issues() {
+
+    let clean = vec![
+        caption(1_000, CompositionState::EpochStart, 0),
+        display_set(5_000, CompositionState::Normal),
+        caption(6_000, CompositionState::EpochStart, 1),
+        caption(7_000, CompositionState::Normal, 2),
+        display_set(9_000, CompositionState::Normal),
+    ];
+    let broken = vec![
+        caption(1_000, CompositionState::EpochStart, 0),
+        display_set(5_000, CompositionState::Normal),
+        caption(4_000, CompositionState::EpochStart, 1),
+        display_set(6_000, CompositionState::Normal),
+        caption(7_000, CompositionState::EpochStart, 2),
+        display_set(6_500, CompositionState::Normal),
+    ];
+
+    assert!(timing_issues(&clean).is_empty());
+    assert!(timing_issues(&[]).is_empty());
+    assert_eq!(
+        timing_issues(&broken),
+        vec![
+            TimingIssue::Overlap { a: 1_000, b: 4_000 },
+            TimingIssue::NegativeGap { a: 7_000, end: 6_500 },
+        ],
+    );
+    assert!(timing_issues_with_min_gap(&clean, 1_000).is_empty());
+    assert_eq!(
+        timing_issues_with_min_gap(&clean, 1_001),
+        vec![TimingIssue::ShortGap { end: 5_000, b: 6_000 }],
+    );
+}
+
+#[test]
+fn test_export_edl_markers() {
+
+    let sets = vec![
+        caption(0, CompositionState::EpochStart, 0),
+        display_set(1_799 * 3_003, CompositionState::Normal),
+        caption(1_800 * 3_003, CompositionState::EpochStart, 1),
+        display_set(17_982 * 3_003, CompositionState::Normal),
+    ];
+    let mut output = vec![];
+
+    export_edl_markers(&sets, FrameRate::Fps29_97, &mut output).unwrap();
+
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "001  00:00:00;00  00:00:59;29\n002  00:01:00;02  00:10:00;00\n",
+    );
+}
+
+#[test]
+fn test_forced_epochs() {
+
+    let forced = |pts, state| {
+        let mut ds = caption(pts, state, 0);
+        ds.composition.objects.values_mut().for_each(|co| co.forced = true);
+        ds
+    };
+    let sets = vec![
+        caption(1_000, CompositionState::EpochStart, 0),
+        display_set(2_000, CompositionState::Normal),
+        caption(3_000, CompositionState::EpochStart, 0),
+        forced(4_000, CompositionState::Normal),
+        display_set(5_000, CompositionState::Normal),
+        forced(6_000, CompositionState::EpochStart),
+        caption(7_000, CompositionState::EpochStart, 0),
+    ];
+
+    assert_eq!(forced_epochs(&sets), vec![2, 5]);
+    assert!(forced_epochs(&[]).is_empty());
+}
+
+#[test]
+fn test_epoch_index_at() {
+
+    let sets = vec![
+        display_set(500, CompositionState::Normal),
+        display_set(1_000, CompositionState::EpochStart),
+        display_set(2_000, CompositionState::Normal),
+        display_set(3_000, CompositionState::Normal),
+        display_set(4_000, CompositionState::EpochStart),
+        display_set(5_000, CompositionState::AcquisitionPoint),
+        display_set(6_000, CompositionState::Normal),
+    ];
+
+    assert_eq!(epoch_index_at(&sets, 0), None);
+    assert_eq!(epoch_index_at(&sets, 999), None);
+    assert_eq!(epoch_index_at(&sets, 1_000), Some(1));
+    assert_eq!(epoch_index_at(&sets, 3_500), Some(1));
+    assert_eq!(epoch_index_at(&sets, 4_000), Some(4));
+    assert_eq!(epoch_index_at(&sets, 5_500), Some(4));
+    assert_eq!(epoch_index_at(&sets, u32::MAX), Some(4));
+    assert_eq!(epoch_index_at(&[], 1_000), None);
+}
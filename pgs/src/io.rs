@@ -0,0 +1,129 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a
+ * copy of the MPL was not distributed with this file, You can obtain one at
+ * https://mozilla.org/MPL/2.0/.
+ *
+ * SPDX-License-Identifier: MPL-2.0
+ */
+
+//! Provides I/O adapters for reading PGS streams from untrusted sources.
+//!
+//! # Overview
+//!
+//! A service that accepts uploaded SUP files should not allow a maliciously large input to
+//! exhaust its memory while parsing. Wrapping the input in a [LimitedReader] caps the total
+//! number of bytes that will be read from it.
+//!
+//! # Archives
+//!
+//! Many standalone PGS streams may also be bundled into a single archive, which is useful for
+//! applications such as games or menus that have many short subtitle assets. Archives are
+//! read with [SupArchive] and written with [write_archive]. An archive consists of a header, a
+//! central directory, and then the data of each entry. All integers are big-endian.
+//!
+//! The header consists of the magic number `SUPA` followed by the number of entries as a 32-bit
+//! integer. The central directory then lists each entry as:
+//!
+//! 1. The length of the entry's name in bytes, as a 16-bit integer.
+//! 2. The entry's name, encoded as UTF-8.
+//! 3. The offset of the entry's data from the start of the archive, as a 64-bit integer.
+//! 4. The length of the entry's data in bytes, as a 64-bit integer.
+//!
+//! The data of each entry is a complete PGS stream, exactly as it would appear within a
+//! standalone SUP file.
+
+#[cfg(test)]
+mod tests;
+#[cfg(test)]
+mod archivetests;
+
+mod archive;
+
+pub use archive::*;
+
+use std::io::{Error as IoError, Read, Result as IoResult};
+use thiserror::Error as ThisError;
+
+/// The error carried within the [IoError] returned by [LimitedReader] once its limit has been
+/// exceeded. This can be retrieved from that error using
+/// [get_ref](std::io::Error::get_ref).
+#[derive(ThisError, Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[error("input exceeds the limit of {limit} bytes")]
+pub struct LimitExceeded {
+    /// The maximum number of bytes that may be read.
+    pub limit: u64,
+}
+
+/// Wraps a reader so that no more than a fixed number of bytes may be read from it.
+///
+/// Once the limit has been reached, further reads return an error of kind
+/// [Other](std::io::ErrorKind::Other) carrying [LimitExceeded], unless the underlying reader
+/// has also reached its end, in which case they return zero bytes as usual. An input that is
+/// exactly as long as the limit can therefore be read in its entirety. Because the error is not
+/// [UnexpectedEof](std::io::ErrorKind::UnexpectedEof), it is not mistaken for the end of the
+/// stream when reading display sets until that condition is encountered.
+pub struct LimitedReader<R: Read> {
+    inner: R,
+    limit: u64,
+    read: u64,
+}
+
+impl<R: Read> LimitedReader<R> {
+
+    /// Wraps the specified reader, allowing no more than `limit` bytes to be read from it.
+    pub fn new(inner: R, limit: u64) -> Self {
+        LimitedReader {
+            inner,
+            limit,
+            read: 0,
+        }
+    }
+
+    /// Returns the maximum number of bytes that may be read.
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Returns the number of bytes that have been read so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.read
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Unwraps this reader, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+
+        if buf.is_empty() {
+            return Ok(0)
+        }
+
+        let remaining = self.limit - self.read;
+
+        if remaining == 0 {
+            return match self.inner.read(&mut [0x00_u8; 1])? {
+                0 => Ok(0),
+                _ => Err(IoError::other(LimitExceeded { limit: self.limit })),
+            }
+        }
+
+        let length = buf.len().min(remaining.min(usize::MAX as u64) as usize);
+        let count = self.inner.read(&mut buf[..length])?;
+
+        self.read += count as u64;
+
+        Ok(count)
+    }
+}
@@ -0,0 +1,225 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a
+ * copy of the MPL was not distributed with this file, You can obtain one at
+ * https://mozilla.org/MPL/2.0/.
+ *
+ * SPDX-License-Identifier: MPL-2.0
+ */
+
+use super::super::displayset::{
+    DisplaySet,
+    ReadDisplaySetExt,
+    ReadResult,
+    WriteDisplaySetExt,
+    WriteError as DisplaySetWriteError,
+};
+use std::io::{Cursor, Error as IoError, ErrorKind, Read, Seek, SeekFrom, Write};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use thiserror::Error as ThisError;
+
+const MAGIC_NUMBER: &[u8; 4] = b"SUPA";
+
+/// The error type for [SupArchive] and [write_archive].
+#[derive(ThisError, Debug)]
+pub enum ArchiveError {
+    /// The archive could not be read or written because of an underlying I/O error.
+    #[error("archive IO error")]
+    IoError {
+        /// The underlying I/O error.
+        #[from]
+        source: IoError,
+    },
+    /// The input does not begin with the archive's magic number.
+    #[error("unrecognized archive magic number")]
+    UnrecognizedMagicNumber,
+    /// The name of an entry within the central directory is not valid UTF-8.
+    #[error("archive entry name is not valid UTF-8")]
+    InvalidEntryName,
+    /// The offset of an entry within the central directory lies beyond the range of the
+    /// input.
+    #[error("archive entry {name} has an invalid offset")]
+    InvalidEntryOffset {
+        /// The name of the entry.
+        name: String,
+    },
+    /// The name of an entry being written is longer than 65,535 bytes.
+    #[error("archive entry name is too long")]
+    EntryNameTooLong,
+    /// No entry within the archive has the requested name.
+    #[error("archive has no entry named {name}")]
+    UnknownEntry {
+        /// The requested name.
+        name: String,
+    },
+    /// The display sets of an entry could not be written.
+    #[error("display set write error")]
+    WriteError {
+        /// The underlying display set write error.
+        #[from]
+        source: DisplaySetWriteError,
+    },
+}
+
+/// Describes where an entry's data resides within an archive.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ArchiveEntry {
+    /// The name of the entry, which should be unique within the archive.
+    pub name: String,
+    /// The offset of the entry's data within the input. This is the offset recorded within the
+    /// central directory plus the position at which the archive begins.
+    pub offset: u64,
+    /// The length of the entry's data in bytes.
+    pub length: u64,
+}
+
+/// Reads entries from an archive.
+pub struct SupArchive<R: Read + Seek> {
+    input: R,
+    entries: Vec<ArchiveEntry>,
+}
+
+impl<R: Read + Seek> SupArchive<R> {
+
+    /// Opens an archive by reading its central directory. The input should be positioned at
+    /// the start of the archive, which is where entry offsets are relative to.
+    pub fn open(mut input: R) -> Result<Self, ArchiveError> {
+
+        let start = input.stream_position()?;
+        let mut magic_number = [0x00_u8; 4];
+
+        input.read_exact(&mut magic_number)?;
+
+        if &magic_number != MAGIC_NUMBER {
+            return Err(ArchiveError::UnrecognizedMagicNumber)
+        }
+
+        let count = input.read_u32::<BigEndian>()?;
+        let mut entries = Vec::<ArchiveEntry>::new();
+
+        for _ in 0..count {
+
+            let mut name = vec![0x00_u8; input.read_u16::<BigEndian>()? as usize];
+
+            input.read_exact(&mut name)?;
+
+            let name = String::from_utf8(name).map_err(|_| ArchiveError::InvalidEntryName)?;
+            let Some(offset) = start.checked_add(input.read_u64::<BigEndian>()?) else {
+                return Err(ArchiveError::InvalidEntryOffset { name })
+            };
+
+            entries.push(
+                ArchiveEntry {
+                    name,
+                    offset,
+                    length: input.read_u64::<BigEndian>()?,
+                }
+            );
+        }
+
+        Ok(SupArchive { input, entries })
+    }
+
+    /// Returns every entry listed within the central directory, in the order they are listed.
+    pub fn entries(&self) -> &[ArchiveEntry] {
+        &self.entries
+    }
+
+    /// Returns the name of every entry, in the order they are listed.
+    pub fn entry_names(&self) -> Vec<&str> {
+        self.entries.iter().map(|entry| entry.name.as_str()).collect()
+    }
+
+    /// Reads the entry having the specified name, returning an iterator over its display sets.
+    /// Should an entry contain a display set that cannot be read, the iterator returns the
+    /// error and then stops. The entry's data is read as it is found rather than being
+    /// allocated up front, so an entry whose recorded length runs past the end of the input
+    /// results in an [UnexpectedEof](std::io::ErrorKind::UnexpectedEof) error.
+    pub fn read_entry(&mut self, name: &str)
+        -> Result<impl Iterator<Item = ReadResult<DisplaySet>>, ArchiveError> {
+
+        let entry = self.entries.iter()
+            .find(|entry| entry.name == name)
+            .ok_or_else(|| ArchiveError::UnknownEntry { name: name.to_string() })?;
+        let mut data = vec![];
+
+        self.input.seek(SeekFrom::Start(entry.offset))?;
+
+        if (&mut self.input).take(entry.length).read_to_end(&mut data)? as u64 != entry.length {
+            return Err(IoError::from(ErrorKind::UnexpectedEof).into())
+        }
+
+        Ok(EntryDisplaySets { input: Cursor::new(data), failed: false })
+    }
+}
+
+/// Iterates over the display sets of an archive entry.
+struct EntryDisplaySets {
+    input: Cursor<Vec<u8>>,
+    failed: bool,
+}
+
+impl Iterator for EntryDisplaySets {
+
+    type Item = ReadResult<DisplaySet>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+
+        if self.failed || self.input.position() >= self.input.get_ref().len() as u64 {
+            return None
+        }
+
+        let result = self.input.read_display_set();
+
+        self.failed = result.is_err();
+
+        Some(result)
+    }
+}
+
+/// Writes an archive containing the specified entries, each consisting of a name and the
+/// display sets making up its stream.
+pub fn write_archive<W: Write>(
+    mut output: W,
+    entries: &[(&str, &[DisplaySet])],
+) -> Result<(), ArchiveError> {
+
+    let mut streams = Vec::<Vec<u8>>::new();
+
+    if entries.iter().any(|(name, _)| name.len() > u16::MAX as usize) {
+        return Err(ArchiveError::EntryNameTooLong)
+    }
+
+    for (_, display_sets) in entries {
+
+        let mut stream = vec![];
+
+        for ds in display_sets.iter() {
+            stream.write_display_set(ds.clone())?;
+        }
+
+        streams.push(stream);
+    }
+
+    let mut offset = 8 + entries.iter()
+        .map(|(name, _)| 18 + name.len() as u64)
+        .sum::<u64>();
+
+    output.write_all(MAGIC_NUMBER)?;
+    output.write_u32::<BigEndian>(entries.len() as u32)?;
+
+    for ((name, _), stream) in entries.iter().zip(&streams) {
+        output.write_u16::<BigEndian>(name.len() as u16)?;
+        output.write_all(name.as_bytes())?;
+        output.write_u64::<BigEndian>(offset)?;
+        output.write_u64::<BigEndian>(stream.len() as u64)?;
+        offset += stream.len() as u64;
+    }
+
+    for stream in streams {
+        output.write_all(&stream)?;
+    }
+
+    Ok(())
+}
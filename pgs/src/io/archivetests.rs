@@ -0,0 +1,96 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * To the extent possible under law, the person who associated CC0 with this file has waived all
+ * copyright and related or neighboring rights to this file.
+ *
+ * You should have received a copy of the CC0 legalcode along with this work. If not, see
+ * <http://creativecommons.org/publicdomain/zero/1.0/>.
+ *
+ * SPDX-License-Identifier: CC0-1.0
+ */
+
+use super::{
+    *,
+    super::displayset::{DisplaySet, ReadResult},
+};
+use std::io::{Cursor, ErrorKind, Seek, SeekFrom};
+
+fn display_set(pts: u32) -> DisplaySet {
+    DisplaySet {
+        pts,
+        width: 1920,
+        height: 1080,
+        frame_rate: 0x10,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_archive_cycle() {
+
+    let menu = vec![display_set(0), display_set(90_000)];
+    let intro = vec![display_set(45_000)];
+    let mut buffer = vec![0xAA_u8; 3];
+
+    write_archive(&mut buffer, &[("menu", &menu), ("intro", &intro), ("empty", &[])]).unwrap();
+
+    let mut cursor = Cursor::new(buffer);
+
+    cursor.seek(SeekFrom::Start(3)).unwrap();
+
+    let mut archive = SupArchive::open(cursor).unwrap();
+
+    assert_eq!(archive.entry_names(), vec!["menu", "intro", "empty"]);
+    assert_eq!(
+        archive.read_entry("intro").unwrap().collect::<ReadResult<Vec<_>>>().unwrap(),
+        intro,
+    );
+    assert_eq!(
+        archive.read_entry("menu").unwrap().collect::<ReadResult<Vec<_>>>().unwrap(),
+        menu,
+    );
+    assert_eq!(archive.read_entry("empty").unwrap().count(), 0);
+    assert!(matches!(
+        archive.read_entry("outro"),
+        Err(ArchiveError::UnknownEntry { name }) if name == "outro",
+    ));
+}
+
+#[test]
+fn test_archive_magic_number() {
+    assert!(matches!(
+        SupArchive::open(Cursor::new(b"PG\x00\x00\x00\x00\x00\x00".to_vec())),
+        Err(ArchiveError::UnrecognizedMagicNumber),
+    ));
+}
+
+#[test]
+fn test_archive_invalid_entries() {
+
+    let mut directory = b"SUPA\x00\x00\x00\x01\x00\x01a".to_vec();
+
+    directory.extend(u64::MAX.to_be_bytes());
+    directory.extend(0_u64.to_be_bytes());
+
+    let mut input = Cursor::new([vec![0x00_u8; 4], directory.clone()].concat());
+
+    input.set_position(4);
+
+    assert!(matches!(
+        SupArchive::open(input),
+        Err(ArchiveError::InvalidEntryOffset { name }) if name == "a",
+    ));
+
+    directory.truncate(11);
+    directory.extend(27_u64.to_be_bytes());
+    directory.extend(u64::MAX.to_be_bytes());
+    directory.extend([0x00_u8; 16]);
+
+    let mut archive = SupArchive::open(Cursor::new(directory)).unwrap();
+
+    assert!(matches!(
+        archive.read_entry("a"),
+        Err(ArchiveError::IoError { source }) if source.kind() == ErrorKind::UnexpectedEof,
+    ));
+}
@@ -0,0 +1,77 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * To the extent possible under law, the person who associated CC0 with this file has waived all
+ * copyright and related or neighboring rights to this file.
+ *
+ * You should have received a copy of the CC0 legalcode along with this work. If not, see
+ * <http://creativecommons.org/publicdomain/zero/1.0/>.
+ *
+ * SPDX-License-Identifier: CC0-1.0
+ */
+
+use super::{
+    *,
+    super::{
+        displayset::{DisplaySet, ReadDisplaySetExt, ReadError, WriteDisplaySetExt},
+        segment::ReadError as SegmentReadError,
+    },
+};
+use std::io::ErrorKind;
+
+fn stream() -> Vec<u8> {
+
+    let mut buffer = vec![];
+
+    for pts in [0, 90_000] {
+        buffer.write_display_set(
+            DisplaySet {
+                pts,
+                width: 1920,
+                height: 1080,
+                frame_rate: 0x10,
+                ..Default::default()
+            }
+        ).unwrap();
+    }
+
+    buffer
+}
+
+#[test]
+fn test_limited_reader_within_limit() {
+
+    let buffer = stream();
+    let mut input = LimitedReader::new(buffer.as_slice(), buffer.len() as u64);
+
+    assert_eq!(input.read_display_set().unwrap().pts, 0);
+    assert_eq!(input.read_display_set().unwrap().pts, 90_000);
+    assert!(matches!(
+        input.read_display_set(),
+        Err(ReadError::ReadError { source: SegmentReadError::IoError { source } })
+            if source.kind() == ErrorKind::UnexpectedEof,
+    ));
+    assert_eq!(input.bytes_read(), buffer.len() as u64);
+}
+
+#[test]
+fn test_limited_reader_past_limit() {
+
+    let buffer = stream();
+    let limit = buffer.len() as u64 - 1;
+    let mut input = LimitedReader::new(buffer.as_slice(), limit);
+
+    assert_eq!(input.read_display_set().unwrap().pts, 0);
+
+    match input.read_display_set() {
+        Err(ReadError::ReadError { source: SegmentReadError::IoError { source } }) => {
+            assert_eq!(source.kind(), ErrorKind::Other);
+            assert_eq!(
+                source.get_ref().and_then(|err| err.downcast_ref::<LimitExceeded>()),
+                Some(&LimitExceeded { limit }),
+            );
+        }
+        other => panic!("expected limit to be exceeded, got {:?}", other.map(|ds| ds.pts)),
+    }
+    assert_eq!(input.bytes_read(), limit);
+}
@@ -72,8 +72,25 @@
 //! is more suited towards writing tooling that modifies stream properties, like window
 //! positions and object colors.
 
+#[cfg(test)]
+mod tests;
+
+#[cfg(feature = "image")]
+pub mod cue;
 pub mod displayset;
+pub mod epoch;
+pub mod io;
+pub mod limits;
+pub mod pattern;
+pub mod render;
+pub mod rgb;
+pub mod rle;
 pub mod segment;
+pub mod stats;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+use segment::FrameRate;
 
 pub fn ts_to_timestamp(ts: u32) -> String {
 
@@ -87,3 +104,68 @@ pub fn ts_to_timestamp(ts: u32) -> String {
 
     format!("{:02}:{:02}:{:02}.{:03}", h, m, s, ms)
 }
+
+/// Converts a 90 kHz PTS value into an SMPTE timecode at the specified frame rate, rounding to
+/// the nearest frame. The 29.97 and 59.94 rates use drop-frame timecodes in the form
+/// `HH:MM:SS;FF`, which skip frame numbers at the start of each minute not divisible by ten so
+/// that the timecode keeps pace with the clock. All other rates use non-drop-frame timecodes
+/// in the form `HH:MM:SS:FF`, with 23.976 counted at a nominal 24 frames per second.
+pub fn ts_to_timecode(ts: u32, rate: FrameRate) -> String {
+
+    let (numerator, denominator) = rate.ratio();
+    let (numerator, denominator) = (numerator as u64, denominator as u64);
+    let mut frame = (ts as u64 * numerator + 45_000 * denominator) / (90_000 * denominator);
+    let nominal = numerator.div_ceil(denominator);
+    let drop = match rate {
+        FrameRate::Fps29_97 => 2,
+        FrameRate::Fps59_94 => 4,
+        _ => 0,
+    };
+
+    if drop > 0 {
+
+        let frames_per_minute = nominal * 60 - drop;
+        let frames_per_ten_minutes = frames_per_minute * 10 + drop;
+        let tens = frame / frames_per_ten_minutes;
+        let remainder = frame % frames_per_ten_minutes;
+
+        frame += drop * 9 * tens;
+
+        if remainder > drop {
+            frame += drop * ((remainder - drop) / frames_per_minute);
+        }
+    }
+
+    format!(
+        "{:02}:{:02}:{:02}{}{:02}",
+        frame / (nominal * 3_600),
+        frame / (nominal * 60) % 60,
+        frame / nominal % 60,
+        if drop > 0 { ';' } else { ':' },
+        frame % nominal,
+    )
+}
+
+/// Parses a timestamp in the form `HH:MM:SS.mmm` (as produced by [ts_to_timestamp]) into a
+/// 90 kHz PTS value. The milliseconds portion may be omitted. Returns `None` if the timestamp
+/// is malformed or cannot be represented.
+pub fn timestamp_to_ts(timestamp: &str) -> Option<u32> {
+
+    let (hms, ms) = match timestamp.split_once('.') {
+        Some((hms, ms)) if ms.len() == 3 => (hms, ms.parse::<u32>().ok()?),
+        Some(_) => return None,
+        None => (timestamp, 0),
+    };
+    let mut parts = hms.split(':');
+    let h = parts.next()?.parse::<u32>().ok()?;
+    let m = parts.next()?.parse::<u32>().ok()?;
+    let s = parts.next()?.parse::<u32>().ok()?;
+
+    if parts.next().is_some() || m >= 60 || s >= 60 {
+        return None
+    }
+
+    h.checked_mul(3_600_000)?
+        .checked_add(m * 60_000 + s * 1_000 + ms)?
+        .checked_mul(90)
+}
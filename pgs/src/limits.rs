@@ -0,0 +1,154 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a
+ * copy of the MPL was not distributed with this file, You can obtain one at
+ * https://mozilla.org/MPL/2.0/.
+ *
+ * SPDX-License-Identifier: MPL-2.0
+ */
+
+//! Defines the limits that Blu-ray players place on PGS streams.
+//!
+//! # Overview
+//!
+//! A stream that is perfectly valid at the bitstream level may still exceed what a hardware
+//! player is able to decode or compose in time. These limits are centralized here so that
+//! authoring and validation tools can check against them consistently.
+
+#[cfg(test)]
+mod tests;
+
+use super::displayset::DisplaySet;
+use thiserror::Error as ThisError;
+
+/// The maximum number of windows that may be defined at once.
+pub const MAX_WINDOWS: usize = 2;
+
+/// The maximum number of composition objects that may be shown at once.
+pub const MAX_COMPOSITION_OBJECTS: usize = 2;
+
+/// The maximum number of palettes that may be defined within an epoch.
+pub const MAX_PALETTES: usize = 8;
+
+/// The maximum number of objects that may be defined within an epoch.
+pub const MAX_OBJECTS: usize = 64;
+
+/// The minimum width and height of an object in pixels.
+pub const MIN_OBJECT_DIMENSION: u16 = 8;
+
+/// The maximum width and height of an object in pixels.
+pub const MAX_OBJECT_DIMENSION: u16 = 4096;
+
+/// The width of the graphics plane in pixels.
+pub const GRAPHICS_PLANE_WIDTH: u16 = 1920;
+
+/// The height of the graphics plane in pixels.
+pub const GRAPHICS_PLANE_HEIGHT: u16 = 1080;
+
+/// The size of the graphics plane in bytes, with each pixel occupying a single palette index.
+pub const GRAPHICS_PLANE_BYTES: usize =
+    GRAPHICS_PLANE_WIDTH as usize * GRAPHICS_PLANE_HEIGHT as usize;
+
+/// Returns the maximum dimensions of the graphics plane as `(width, height)`.
+pub fn graphics_plane_dimensions() -> (u16, u16) {
+    (GRAPHICS_PLANE_WIDTH, GRAPHICS_PLANE_HEIGHT)
+}
+
+/// Describes how a display set (DS) exceeds the limits of a Blu-ray player.
+#[derive(ThisError, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum LimitViolation {
+    /// The screen is larger than the graphics plane.
+    #[error("screen dimensions exceed graphics plane")]
+    ScreenTooLarge {
+        /// The width of the screen.
+        width: u16,
+        /// The height of the screen.
+        height: u16,
+    },
+    /// The DS defines more than [MAX_WINDOWS] windows.
+    #[error("too many windows")]
+    TooManyWindows {
+        /// The number of windows defined.
+        count: usize,
+    },
+    /// The DS composes more than [MAX_COMPOSITION_OBJECTS] objects.
+    #[error("too many composition objects")]
+    TooManyCompositionObjects {
+        /// The number of composition objects defined.
+        count: usize,
+    },
+    /// The DS defines more than [MAX_PALETTES] palettes.
+    #[error("too many palettes")]
+    TooManyPalettes {
+        /// The number of distinct palette IDs defined.
+        count: usize,
+    },
+    /// The DS defines more than [MAX_OBJECTS] objects.
+    #[error("too many objects")]
+    TooManyObjects {
+        /// The number of distinct object IDs defined.
+        count: usize,
+    },
+    /// An object is smaller than [MIN_OBJECT_DIMENSION] or larger than [MAX_OBJECT_DIMENSION]
+    /// in either direction.
+    #[error("object dimensions out of range")]
+    InvalidObjectDimensions {
+        /// The ID of the object.
+        object_id: u16,
+        /// The width of the object.
+        width: u16,
+        /// The height of the object.
+        height: u16,
+    },
+}
+
+/// Checks a display set (DS) against the limits of a Blu-ray player, returning the first
+/// violation encountered.
+pub fn within_limits(ds: &DisplaySet) -> Result<(), LimitViolation> {
+
+    if ds.width > GRAPHICS_PLANE_WIDTH || ds.height > GRAPHICS_PLANE_HEIGHT {
+        return Err(LimitViolation::ScreenTooLarge { width: ds.width, height: ds.height })
+    }
+
+    if ds.windows.len() > MAX_WINDOWS {
+        return Err(LimitViolation::TooManyWindows { count: ds.windows.len() })
+    }
+
+    if ds.composition.objects.len() > MAX_COMPOSITION_OBJECTS {
+        return Err(
+            LimitViolation::TooManyCompositionObjects { count: ds.composition.objects.len() }
+        )
+    }
+
+    let mut palette_ids = ds.palettes.keys().map(|vid| vid.id).collect::<Vec<u8>>();
+
+    palette_ids.dedup();
+
+    if palette_ids.len() > MAX_PALETTES {
+        return Err(LimitViolation::TooManyPalettes { count: palette_ids.len() })
+    }
+
+    let mut object_ids = ds.objects.keys().map(|vid| vid.id).collect::<Vec<u16>>();
+
+    object_ids.dedup();
+
+    if object_ids.len() > MAX_OBJECTS {
+        return Err(LimitViolation::TooManyObjects { count: object_ids.len() })
+    }
+
+    for (vid, object) in &ds.objects {
+        let range = MIN_OBJECT_DIMENSION..=MAX_OBJECT_DIMENSION;
+        if !range.contains(&object.width) || !range.contains(&object.height) {
+            return Err(
+                LimitViolation::InvalidObjectDimensions {
+                    object_id: vid.id,
+                    width: object.width,
+                    height: object.height,
+                }
+            )
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,102 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * To the extent possible under law, the person who associated CC0 with this file has waived all
+ * copyright and related or neighboring rights to this file.
+ *
+ * You should have received a copy of the CC0 legalcode along with this work. If not, see
+ * <http://creativecommons.org/publicdomain/zero/1.0/>.
+ *
+ * SPDX-License-Identifier: CC0-1.0
+ */
+
+use super::{
+    *,
+    super::displayset::{Cid, CompositionObject, Object, Vid, Window},
+};
+
+fn display_set() -> DisplaySet {
+
+    let mut display_set = DisplaySet {
+        width: 1920,
+        height: 1080,
+        ..Default::default()
+    };
+
+    display_set.windows.insert(0, Window::default());
+    display_set.objects.insert(
+        Vid {
+            id: 0,
+            version: 0,
+        },
+        Object {
+            width: 8,
+            height: 8,
+            lines: vec![vec![0; 8]; 8],
+        },
+    );
+    display_set.composition.objects.insert(
+        Cid {
+            object_id: 0,
+            window_id: 0,
+        },
+        CompositionObject::default(),
+    );
+
+    display_set
+}
+
+#[test]
+fn test_within_limits() {
+    assert_eq!(within_limits(&display_set()), Ok(()));
+    assert_eq!(graphics_plane_dimensions(), (1920, 1080));
+    assert_eq!(GRAPHICS_PLANE_BYTES, 2_073_600);
+}
+
+#[test]
+fn test_screen_too_large() {
+
+    let mut display_set = display_set();
+
+    display_set.width = 3840;
+    display_set.height = 2160;
+
+    assert_eq!(
+        within_limits(&display_set),
+        Err(LimitViolation::ScreenTooLarge { width: 3840, height: 2160 }),
+    );
+}
+
+#[test]
+fn test_too_many_windows() {
+
+    let mut display_set = display_set();
+
+    display_set.windows.insert(1, Window::default());
+    display_set.windows.insert(2, Window::default());
+
+    assert_eq!(within_limits(&display_set), Err(LimitViolation::TooManyWindows { count: 3 }));
+}
+
+#[test]
+fn test_invalid_object_dimensions() {
+
+    let mut display_set = display_set();
+
+    display_set.objects.insert(
+        Vid {
+            id: 1,
+            version: 0,
+        },
+        Object {
+            width: 4,
+            height: 8,
+            lines: vec![vec![0; 4]; 8],
+        },
+    );
+
+    assert_eq!(
+        within_limits(&display_set),
+        Err(LimitViolation::InvalidObjectDimensions { object_id: 1, width: 4, height: 8 }),
+    );
+}
@@ -0,0 +1,148 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a
+ * copy of the MPL was not distributed with this file, You can obtain one at
+ * https://mozilla.org/MPL/2.0/.
+ *
+ * SPDX-License-Identifier: MPL-2.0
+ */
+
+//! Generates test patterns for verifying how players render and position captions.
+
+#[cfg(test)]
+mod tests;
+
+use super::{
+    displayset::{
+        Cid,
+        Composition,
+        CompositionObject,
+        DisplaySet,
+        Object,
+        Palette,
+        PaletteEntry,
+        Vid,
+        Window,
+    },
+    rgb::{ycbcr_pixel, ColorMatrix, RgbPixel},
+    segment::CompositionState,
+};
+use std::collections::BTreeMap;
+
+/// The colors of the bars drawn by [TestPatternKind::ColorBars], from left to right.
+const BAR_COLORS: [(f64, f64, f64); 7] = [
+    (1.0, 1.0, 1.0),
+    (1.0, 1.0, 0.0),
+    (0.0, 1.0, 1.0),
+    (0.0, 1.0, 0.0),
+    (1.0, 0.0, 1.0),
+    (1.0, 0.0, 0.0),
+    (0.0, 0.0, 1.0),
+];
+
+/// The kinds of test patterns that can be generated.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TestPatternKind {
+    /// Seven vertical bars spanning the screen: white, yellow, cyan, green, magenta, red, and
+    /// blue.
+    ColorBars,
+    /// A white crosshair two pixels thick that spans the screen and intersects at its center.
+    Crosshair,
+    /// A white L-shaped marker in each corner of the screen, useful for detecting overscan
+    /// and misaligned scaling.
+    CornerMarkers,
+}
+
+/// Generates a self-contained [EpochStart](CompositionState::EpochStart) display set that
+/// shows a test pattern across the entire screen. A single window and a single object cover
+/// the screen, so each dimension should lie within the
+/// [object dimension limits](super::limits::MAX_OBJECT_DIMENSION) of a player.
+pub fn test_pattern(width: u16, height: u16, kind: TestPatternKind) -> DisplaySet {
+
+    let (w, h) = (width as u32, height as u32);
+    let marker_length = (w.min(h) / 10).max(1);
+    let lines = (0..h).map(|y|
+        (0..w).map(|x|
+            match kind {
+                TestPatternKind::ColorBars => {
+                    1 + (x * BAR_COLORS.len() as u32 / w) as u8
+                }
+                TestPatternKind::Crosshair => {
+                    (x + 1 == w / 2 || x == w / 2 || y + 1 == h / 2 || y == h / 2) as u8
+                }
+                TestPatternKind::CornerMarkers => {
+                    let near_x = x < marker_length || x >= w - marker_length;
+                    let near_y = y < marker_length || y >= h - marker_length;
+                    let on_edge = x < 4 || x >= w.saturating_sub(4)
+                        || y < 4 || y >= h.saturating_sub(4);
+                    (near_x && near_y && on_edge) as u8
+                }
+            }
+        ).collect::<Vec<u8>>()
+    ).collect::<Vec<Vec<u8>>>();
+    let mut entries = BTreeMap::<u8, PaletteEntry>::new();
+
+    entries.insert(
+        0,
+        PaletteEntry {
+            y: 16,
+            cr: 128,
+            cb: 128,
+            alpha: 0,
+        },
+    );
+
+    for (index, &(red, green, blue)) in BAR_COLORS.iter().enumerate() {
+
+        let ycbcr = ycbcr_pixel(RgbPixel { red, green, blue }, ColorMatrix::Bt709);
+
+        entries.insert(
+            1 + index as u8,
+            PaletteEntry {
+                y: ycbcr.y,
+                cr: ycbcr.cr,
+                cb: ycbcr.cb,
+                alpha: 255,
+            },
+        );
+    }
+
+    let cid = Cid {
+        object_id: 0,
+        window_id: 0,
+    };
+    let mut display_set = DisplaySet {
+        width,
+        height,
+        frame_rate: 0x10,
+        composition: Composition {
+            state: CompositionState::EpochStart,
+            order: vec![cid.clone()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    display_set.windows.insert(
+        0,
+        Window {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        },
+    );
+    display_set.palettes.insert(Vid::default(), Palette { entries });
+    display_set.objects.insert(
+        Vid::default(),
+        Object {
+            width,
+            height,
+            lines,
+        },
+    );
+    display_set.composition.objects.insert(cid, CompositionObject::default());
+
+    display_set
+}
@@ -0,0 +1,66 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * To the extent possible under law, the person who associated CC0 with this file has waived all
+ * copyright and related or neighboring rights to this file.
+ *
+ * You should have received a copy of the CC0 legalcode along with this work. If not, see
+ * <http://creativecommons.org/publicdomain/zero/1.0/>.
+ *
+ * SPDX-License-Identifier: CC0-1.0
+ */
+
+use super::{
+    *,
+    super::{
+        displayset::roundtrip,
+        limits::within_limits,
+    },
+};
+
+fn pixel(ds: &DisplaySet, x: usize, y: usize) -> u8 {
+    ds.objects[&Vid::default()].lines[y][x]
+}
+
+#[test]
+fn test_patterns_roundtrip() {
+    for kind in [
+        TestPatternKind::ColorBars,
+        TestPatternKind::Crosshair,
+        TestPatternKind::CornerMarkers,
+    ] {
+
+        let ds = test_pattern(1920, 1080, kind);
+
+        assert_eq!(ds.composition.state, CompositionState::EpochStart);
+        assert!(within_limits(&ds).is_ok());
+        assert!(ds.unresolved_windows().is_empty());
+        assert_eq!(roundtrip(&ds).unwrap(), ds);
+    }
+}
+
+#[test]
+fn test_pattern_pixels() {
+
+    let bars = test_pattern(1920, 1080, TestPatternKind::ColorBars);
+
+    assert_eq!(pixel(&bars, 0, 0), 1);
+    assert_eq!(pixel(&bars, 1919, 1079), 7);
+    assert_eq!(bars.palettes[&Vid::default()].entries[&1].y, 235);
+
+    let crosshair = test_pattern(1920, 1080, TestPatternKind::Crosshair);
+
+    assert_eq!(pixel(&crosshair, 959, 0), 1);
+    assert_eq!(pixel(&crosshair, 960, 0), 1);
+    assert_eq!(pixel(&crosshair, 0, 540), 1);
+    assert_eq!(pixel(&crosshair, 0, 0), 0);
+
+    let markers = test_pattern(1920, 1080, TestPatternKind::CornerMarkers);
+
+    assert_eq!(pixel(&markers, 0, 0), 1);
+    assert_eq!(pixel(&markers, 107, 0), 1);
+    assert_eq!(pixel(&markers, 108, 0), 0);
+    assert_eq!(pixel(&markers, 50, 50), 0);
+    assert_eq!(pixel(&markers, 1919, 1079), 1);
+    assert_eq!(pixel(&markers, 960, 0), 0);
+}
@@ -0,0 +1,526 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a
+ * copy of the MPL was not distributed with this file, You can obtain one at
+ * https://mozilla.org/MPL/2.0/.
+ *
+ * SPDX-License-Identifier: MPL-2.0
+ */
+
+//! Visualizes display sets.
+
+#[cfg(test)]
+mod tests;
+
+use super::{
+    displayset::{
+        adler32,
+        latest,
+        visible_pixels,
+        DisplaySet,
+        Object,
+        Palette,
+        PaletteEntry,
+        ReadDisplaySetExt,
+        ReadError,
+        Vid,
+    },
+    epoch::EpochContext,
+    limits::{GRAPHICS_PLANE_HEIGHT, GRAPHICS_PLANE_WIDTH},
+    rgb::{rgb_pixel, ycbcr_pixel, ColorMatrix, RgbPixel, YcbcrPixel},
+    segment::{CompositionState, ReadError as SegmentReadError},
+};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as FmtWrite,
+    io::{Error as IoError, ErrorKind, Read, Write},
+};
+use thiserror::Error as ThisError;
+
+/// The error type for [render_at] and [render_frame].
+#[derive(ThisError, Debug)]
+pub enum RenderError {
+    /// A display set could not be read from the input.
+    #[error("display set read error")]
+    ReadError {
+        /// The underlying display set read error.
+        #[from]
+        source: ReadError,
+    },
+    /// The screen is larger than the graphics plane, so it cannot be rendered.
+    #[error("screen dimensions of {width}x{height} exceed graphics plane")]
+    ScreenTooLarge {
+        /// The width of the screen.
+        width: u16,
+        /// The height of the screen.
+        height: u16,
+    },
+    /// Nothing is visible on the screen at the requested time.
+    #[error("nothing is on screen at the requested time")]
+    NothingOnScreen {
+        /// The requested time.
+        pts: u32,
+    },
+    /// The image could not be written because of an underlying I/O error.
+    #[error("image IO error")]
+    IoError {
+        /// The underlying I/O error.
+        #[from]
+        source: IoError,
+    },
+}
+
+/// Defines options that control how frames are rendered.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct RenderOptions {
+    /// If set, fills the screen with this opaque RGB color before compositing, which simulates
+    /// a video background. This makes it possible to preview white subtitles, which are
+    /// otherwise hard to see on a transparent canvas. A mid-gray works well for this.
+    /// Otherwise, pixels that no object covers remain fully transparent.
+    pub background: Option<(u8, u8, u8)>,
+}
+
+/// A rasterized screen.
+#[derive(Clone, Debug, Default, Hash, PartialEq)]
+pub struct Frame {
+    /// The width of the screen in pixels.
+    pub width: u16,
+    /// The height of the screen in pixels.
+    pub height: u16,
+    /// The palette entry of each pixel in row-major order. Pixels that no object covers are
+    /// fully transparent (that is, they have an alpha value of zero).
+    pub pixels: Vec<PaletteEntry>,
+}
+
+impl Frame {
+
+    /// Returns the palette entry of the pixel at the specified coordinates, or `None` if they
+    /// lie outside of the screen.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<&PaletteEntry> {
+        if x < self.width as u32 && y < self.height as u32 {
+            self.pixels.get(y as usize * self.width as usize + x as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Converts this frame to 8-bit RGBA samples in row-major order, which is the raw form
+    /// expected by most imaging libraries. Palette entries are converted to RGB using
+    /// [rgb_pixel].
+    pub fn to_rgba(&self) -> Vec<u8> {
+
+        let mut rgba = Vec::<u8>::with_capacity(self.pixels.len() * 4);
+
+        for entry in &self.pixels {
+            let rgb = rgb_pixel(
+                YcbcrPixel { y: entry.y, cb: entry.cb, cr: entry.cr },
+                ColorMatrix::Bt709,
+            );
+            for channel in [rgb.red, rgb.green, rgb.blue] {
+                rgba.push((channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+            }
+            rgba.push(entry.alpha);
+        }
+
+        rgba
+    }
+
+    /// Converts this frame to an [RgbaImage](image::RgbaImage), so that it can be resized,
+    /// saved in any format, or otherwise processed using the `image` crate. Any screen
+    /// positions lacking a pixel are transparent.
+    #[cfg(feature = "image")]
+    pub fn to_image(&self) -> image::RgbaImage {
+
+        let mut rgba = self.to_rgba();
+
+        rgba.resize(self.width as usize * self.height as usize * 4, 0);
+
+        image::RgbaImage::from_raw(self.width as u32, self.height as u32, rgba)
+            .unwrap_or_default()
+    }
+
+    /// Writes this frame as an RGBA PNG image. Palette entries are converted to RGB using
+    /// [rgb_pixel]. The image data is stored without compression, which keeps this free of
+    /// external dependencies at the cost of larger files.
+    pub fn write_png<W: Write>(&self, mut output: W) -> Result<(), IoError> {
+
+        let row_size = self.width as usize * 4 + 1;
+        let mut raw = Vec::<u8>::with_capacity(row_size * self.height as usize);
+        let mut header = Vec::<u8>::with_capacity(13);
+
+        for row in self.to_rgba().chunks(self.width.max(1) as usize * 4) {
+            raw.push(0x00);
+            raw.extend(row);
+        }
+
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        header.extend([8, 6, 0, 0, 0]);
+
+        output.write_all(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'])?;
+        write_png_chunk(&mut output, b"IHDR", &header)?;
+        write_png_chunk(&mut output, b"IDAT", &zlib_store(&raw))?;
+        write_png_chunk(&mut output, b"IEND", &[])?;
+
+        Ok(())
+    }
+}
+
+/// Renders the screen as it appears at the specified time and writes it to `output` as a PNG
+/// image. Timestamps can be converted using [timestamp_to_ts](super::timestamp_to_ts).
+///
+/// Display sets are read from the input until one is encountered that is presented after the
+/// requested time. The last display set presented at or before that time is then rendered
+/// along with the state it inherits from its epoch. If nothing is visible on the screen at
+/// that time, [RenderError::NothingOnScreen] is returned and nothing is written.
+pub fn render_at<R: Read, W: Write>(mut input: R, pts: u32, output: W) -> Result<(), RenderError> {
+
+    let mut context = EpochContext::default();
+    let mut active = None::<DisplaySet>;
+
+    loop {
+
+        let ds = match input.read_display_set() {
+            Ok(ds) => {
+                ds
+            }
+            Err(ReadError::ReadError { source: SegmentReadError::IoError { source } })
+                if source.kind() == ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(err) => {
+                return Err(err.into())
+            }
+        };
+
+        if ds.pts > pts {
+            break
+        }
+
+        if let Some(previous) = active.replace(ds) {
+            context.update(&previous);
+        }
+    }
+
+    let frame = match active {
+        Some(ds) => render_frame(&ds, &context)?,
+        None => Frame::default(),
+    };
+
+    if frame.pixels.iter().all(|entry| entry.alpha == 0) {
+        return Err(RenderError::NothingOnScreen { pts })
+    }
+
+    frame.write_png(output)?;
+
+    Ok(())
+}
+
+/// Renders a display set on its own as an [RgbaImage](image::RgbaImage). As no state is
+/// inherited from the rest of its epoch, this is best suited to display sets that define
+/// everything they compose. Otherwise, use [render_frame] and then [Frame::to_image].
+#[cfg(feature = "image")]
+pub fn to_image(ds: &DisplaySet) -> Result<image::RgbaImage, RenderError> {
+    Ok(render_frame(ds, &EpochContext::default())?.to_image())
+}
+
+/// Computes a perceptual hash of a display set as rendered on its own, for matching the same
+/// caption across streams that encode it differently. Captions that look alike produce hashes
+/// differing in few bits, which can be counted with [count_ones](u64::count_ones) on their
+/// exclusive or.
+///
+/// This is a difference hash. The rendered screen is composited over black, converted to
+/// grayscale, and shrunk to nine by eight pixels. Each bit then records whether a pixel is
+/// brighter than its neighbor to the right.
+#[cfg(feature = "image")]
+pub fn phash(ds: &DisplaySet) -> Result<u64, RenderError> {
+
+    let rgba = to_image(ds)?;
+    let gray = image::GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [red, green, blue, alpha] = rgba.get_pixel(x, y).0;
+        let luma = 0.2126 * red as f64 + 0.7152 * green as f64 + 0.0722 * blue as f64;
+        image::Luma([(luma * alpha as f64 / 255.0).round() as u8])
+    });
+    let thumbnail = image::imageops::resize(&gray, 9, 8, image::imageops::FilterType::Triangle);
+    let mut hash = 0_u64;
+
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if thumbnail.get_pixel(x, y).0[0] > thumbnail.get_pixel(x + 1, y).0[0] {
+                hash |= 1;
+            }
+        }
+    }
+
+    Ok(hash)
+}
+
+/// Renders two display sets on their own and highlights where they differ, as a visual aid
+/// when editing the appearance of a caption. Pixels that differ are red, while those that are
+/// the same are gray. Pixels that are fully transparent in both are considered the same,
+/// regardless of their color. Should the screen sizes differ, only the area common to both is
+/// compared, and that is the size of the returned image.
+#[cfg(feature = "image")]
+pub fn diff_image(a: &DisplaySet, b: &DisplaySet) -> Result<image::RgbaImage, RenderError> {
+
+    let a = to_image(a)?;
+    let b = to_image(b)?;
+
+    Ok(image::RgbaImage::from_fn(a.width().min(b.width()), a.height().min(b.height()), |x, y| {
+        let (a, b) = (a.get_pixel(x, y), b.get_pixel(x, y));
+        if a == b || (a.0[3] == 0 && b.0[3] == 0) {
+            image::Rgba([128, 128, 128, 255])
+        } else {
+            image::Rgba([255, 0, 0, 255])
+        }
+    }))
+}
+
+/// Rasterizes the screen as it appears once a display set has been presented.
+///
+/// Windows, objects, and palettes that the display set does not define itself are resolved
+/// from the state it inherits from its epoch, unless the display set begins a new epoch. A
+/// display set that only updates palettes and composes no objects of its own continues to
+/// show the inherited [composition](EpochContext::composition) using its updated palette. This
+/// is how fade animations are typically authored.
+///
+/// As the frame is allocated up front, a display set whose screen is larger than the graphics
+/// plane is rejected with [RenderError::ScreenTooLarge].
+pub fn render_frame(ds: &DisplaySet, context: &EpochContext) -> Result<Frame, RenderError> {
+    render_frame_with_options(ds, context, &RenderOptions::default())
+}
+
+/// Rasterizes the screen as it appears once a display set has been presented, using the
+/// specified options. See [render_frame] for how inherited state is resolved.
+pub fn render_frame_with_options(
+    ds: &DisplaySet,
+    context: &EpochContext,
+    options: &RenderOptions,
+) -> Result<Frame, RenderError> {
+
+    if ds.width > GRAPHICS_PLANE_WIDTH || ds.height > GRAPHICS_PLANE_HEIGHT {
+        return Err(RenderError::ScreenTooLarge { width: ds.width, height: ds.height })
+    }
+
+    let empty = EpochContext::default();
+    let context = if ds.composition.state == CompositionState::EpochStart {
+        &empty
+    } else {
+        context
+    };
+    let composition = if ds.palete_update_only && ds.composition.objects.is_empty() {
+        &context.composition
+    } else {
+        &ds.composition
+    };
+    let windows = if ds.windows.is_empty() { &context.windows } else { &ds.windows };
+    let objects = merge::<Vid<u16>, Object>(&context.objects, &ds.objects);
+    let palettes = merge::<Vid<u8>, Palette>(&context.palettes, &ds.palettes);
+    let palette = latest(&palettes, ds.palette_id);
+    let mut frame = Frame {
+        width: ds.width,
+        height: ds.height,
+        pixels: vec![PaletteEntry::default(); ds.width as usize * ds.height as usize],
+    };
+
+    for (cid, co) in composition.ordered_objects() {
+
+        let (Some(object), Some(window)) = (
+            latest(&objects, cid.object_id),
+            windows.get(&cid.window_id),
+        ) else {
+            continue
+        };
+
+        for (x, y, index) in visible_pixels(co, object) {
+            if x < frame.width as u32 && y < frame.height as u32 && window.contains(x, y) {
+                if let Some(entry) = palette.and_then(|palette| palette.entries.get(&index)) {
+                    frame.pixels[y as usize * frame.width as usize + x as usize] = entry.clone();
+                }
+            }
+        }
+    }
+
+    if let Some((red, green, blue)) = options.background {
+
+        let background = ycbcr_pixel(
+            RgbPixel {
+                red: red as f64 / 255.0,
+                green: green as f64 / 255.0,
+                blue: blue as f64 / 255.0,
+            },
+            ColorMatrix::Bt709,
+        );
+
+        for entry in frame.pixels.iter_mut() {
+            let alpha = entry.alpha as f64 / 255.0;
+            let blend = |foreground: u8, background: u8| {
+                (foreground as f64 * alpha + background as f64 * (1.0 - alpha)).round() as u8
+            };
+            *entry = PaletteEntry {
+                y: blend(entry.y, background.y),
+                cr: blend(entry.cr, background.cr),
+                cb: blend(entry.cb, background.cb),
+                alpha: 255,
+            };
+        }
+    }
+
+    Ok(frame)
+}
+
+/// Draws the spatial layout of a display set as an SVG document without rasterizing any
+/// pixels. The screen, each window, and the bounding box of each composition object are drawn
+/// as labeled rectangles. When a composition object is cropped, its crop region is drawn as well
+/// and the object's bounding box is positioned such that the crop region lands where it is
+/// placed on the screen. Objects that are not defined within the display set itself only have
+/// their crop regions (if any) drawn, as their dimensions are unknown.
+pub fn layout_svg(ds: &DisplaySet) -> String {
+
+    let mut svg = String::new();
+
+    writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+            viewBox=\"0 0 {0} {1}\">",
+        ds.width,
+        ds.height,
+    ).unwrap();
+    writeln!(
+        svg,
+        "  <rect class=\"screen\" x=\"0\" y=\"0\" width=\"{}\" height=\"{}\" fill=\"black\"/>",
+        ds.width,
+        ds.height,
+    ).unwrap();
+
+    for (window_id, window) in ds.windows.iter() {
+        write_rect(
+            &mut svg,
+            "window",
+            "blue",
+            (window.x as i32, window.y as i32, window.width, window.height),
+            &format!("window {}", window_id),
+        );
+    }
+
+    for (cid, co) in ds.composition.ordered_objects() {
+
+        let label = format!("object {} / window {}", cid.object_id, cid.window_id);
+        if let Some(object) = latest(&ds.objects, cid.object_id) {
+
+            let (crop_x, crop_y) = co.crop.as_ref().map_or((0, 0), |crop| (crop.x, crop.y));
+
+            write_rect(
+                &mut svg,
+                "object",
+                "lime",
+                (
+                    co.x as i32 - crop_x as i32,
+                    co.y as i32 - crop_y as i32,
+                    object.width,
+                    object.height,
+                ),
+                &label,
+            );
+        }
+
+        if let Some(crop) = &co.crop {
+            write_rect(
+                &mut svg,
+                "crop",
+                "red",
+                (co.x as i32, co.y as i32, crop.width, crop.height),
+                &format!("crop of {}", label),
+            );
+        }
+    }
+
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+fn merge<'a, K: Clone + Ord, V>(
+    inherited: &'a BTreeMap<K, V>,
+    defined: &'a BTreeMap<K, V>,
+) -> BTreeMap<K, &'a V> {
+    inherited.iter().chain(defined.iter()).map(|(key, value)| (key.clone(), value)).collect()
+}
+
+fn write_rect(
+    svg: &mut String,
+    class: &str,
+    color: &str,
+    rect: (i32, i32, u16, u16),
+    label: &str,
+) {
+
+    let (x, y, width, height) = rect;
+
+    writeln!(
+        svg,
+        "  <rect class=\"{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" \
+            stroke=\"{}\"/>",
+        class,
+        x,
+        y,
+        width,
+        height,
+        color,
+    ).unwrap();
+    writeln!(
+        svg,
+        "  <text class=\"{}\" x=\"{}\" y=\"{}\" fill=\"{}\" font-size=\"16\" \
+            dominant-baseline=\"hanging\">{}</text>",
+        class,
+        x + 2,
+        y + 2,
+        color,
+        label,
+    ).unwrap();
+}
+
+fn write_png_chunk<W: Write>(output: &mut W, kind: &[u8; 4], data: &[u8]) -> Result<(), IoError> {
+
+    let mut crc = 0xFFFF_FFFF_u32;
+
+    for &byte in kind.iter().chain(data) {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+
+    output.write_all(&(data.len() as u32).to_be_bytes())?;
+    output.write_all(kind)?;
+    output.write_all(data)?;
+    output.write_all(&(!crc).to_be_bytes())?;
+
+    Ok(())
+}
+
+/// Wraps data within a zlib stream made of uncompressed (stored) deflate blocks.
+fn zlib_store(data: &[u8]) -> Vec<u8> {
+
+    let mut output = Vec::<u8>::with_capacity(data.len() + data.len() / 65_535 * 5 + 11);
+    let mut blocks = data.chunks(65_535).peekable();
+
+    output.extend([0x78, 0x01]);
+
+    if data.is_empty() {
+        output.extend([0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+
+    while let Some(block) = blocks.next() {
+        output.push(blocks.peek().is_none() as u8);
+        output.extend((block.len() as u16).to_le_bytes());
+        output.extend((!(block.len() as u16)).to_le_bytes());
+        output.extend(block);
+    }
+
+    output.extend(adler32(data.iter().copied()).to_be_bytes());
+
+    output
+}
@@ -0,0 +1,368 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * To the extent possible under law, the person who associated CC0 with this file has waived all
+ * copyright and related or neighboring rights to this file.
+ *
+ * You should have received a copy of the CC0 legalcode along with this work. If not, see
+ * <http://creativecommons.org/publicdomain/zero/1.0/>.
+ *
+ * SPDX-License-Identifier: CC0-1.0
+ */
+
+use super::{
+    *,
+    super::{
+        displayset::{Cid, Composition, CompositionObject, Window, WriteDisplaySetExt},
+        pattern::{test_pattern, TestPatternKind},
+        segment::Crop,
+    },
+};
+
+#[test]
+fn test_layout_svg() {
+
+    let mut ds = DisplaySet {
+        width: 1920,
+        height: 1080,
+        ..Default::default()
+    };
+
+    ds.windows.insert(
+        1,
+        Window {
+            x: 100,
+            y: 900,
+            width: 800,
+            height: 100,
+        },
+    );
+    ds.objects.insert(
+        Vid {
+            id: 5,
+            version: 0,
+        },
+        Object {
+            width: 400,
+            height: 80,
+            lines: vec![vec![0; 400]; 80],
+        },
+    );
+    ds.composition.objects.insert(
+        Cid {
+            object_id: 5,
+            window_id: 1,
+        },
+        CompositionObject {
+            x: 150,
+            y: 910,
+            forced: false,
+            crop: Some(
+                Crop {
+                    x: 10,
+                    y: 20,
+                    width: 300,
+                    height: 50,
+                }
+            ),
+        },
+    );
+    ds.composition.objects.insert(
+        Cid {
+            object_id: 6,
+            window_id: 1,
+        },
+        CompositionObject {
+            x: 500,
+            y: 910,
+            forced: false,
+            crop: None,
+        },
+    );
+
+    let svg = layout_svg(&ds);
+
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"1920\""));
+    assert!(svg.ends_with("</svg>\n"));
+    assert!(svg.contains(
+        "<rect class=\"screen\" x=\"0\" y=\"0\" width=\"1920\" height=\"1080\""
+    ));
+    assert!(svg.contains(
+        "<rect class=\"window\" x=\"100\" y=\"900\" width=\"800\" height=\"100\""
+    ));
+    assert!(svg.contains(">window 1</text>"));
+    assert!(svg.contains(
+        "<rect class=\"object\" x=\"140\" y=\"890\" width=\"400\" height=\"80\""
+    ));
+    assert!(svg.contains(">object 5 / window 1</text>"));
+    assert!(svg.contains(
+        "<rect class=\"crop\" x=\"150\" y=\"910\" width=\"300\" height=\"50\""
+    ));
+    assert!(svg.contains(">crop of object 5 / window 1</text>"));
+    assert!(!svg.contains("object 6"));
+}
+
+#[test]
+fn test_render_frame_palette_update_only() {
+
+    let cid = Cid {
+        object_id: 0,
+        window_id: 0,
+    };
+    let mut epoch_start = DisplaySet {
+        width: 4,
+        height: 2,
+        composition: Composition {
+            state: CompositionState::EpochStart,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut fade = DisplaySet {
+        width: 4,
+        height: 2,
+        palete_update_only: true,
+        composition: Composition {
+            state: CompositionState::Normal,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut context = EpochContext::default();
+
+    epoch_start.windows.insert(
+        0,
+        Window {
+            x: 1,
+            y: 0,
+            width: 2,
+            height: 2,
+        },
+    );
+    epoch_start.objects.insert(
+        Vid {
+            id: 0,
+            version: 0,
+        },
+        Object {
+            width: 2,
+            height: 1,
+            lines: vec![vec![1, 2]],
+        },
+    );
+    epoch_start.palettes.insert(Vid::default(), palette(255));
+    epoch_start.composition.objects.insert(
+        cid.clone(),
+        CompositionObject {
+            x: 1,
+            ..Default::default()
+        },
+    );
+    fade.palettes.insert(Vid { id: 0, version: 1 }, palette(64));
+
+    let frame = render_frame(&epoch_start, &context).unwrap();
+
+    assert_eq!(frame.pixel(0, 0).unwrap().alpha, 0);
+    assert_eq!(frame.pixel(1, 0).unwrap().alpha, 255);
+    assert_eq!(frame.pixel(2, 0).unwrap().alpha, 255);
+    assert_eq!(frame.pixel(1, 1).unwrap().alpha, 0);
+    assert!(frame.pixel(4, 0).is_none());
+
+    context.update(&epoch_start);
+
+    let frame = render_frame(&fade, &context).unwrap();
+
+    assert_eq!(frame.pixel(0, 0).unwrap().alpha, 0);
+    assert_eq!(frame.pixel(1, 0).unwrap().alpha, 64);
+    assert_eq!(frame.pixel(2, 0).unwrap().alpha, 64);
+    assert_eq!(frame.pixel(2, 0).unwrap().y, 2);
+
+    context.update(&fade);
+
+    assert_eq!(context.composition.objects.len(), 1);
+    assert_eq!(render_frame(&fade, &EpochContext::default()).unwrap(), Frame {
+        width: 4,
+        height: 2,
+        pixels: vec![PaletteEntry::default(); 8],
+    });
+}
+
+#[test]
+fn test_render_frame_background() {
+
+    let mut ds = DisplaySet {
+        width: 3,
+        height: 1,
+        ..Default::default()
+    };
+    let mut entries = BTreeMap::<u8, PaletteEntry>::new();
+
+    entries.insert(1, PaletteEntry { y: 235, cr: 128, cb: 128, alpha: 255 });
+    entries.insert(2, PaletteEntry { y: 235, cr: 128, cb: 128, alpha: 0 });
+    ds.palettes.insert(Vid::default(), Palette { entries });
+    ds.windows.insert(0, Window { x: 0, y: 0, width: 3, height: 1 });
+    ds.objects.insert(
+        Vid::default(),
+        Object {
+            width: 2,
+            height: 1,
+            lines: vec![vec![1, 2]],
+        },
+    );
+    ds.composition.objects.insert(Cid::default(), CompositionObject::default());
+
+    let gray = PaletteEntry { y: 126, cr: 128, cb: 128, alpha: 255 };
+    let options = RenderOptions {
+        background: Some((128, 128, 128)),
+    };
+    let frame = render_frame_with_options(&ds, &EpochContext::default(), &options).unwrap();
+
+    let white = ds.palettes[&Vid::default()].entries[&1].clone();
+
+    assert_eq!(frame.pixels, vec![white, gray.clone(), gray]);
+    let context = EpochContext::default();
+
+    assert_eq!(
+        render_frame_with_options(&ds, &context, &RenderOptions::default()).unwrap(),
+        render_frame(&ds, &context).unwrap(),
+    );
+    assert_eq!(render_frame(&ds, &context).unwrap().pixel(2, 0).unwrap().alpha, 0);
+
+    ds.width = 65_535;
+    ds.height = 65_535;
+
+    assert!(matches!(
+        render_frame(&ds, &context),
+        Err(RenderError::ScreenTooLarge { width: 65_535, height: 65_535 }),
+    ));
+}
+
+#[test]
+fn test_frame_to_rgba() {
+
+    let frame = Frame {
+        width: 2,
+        height: 1,
+        pixels: vec![
+            PaletteEntry { y: 235, cr: 128, cb: 128, alpha: 255 },
+            PaletteEntry { y: 16, cr: 128, cb: 128, alpha: 64 },
+        ],
+    };
+
+    assert_eq!(frame.to_rgba(), vec![255, 255, 255, 255, 0, 0, 0, 64]);
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn test_to_image() {
+
+    let ds = test_pattern(64, 32, TestPatternKind::ColorBars);
+    let frame = render_frame(&ds, &EpochContext::default()).unwrap();
+    let image = to_image(&ds).unwrap();
+
+    assert_eq!(image.dimensions(), (64, 32));
+    assert_eq!(image.into_raw(), frame.to_rgba());
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn test_phash() {
+
+    let ds = test_pattern(64, 32, TestPatternKind::ColorBars);
+    let mut buffer = vec![];
+
+    buffer.write_display_set(ds.clone()).unwrap();
+
+    let reencoded = buffer.as_slice().read_display_set().unwrap();
+    let crosshair = test_pattern(64, 32, TestPatternKind::Crosshair);
+
+    assert_ne!(phash(&ds).unwrap(), 0);
+    assert_eq!(phash(&ds).unwrap(), phash(&ds.clone()).unwrap());
+    assert_eq!(phash(&ds).unwrap(), phash(&reencoded).unwrap());
+    assert!((phash(&ds).unwrap() ^ phash(&crosshair).unwrap()).count_ones() > 8);
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn test_diff_image() {
+
+    let bars = test_pattern(64, 32, TestPatternKind::ColorBars);
+    let gray = image::Rgba([128, 128, 128, 255]);
+    let red = image::Rgba([255, 0, 0, 255]);
+    let identical = diff_image(&bars, &bars.clone()).unwrap();
+
+    assert_eq!(identical.dimensions(), (64, 32));
+    assert!(identical.pixels().all(|pixel| *pixel == gray));
+
+    let crosshair = test_pattern(48, 40, TestPatternKind::Crosshair);
+    let different = diff_image(&bars, &crosshair).unwrap();
+
+    assert_eq!(different.dimensions(), (48, 32));
+    assert!(different.pixels().any(|pixel| *pixel == red));
+}
+
+fn palette(alpha: u8) -> Palette {
+
+    let mut entries = BTreeMap::<u8, PaletteEntry>::new();
+
+    for index in 1..=2 {
+        entries.insert(
+            index,
+            PaletteEntry {
+                y: index,
+                cr: 128,
+                cb: 128,
+                alpha,
+            },
+        );
+    }
+
+    Palette { entries }
+}
+
+#[test]
+fn test_render_at() {
+
+    let caption = test_pattern(32, 16, TestPatternKind::ColorBars).with_composition_number(0);
+    let mut clear = DisplaySet {
+        pts: 180_000,
+        width: 32,
+        height: 16,
+        composition: Composition {
+            number: 1,
+            state: CompositionState::Normal,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut input = vec![];
+    let mut png = vec![];
+
+    clear.windows = caption.windows.clone();
+    input.write_display_set(DisplaySet { pts: 90_000, ..caption }).unwrap();
+    input.write_display_set(clear).unwrap();
+
+    assert!(matches!(
+        render_at(input.as_slice(), 45_000, &mut png),
+        Err(RenderError::NothingOnScreen { pts: 45_000 }),
+    ));
+    assert!(matches!(
+        render_at(input.as_slice(), 180_000, &mut png),
+        Err(RenderError::NothingOnScreen { pts: 180_000 }),
+    ));
+    assert!(png.is_empty());
+
+    render_at(input.as_slice(), 135_000, &mut png).unwrap();
+
+    assert_eq!(png[..8], [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n']);
+    assert_eq!(&png[12..16], b"IHDR");
+    assert_eq!(png[16..24], [0, 0, 0, 32, 0, 0, 0, 16]);
+    assert_eq!(&png[37..41], b"IDAT");
+
+    // The first stored block begins after the zlib header and its own five-byte header. Each
+    // row begins with a filter type byte.
+    let first_pixel = &png[41 + 2 + 5..][..5];
+
+    assert_eq!(first_pixel, [0x00, 255, 255, 255, 255]);
+    assert!(png.ends_with(&[0xAE, 0x42, 0x60, 0x82]));
+}
@@ -0,0 +1,170 @@
+/*
+ * Copyright 2021 William Swartzendruber
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a
+ * copy of the MPL was not distributed with this file, You can obtain one at
+ * https://mozilla.org/MPL/2.0/.
+ *
+ * SPDX-License-Identifier: MPL-2.0
+ */
+
+//! Converts between the range-limited YC<sub>b</sub>C<sub>r</sub> values used by palettes and
+//! normalized RGB values using the BT.601, BT.709, or BT.2020 matrix.
+
+#[cfg(test)]
+mod tests;
+
+/// A range-limited YC<sub>b</sub>C<sub>r</sub> pixel, as stored within a palette entry.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct YcbcrPixel {
+    /// The luminosity value.
+    pub y: u8,
+    /// The blue-difference chroma value.
+    pub cb: u8,
+    /// The red-difference chroma value.
+    pub cr: u8,
+}
+
+/// An RGB pixel whose channels are normalized to the range of `0.0` to `1.0`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RgbPixel {
+    /// The red channel.
+    pub red: f64,
+    /// The green channel.
+    pub green: f64,
+    /// The blue channel.
+    pub blue: f64,
+}
+
+/// Selects the coefficients used to convert between YC<sub>b</sub>C<sub>r</sub> and RGB.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum ColorMatrix {
+    /// The ITU-R BT.601 matrix, as used by standard-definition content.
+    Bt601,
+    /// The ITU-R BT.709 matrix, as used by high-definition content. This is the default.
+    #[default]
+    Bt709,
+    /// The ITU-R BT.2020 matrix, as used by ultra-high-definition content.
+    Bt2020,
+}
+
+struct Coefficients {
+    luma_red: f64,
+    luma_green: f64,
+    luma_blue: f64,
+    red_cr: f64,
+    green_cb: f64,
+    green_cr: f64,
+    blue_cb: f64,
+    cb_red: f64,
+    cb_green: f64,
+    cr_green: f64,
+    cr_blue: f64,
+}
+
+impl ColorMatrix {
+
+    fn coefficients(self) -> &'static Coefficients {
+        match self {
+            ColorMatrix::Bt601 => &Coefficients {
+                luma_red: 0.299,
+                luma_green: 0.587,
+                luma_blue: 0.114,
+                red_cr: 1.13984,
+                green_cb: 0.39465,
+                green_cr: 0.58060,
+                blue_cb: 2.03211,
+                cb_red: -0.14714,
+                cb_green: -0.28886,
+                cr_green: -0.51499,
+                cr_blue: -0.10001,
+            },
+            ColorMatrix::Bt709 => &Coefficients {
+                luma_red: 0.2126,
+                luma_green: 0.7152,
+                luma_blue: 0.0722,
+                red_cr: 1.28033,
+                green_cb: 0.21482,
+                green_cr: 0.38059,
+                blue_cb: 2.12798,
+                cb_red: -0.09991,
+                cb_green: -0.33609,
+                cr_green: -0.55861,
+                cr_blue: -0.05639,
+            },
+            ColorMatrix::Bt2020 => &Coefficients {
+                luma_red: 0.2627,
+                luma_green: 0.6780,
+                luma_blue: 0.0593,
+                red_cr: 1.19886,
+                green_cb: 0.18871,
+                green_cr: 0.46451,
+                blue_cb: 2.15757,
+                cb_red: -0.12176,
+                cb_green: -0.31424,
+                cr_green: -0.56554,
+                cr_blue: -0.04946,
+            },
+        }
+    }
+}
+
+/// Converts a YC<sub>b</sub>C<sub>r</sub> pixel to RGB using the specified matrix.
+pub fn rgb_pixel(input: YcbcrPixel, matrix: ColorMatrix) -> RgbPixel {
+
+    let c = matrix.coefficients();
+    let y = expand(input.y as f64 / 255.0);
+    let cb = (input.cb as f64 - 128.0) / 128.0;
+    let cr = (input.cr as f64 - 128.0) / 128.0;
+
+    RgbPixel {
+        red:   y + c.red_cr * cr,
+        green: y - c.green_cb * cb - c.green_cr * cr,
+        blue:  y + c.blue_cb * cb,
+    }
+}
+
+/// Converts an RGB pixel to YC<sub>b</sub>C<sub>r</sub> using the specified matrix. Every pixel
+/// produced by [rgb_pixel] whose luminosity value lies between `16` and `234` converts back to
+/// its original value when the same matrix is used for both conversions.
+pub fn ycbcr_pixel(rgb: RgbPixel, matrix: ColorMatrix) -> YcbcrPixel {
+
+    let c = matrix.coefficients();
+
+    YcbcrPixel {
+        y:
+           ((compress(
+                c.luma_red * rgb.red
+                + c.luma_green * rgb.green
+                + c.luma_blue * rgb.blue
+            ) * 255.0) - 0.25).clamp(0.0, 255.0).round() as u8,
+            // The '- 0.25' is an absolutely ridiculous hack to ensure that all possible YCbCr
+            // combinations map to RGB and back to their original values.
+        cb:
+            ((
+                c.cb_red * rgb.red
+                + c.cb_green * rgb.green
+                + 0.436 * rgb.blue
+                + 1.0
+            ) * 128.0).clamp(0.0, 255.0).round() as u8,
+        cr:
+            ((
+                0.615 * rgb.red
+                + c.cr_green * rgb.green
+                + c.cr_blue * rgb.blue
+                + 1.0
+            ) * 128.0).clamp(0.0, 255.0).round() as u8,
+    }
+}
+
+fn compress(value: f64) -> f64 {
+    (value * 0.859375) + 0.06274509803
+}
+
+fn expand(value: f64) -> f64 {
+    match value {
+        v if v < 0.06274509803 => 0.0,
+        v if v > 0.92156862745 => 1.0,
+        _ => (value - 0.06274509803) / 0.859375,
+    }
+}
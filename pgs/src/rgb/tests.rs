@@ -15,13 +15,15 @@ use super::*;
 #[test]
 fn test_every_possible_yuv_combination() {
 
-    for y in 16..235 {
-        for cb in 0..=255 {
-            for cr in 0..=255 {
+    for matrix in [ColorMatrix::Bt601, ColorMatrix::Bt709, ColorMatrix::Bt2020] {
+        for y in 16..235 {
+            for cb in 0..=255 {
+                for cr in 0..=255 {
 
-                let yuv = YcbcrPixel { y, cb, cr };
+                    let yuv = YcbcrPixel { y, cb, cr };
 
-                assert_eq!(yuv, ycbcr_pixel(rgb_pixel(yuv)));
+                    assert_eq!(yuv, ycbcr_pixel(rgb_pixel(yuv, matrix), matrix), "{:?}", matrix);
+                }
             }
         }
     }
@@ -0,0 +1,253 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a
+ * copy of the MPL was not distributed with this file, You can obtain one at
+ * https://mozilla.org/MPL/2.0/.
+ *
+ * SPDX-License-Identifier: MPL-2.0
+ */
+
+//! Encodes and decodes the run-length encoding (RLE) used by object definition segments.
+//!
+//! # Overview
+//!
+//! Each line of an object is a sequence of palette indexes. Display set reading and writing
+//! handle this automatically, but tools that process objects on their own (such as recoloring
+//! individual pixels of an object definition segment) may use this module directly.
+
+#[cfg(test)]
+mod tests;
+
+use std::mem;
+use thiserror::Error as ThisError;
+
+/// The error type for [compress] and [decompress].
+#[derive(ThisError, Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RleError {
+    /// The data ends partway through an RLE sequence.
+    #[error("incomplete RLE sequence")]
+    IncompleteSequence,
+    /// The data contains an RLE sequence that cannot be decoded.
+    #[error("invalid RLE sequence")]
+    InvalidSequence,
+    /// The data ends partway through a line.
+    #[error("incomplete RLE line")]
+    IncompleteLine,
+    /// A line being compressed has a run of more than 16,383 identical pixels.
+    #[error("RLE line too long")]
+    LineTooLong,
+}
+
+/// Decompresses RLE-encoded object data into its lines of palette indexes.
+pub fn decompress(data: &[u8]) -> Result<Vec<Vec<u8>>, RleError> {
+    decompress_with_width(data, 0)
+}
+
+/// Compresses lines of palette indexes into RLE-encoded object data. Each line is terminated
+/// by an end-of-line sequence.
+pub fn compress(lines: &[Vec<u8>]) -> Result<Vec<u8>, RleError> {
+    Ok(compress_with_line_ends(lines)?.0)
+}
+
+/// Decompresses RLE-encoded object data, pre-allocating each line according to the declared
+/// width of the object.
+pub(crate) fn decompress_with_width(data: &[u8], width: u16) -> Result<Vec<Vec<u8>>, RleError> {
+
+    let mut output = Vec::<Vec<u8>>::new();
+
+    decompress_into(data, width, &mut output)?;
+
+    Ok(output)
+}
+
+/// Decompresses RLE-encoded object data, appending each line to `output` as it is completed.
+pub(crate) fn decompress_into(
+    input: &[u8],
+    width: u16,
+    output: &mut Vec<Vec<u8>>,
+) -> Result<(), RleError> {
+
+    // Lines are pre-allocated according to the declared object width, which avoids repeated
+    // reallocation when decoding large (e.g., full-screen) objects. As the width cannot be
+    // trusted, nothing is reserved until a line receives its first pixel, and each line after
+    // the first only reserves as much as the line before it actually held.
+    let mut capacity = width as usize;
+    let mut line = Vec::<u8>::new();
+    let mut iter = input.iter();
+
+    while let Some(byte_1) = iter.next() {
+        if *byte_1 == 0x00 {
+            match iter.next() {
+                Some(byte_2) => {
+                    if *byte_2 == 0x00 {
+                        if !line.is_empty() {
+                            capacity = line.len().min(width as usize);
+                        }
+                        output.push(mem::take(&mut line));
+                    } else if *byte_2 >> 6 == 0 {
+                        reserve_line(&mut line, capacity);
+                        line.resize(line.len() + (*byte_2 & 0x3F) as usize, 0);
+                    } else if *byte_2 >> 6 == 1 {
+                        match iter.next() {
+                            Some(byte_3) => {
+                                let count = (*byte_2 as usize & 0x3F) << 8 | *byte_3 as usize;
+                                reserve_line(&mut line, capacity);
+                                line.resize(line.len() + count, 0);
+                            }
+                            None => {
+                                return Err(RleError::IncompleteSequence)
+                            }
+                        }
+                    } else if *byte_2 >> 6 == 2 {
+                        match iter.next() {
+                            Some(byte_3) => {
+                                reserve_line(&mut line, capacity);
+                                for _ in 0..(*byte_2 & 0x3F) {
+                                    line.push(*byte_3);
+                                }
+                            }
+                            None => {
+                                return Err(RleError::IncompleteSequence)
+                            }
+                        }
+                    } else if *byte_2 >> 6 == 3 {
+                        match iter.next() {
+                            Some(byte_3) => {
+                                match iter.next() {
+                                    Some(byte_4) => {
+                                        reserve_line(&mut line, capacity);
+                                        for _ in 0..(
+                                            (*byte_2 as u16 & 0x3F) << 8
+                                            | *byte_3 as u16
+                                        ) {
+                                            line.push(*byte_4);
+                                        }
+                                    }
+                                    None => {
+                                        return Err(RleError::IncompleteSequence)
+                                    }
+                                }
+                            }
+                            None => {
+                                return Err(RleError::IncompleteSequence)
+                            }
+                        }
+                    } else {
+                        return Err(RleError::InvalidSequence)
+                    }
+                }
+                None => {
+                    return Err(RleError::IncompleteSequence)
+                }
+            }
+        } else {
+            reserve_line(&mut line, capacity);
+            line.push(*byte_1);
+        }
+    }
+
+    if !line.is_empty() {
+        return Err(RleError::IncompleteLine)
+    }
+
+    Ok(())
+}
+
+/// Reserves the expected capacity of a line that has not yet received any pixels.
+fn reserve_line(line: &mut Vec<u8>, capacity: usize) {
+    if line.capacity() == 0 {
+        line.reserve(capacity);
+    }
+}
+
+/// Compresses the lines of an object, also returning the offset just past the end of each
+/// compressed line.
+pub(crate) fn compress_with_line_ends(input: &[Vec<u8>])
+    -> Result<(Vec<u8>, Vec<usize>), RleError> {
+
+    // Compressed output rarely exceeds the size of the input, plus two bytes to end each line.
+    let mut output = Vec::<u8>::with_capacity(
+        input.iter().map(|line| line.len() + 2).sum::<usize>()
+    );
+    let mut line_ends = Vec::<usize>::with_capacity(input.len());
+    let mut byte = 0_u8;
+    let mut count = 0_usize;
+
+    for line in input {
+
+        for next_byte in line {
+            if *next_byte == byte {
+                count += 1;
+            } else {
+                if count > 0 {
+                    output_sequence(&mut output, byte, count)?;
+                }
+                byte = *next_byte;
+                count = 1;
+            }
+        }
+
+        output_sequence(&mut output, byte, count)?;
+        byte = 0;
+        count = 0;
+
+        output.push(0x00);
+        output.push(0x00);
+        line_ends.push(output.len());
+    }
+
+    Ok((output, line_ends))
+}
+
+fn output_sequence(output: &mut Vec<u8>, byte: u8, count: usize) -> Result<(), RleError> {
+
+    if byte == 0x00 {
+        match count {
+            0 => {
+                //panic!("attempted to handle zero-byte sequence in PGS line")
+            }
+            1 ..= 63 => {
+                output.push(0x00);
+                output.push(count as u8);
+            }
+            64 ..= 16_383 => {
+                output.push(0x00);
+                output.push(0x40 | (count >> 8) as u8);
+                output.push((count & 0xFF) as u8);
+            }
+            _ => {
+                return Err(RleError::LineTooLong)
+            }
+        }
+    } else {
+        match count {
+            0 => {
+                //panic!("attempted to handle zero-byte sequence in PGS line")
+            }
+            1 => {
+                output.push(byte);
+            }
+            2 => {
+                output.push(byte);
+                output.push(byte);
+            }
+            3 ..= 63 => {
+                output.push(0x00);
+                output.push(0x80 | count as u8);
+                output.push(byte);
+            }
+            64 ..= 16_383 => {
+                output.push(0x00);
+                output.push(0xC0 | (count >> 8) as u8);
+                output.push((count & 0xFF) as u8);
+                output.push(byte);
+            }
+            _ => {
+                return Err(RleError::LineTooLong)
+            }
+        }
+    }
+
+    Ok(())
+}
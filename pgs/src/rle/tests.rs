@@ -0,0 +1,101 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * To the extent possible under law, the person who associated CC0 with this file has waived all
+ * copyright and related or neighboring rights to this file.
+ *
+ * You should have received a copy of the CC0 legalcode along with this work. If not, see
+ * <http://creativecommons.org/publicdomain/zero/1.0/>.
+ *
+ * SPDX-License-Identifier: CC0-1.0
+ */
+
+use super::{
+    *,
+    super::displayset::ParseError,
+};
+use rand::{thread_rng, Rng};
+
+#[test]
+fn test_rle_compress() {
+
+    let lines = vec![
+        vec![1, 1, 2, 0, 0, 0],
+        vec![3; 70],
+    ];
+
+    assert_eq!(
+        compress(&lines).unwrap(),
+        vec![1, 1, 2, 0x00, 0x03, 0x00, 0x00, 0x00, 0xC0, 0x46, 3, 0x00, 0x00],
+    );
+    assert_eq!(compress(&[vec![5; 16_384]]), Err(RleError::LineTooLong));
+}
+
+#[test]
+fn test_rle_decompress_errors() {
+
+    assert_eq!(decompress(&[1, 2, 0x00, 0x00]).unwrap(), vec![vec![1, 2]]);
+    assert_eq!(decompress(&[0x00, 0x80]), Err(RleError::IncompleteSequence));
+    assert_eq!(decompress(&[1, 2]), Err(RleError::IncompleteLine));
+    assert!(
+        matches!(
+            ParseError::from(RleError::IncompleteSequence),
+            ParseError::IncompleteRleSequence,
+        )
+    );
+    assert!(matches!(ParseError::from(RleError::IncompleteLine), ParseError::IncompleteRleLine));
+}
+
+#[test]
+fn test_rle_decompress_untrusted_width() {
+
+    let lines = decompress_with_width(&[0x00, 0x00].repeat(1_000), u16::MAX).unwrap();
+
+    assert_eq!(lines.len(), 1_000);
+    assert!(lines.iter().all(|line| line.capacity() == 0));
+
+    let lines = decompress_with_width(&[1, 0x00, 0x00].repeat(3), u16::MAX).unwrap();
+
+    assert_eq!(lines, vec![vec![1]; 3]);
+    assert!(lines[1..].iter().all(|line| line.capacity() < u16::MAX as usize));
+}
+
+#[test]
+fn test_rle_cycle_random() {
+
+    let mut rng = thread_rng();
+
+    for trial in 0..400 {
+
+        let width = match trial % 4 {
+            3 => rng.gen_range(1..=16_383),
+            _ => rng.gen_range(1..=300),
+        };
+        let height = rng.gen_range(1..=16);
+        let lines = (0..height).map(|_| {
+            match rng.gen_range(0..4) {
+                // Dense noise, including isolated single pixels of every value.
+                0 => (0..width).map(|_| rng.gen()).collect(),
+                // Long runs of random lengths, which cross the one- and two-byte run limits.
+                1 => {
+                    let mut line = Vec::<u8>::with_capacity(width);
+                    while line.len() < width {
+                        let length = rng.gen_range(1..=width - line.len());
+                        line.extend(std::iter::repeat_n(rng.gen::<u8>(), length));
+                    }
+                    line
+                }
+                // A line that is entirely transparent.
+                2 => vec![0; width],
+                // A line that ends with transparent pixels, ahead of its terminator.
+                _ => (0..width).map(|x| if x < width / 2 { rng.gen() } else { 0 }).collect(),
+            }
+        }).collect::<Vec<Vec<u8>>>();
+        let (data, line_ends) = compress_with_line_ends(&lines).unwrap();
+
+        assert_eq!(line_ends.len(), lines.len());
+        assert_eq!(line_ends.last().copied(), Some(data.len()));
+        assert_eq!(decompress_with_width(&data, width as u16).unwrap(), lines);
+        assert_eq!(decompress(&compress(&lines).unwrap()).unwrap(), lines);
+    }
+}
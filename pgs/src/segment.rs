@@ -62,16 +62,27 @@
 //! An ES signals that the current DS has come to an end.
 //!
 //! See: [EndSegment]
+//!
+//! ## Unknown Segments
+//!
+//! Some streams contain vendor-specific segments whose kinds are not documented. These can
+//! optionally be captured as-is so that they are passed through unmodified.
+//!
+//! See: [UnknownSegment]
 
 #[cfg(test)]
 mod tests;
 
+mod segmentlayout;
 mod segmentread;
 mod segmentwrite;
 
+pub use segmentlayout::*;
 pub use segmentread::*;
 pub use segmentwrite::*;
 
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
 /// Represents a PGS segment.
 #[derive(Clone, Debug, Hash, PartialEq)]
 pub enum Segment {
@@ -91,14 +102,69 @@ pub enum Segment {
     FinalObjectDefinition(FinalObjectDefinitionSegment),
     /// Represents an End Segment (ES).
     End(EndSegment),
+    /// Represents a segment of an unrecognized kind. These are only produced when
+    /// [preserve_unknown](ReadOptions::preserve_unknown) is set.
+    Unknown(UnknownSegment),
+}
+
+impl Segment {
+
+    /// Returns the presentation timestamp (PTS) of this segment, regardless of its kind.
+    pub fn pts(&self) -> u32 {
+        self.timestamps().0
+    }
+
+    /// Returns the decoding timestamp (DTS) of this segment, regardless of its kind.
+    pub fn dts(&self) -> u32 {
+        self.timestamps().1
+    }
+
+    /// Sets the presentation timestamp (PTS) of this segment, regardless of its kind.
+    pub fn set_pts(&mut self, pts: u32) {
+        *self.timestamps_mut().0 = pts;
+    }
+
+    /// Sets the decoding timestamp (DTS) of this segment, regardless of its kind.
+    pub fn set_dts(&mut self, dts: u32) {
+        *self.timestamps_mut().1 = dts;
+    }
+
+    fn timestamps(&self) -> (u32, u32) {
+        match self {
+            Segment::PresentationComposition(pcs) => (pcs.pts, pcs.dts),
+            Segment::WindowDefinition(wds) => (wds.pts, wds.dts),
+            Segment::PaletteDefinition(pds) => (pds.pts, pds.dts),
+            Segment::SingleObjectDefinition(sods) => (sods.pts, sods.dts),
+            Segment::InitialObjectDefinition(iods) => (iods.pts, iods.dts),
+            Segment::MiddleObjectDefinition(mods) => (mods.pts, mods.dts),
+            Segment::FinalObjectDefinition(fods) => (fods.pts, fods.dts),
+            Segment::End(es) => (es.pts, es.dts),
+            Segment::Unknown(us) => (us.pts, us.dts),
+        }
+    }
+
+    fn timestamps_mut(&mut self) -> (&mut u32, &mut u32) {
+        match self {
+            Segment::PresentationComposition(pcs) => (&mut pcs.pts, &mut pcs.dts),
+            Segment::WindowDefinition(wds) => (&mut wds.pts, &mut wds.dts),
+            Segment::PaletteDefinition(pds) => (&mut pds.pts, &mut pds.dts),
+            Segment::SingleObjectDefinition(sods) => (&mut sods.pts, &mut sods.dts),
+            Segment::InitialObjectDefinition(iods) => (&mut iods.pts, &mut iods.dts),
+            Segment::MiddleObjectDefinition(mods) => (&mut mods.pts, &mut mods.dts),
+            Segment::FinalObjectDefinition(fods) => (&mut fods.pts, &mut fods.dts),
+            Segment::End(es) => (&mut es.pts, &mut es.dts),
+            Segment::Unknown(us) => (&mut us.pts, &mut us.dts),
+        }
+    }
 }
 
 /// Defines the role of a PCS (and thereby the associated DS) within an epoch.
-#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum CompositionState {
     /// Indicates that the associated PCS (and the DS it belongs to) defines the start of a new
     /// epoch. As such, the associated DS should contain all other segments necessary to render
     /// a composition onto the screen.
+    #[default]
     EpochStart,
     /// Similar to `EpochStart`, except used to refresh the screen with the current composition.
     /// That is, the associated DS should redefine the same windows, objects, and palettes as
@@ -116,8 +182,90 @@ pub enum CompositionState {
     Normal,
 }
 
-impl Default for CompositionState {
-    fn default() -> Self { Self::EpochStart }
+/// Defines the video frame rates that a PCS can declare within its
+/// [frame_rate](PresentationCompositionSegment::frame_rate) field.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FrameRate {
+    /// 24,000/1,001 (roughly 23.976) frames per second, declared as `0x10`.
+    Fps23_976,
+    /// 24 frames per second, declared as `0x20`.
+    Fps24,
+    /// 25 frames per second, declared as `0x30`.
+    Fps25,
+    /// 30,000/1,001 (roughly 29.97) frames per second, declared as `0x40`.
+    Fps29_97,
+    /// 50 frames per second, declared as `0x60`.
+    Fps50,
+    /// 60,000/1,001 (roughly 59.94) frames per second, declared as `0x70`.
+    Fps59_94,
+}
+
+impl FrameRate {
+
+    /// Decodes a declared frame rate, returning `None` if it is unrecognized.
+    pub fn from_byte(value: u8) -> Option<Self> {
+        match value {
+            0x10 => Some(FrameRate::Fps23_976),
+            0x20 => Some(FrameRate::Fps24),
+            0x30 => Some(FrameRate::Fps25),
+            0x40 => Some(FrameRate::Fps29_97),
+            0x60 => Some(FrameRate::Fps50),
+            0x70 => Some(FrameRate::Fps59_94),
+            _ => None,
+        }
+    }
+
+    /// Encodes this frame rate as it is declared within a PCS.
+    pub fn to_byte(self) -> u8 {
+        match self {
+            FrameRate::Fps23_976 => 0x10,
+            FrameRate::Fps24 => 0x20,
+            FrameRate::Fps25 => 0x30,
+            FrameRate::Fps29_97 => 0x40,
+            FrameRate::Fps50 => 0x60,
+            FrameRate::Fps59_94 => 0x70,
+        }
+    }
+
+    /// Returns the number of frames per second.
+    pub fn fps(self) -> f64 {
+        match self {
+            FrameRate::Fps23_976 => 24_000.0 / 1_001.0,
+            FrameRate::Fps24 => 24.0,
+            FrameRate::Fps25 => 25.0,
+            FrameRate::Fps29_97 => 30_000.0 / 1_001.0,
+            FrameRate::Fps50 => 50.0,
+            FrameRate::Fps59_94 => 60_000.0 / 1_001.0,
+        }
+    }
+
+    /// Returns the number of frames per second as an exact `(numerator, denominator)` pair.
+    pub fn ratio(self) -> (u32, u32) {
+        match self {
+            FrameRate::Fps23_976 => (24_000, 1_001),
+            FrameRate::Fps24 => (24, 1),
+            FrameRate::Fps25 => (25, 1),
+            FrameRate::Fps29_97 => (30_000, 1_001),
+            FrameRate::Fps50 => (50, 1),
+            FrameRate::Fps59_94 => (60_000, 1_001),
+        }
+    }
+}
+
+impl Display for FrameRate {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(
+            match self {
+                FrameRate::Fps23_976 => "23.976",
+                FrameRate::Fps24 => "24",
+                FrameRate::Fps25 => "25",
+                FrameRate::Fps29_97 => "29.97",
+                FrameRate::Fps50 => "50",
+                FrameRate::Fps59_94 => "59.94",
+            }
+        )
+    }
 }
 
 /// Defines a Presentation Composition Segment (PCS).
@@ -304,7 +452,9 @@ pub struct InitialObjectDefinitionSegment {
     /// The version increment of this object. Other portions of this object should have the same
     /// ID.
     pub version: u8,
-    /// The declared length of this object's data buffer, including all follow-on portions.
+    /// The declared length of this object's data buffer, including all follow-on portions. As
+    /// with the data length that is implicitly written for a single-part object, this includes
+    /// the four bytes occupied by the object's dimensions. See [recompute_iods_length].
     pub length: usize,
     /// The width of this complete object in pixels, including follow-on portions.
     pub width: u16,
@@ -365,3 +515,32 @@ pub struct EndSegment {
     /// value is always zero.
     pub dts: u32,
 }
+
+/// Sets the declared [length](InitialObjectDefinitionSegment::length) of a multi-part object
+/// to agree with the data actually held by each of its portions. This must be done after
+/// splicing or otherwise modifying the data of any portion, as the declared length includes
+/// the data of all portions plus the four bytes occupied by the object's dimensions.
+pub fn recompute_iods_length(
+    iods: &mut InitialObjectDefinitionSegment,
+    mods: &[MiddleObjectDefinitionSegment],
+    fods: &FinalObjectDefinitionSegment,
+) {
+    iods.length = iods.data.len()
+        + mods.iter().map(|mods| mods.data.len()).sum::<usize>()
+        + fods.data.len()
+        + 4;
+}
+
+/// Defines a segment of an unrecognized kind, such as a vendor-specific extension. Its payload
+/// is retained as-is so that it can be written back out byte-for-byte.
+#[derive(Clone, Debug, Default, Hash, PartialEq)]
+pub struct UnknownSegment {
+    /// The timestamp indicating when composition decoding should start.
+    pub pts: u32,
+    /// The timestamp indicating when the composition should be displayed.
+    pub dts: u32,
+    /// The unrecognized kind of this segment.
+    pub kind: u8,
+    /// The raw payload of this segment.
+    pub data: Vec<u8>,
+}
@@ -0,0 +1,147 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a
+ * copy of the MPL was not distributed with this file, You can obtain one at
+ * https://mozilla.org/MPL/2.0/.
+ *
+ * SPDX-License-Identifier: MPL-2.0
+ */
+
+use super::Segment;
+
+/// Describes where a single field lands within a serialized segment.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct FieldDescriptor {
+    /// The offset of the field in bytes, relative to the start of the segment's header.
+    pub offset: usize,
+    /// The length of the field in bytes.
+    pub length: usize,
+    /// The name of the field. Fields belonging to repeated structures are prefixed with the
+    /// name and index of the structure, such as `composition_objects[1].x`.
+    pub name: String,
+}
+
+impl Segment {
+
+    /// Describes the byte layout of this segment as it would be written by
+    /// [write_segment](super::WriteSegmentExt::write_segment). Fields are returned in the order
+    /// they are serialized, starting with the thirteen-byte header common to all segments, and
+    /// are laid out contiguously.
+    pub fn describe_layout(&self) -> Vec<FieldDescriptor> {
+
+        let mut layout = Layout::default();
+
+        layout.field("magic_number", 2);
+        layout.field("pts", 4);
+        layout.field("dts", 4);
+        layout.field("kind", 1);
+        layout.field("size", 2);
+
+        match self {
+            Segment::PresentationComposition(pcs) => {
+                layout.field("width", 2);
+                layout.field("height", 2);
+                layout.field("frame_rate", 1);
+                layout.field("composition_number", 2);
+                layout.field("composition_state", 1);
+                layout.field("palette_update_flag", 1);
+                layout.field("palette_id", 1);
+                layout.field("composition_object_count", 1);
+                for (index, comp_obj) in pcs.composition_objects.iter().enumerate() {
+                    let prefix = format!("composition_objects[{}]", index);
+                    layout.field(format!("{}.object_id", prefix), 2);
+                    layout.field(format!("{}.window_id", prefix), 1);
+                    layout.field(format!("{}.flags", prefix), 1);
+                    layout.field(format!("{}.x", prefix), 2);
+                    layout.field(format!("{}.y", prefix), 2);
+                    if comp_obj.crop.is_some() {
+                        layout.field(format!("{}.crop.x", prefix), 2);
+                        layout.field(format!("{}.crop.y", prefix), 2);
+                        layout.field(format!("{}.crop.width", prefix), 2);
+                        layout.field(format!("{}.crop.height", prefix), 2);
+                    }
+                }
+            }
+            Segment::WindowDefinition(wds) => {
+                layout.field("window_count", 1);
+                for index in 0..wds.windows.len() {
+                    let prefix = format!("windows[{}]", index);
+                    layout.field(format!("{}.id", prefix), 1);
+                    layout.field(format!("{}.x", prefix), 2);
+                    layout.field(format!("{}.y", prefix), 2);
+                    layout.field(format!("{}.width", prefix), 2);
+                    layout.field(format!("{}.height", prefix), 2);
+                }
+            }
+            Segment::PaletteDefinition(pds) => {
+                layout.field("id", 1);
+                layout.field("version", 1);
+                for index in 0..pds.entries.len() {
+                    let prefix = format!("entries[{}]", index);
+                    layout.field(format!("{}.id", prefix), 1);
+                    layout.field(format!("{}.y", prefix), 1);
+                    layout.field(format!("{}.cr", prefix), 1);
+                    layout.field(format!("{}.cb", prefix), 1);
+                    layout.field(format!("{}.alpha", prefix), 1);
+                }
+            }
+            Segment::SingleObjectDefinition(sods) => {
+                layout.object_header();
+                layout.field("data_length", 3);
+                layout.field("width", 2);
+                layout.field("height", 2);
+                layout.field("data", sods.data.len());
+            }
+            Segment::InitialObjectDefinition(iods) => {
+                layout.object_header();
+                layout.field("data_length", 3);
+                layout.field("width", 2);
+                layout.field("height", 2);
+                layout.field("data", iods.data.len());
+            }
+            Segment::MiddleObjectDefinition(mods) => {
+                layout.object_header();
+                layout.field("data", mods.data.len());
+            }
+            Segment::FinalObjectDefinition(fods) => {
+                layout.object_header();
+                layout.field("data", fods.data.len());
+            }
+            Segment::End(_) => {
+            }
+            Segment::Unknown(us) => {
+                layout.field("data", us.data.len());
+            }
+        }
+
+        layout.fields
+    }
+}
+
+#[derive(Default)]
+struct Layout {
+    fields: Vec<FieldDescriptor>,
+    offset: usize,
+}
+
+impl Layout {
+
+    fn field(&mut self, name: impl Into<String>, length: usize) {
+
+        self.fields.push(
+            FieldDescriptor {
+                offset: self.offset,
+                length,
+                name: name.into(),
+            }
+        );
+        self.offset += length;
+    }
+
+    fn object_header(&mut self) {
+        self.field("id", 2);
+        self.field("version", 1);
+        self.field("sequence_flags", 1);
+    }
+}
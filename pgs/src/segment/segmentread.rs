@@ -21,13 +21,14 @@ use super::{
     PresentationCompositionSegment,
     Segment,
     SingleObjectDefinitionSegment,
+    UnknownSegment,
     WindowDefinition,
     WindowDefinitionSegment,
 };
 use std::{
-    io::{Error as IoError, Read},
+    io::{copy, sink, Error as IoError, ErrorKind, Read, Seek, SeekFrom},
 };
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt};
 use thiserror::Error as ThisError;
 
 /// A specialized [`Result`](std::result::Result) type for segment-reading operations.
@@ -87,12 +88,40 @@ pub enum ReadError {
     /// - `0xC0` (declares a single, complete object)
     /// - `0x80` (declares the initial portion of an object)
     /// - `0x40` (declares the final portion of an object)
+    ///
     /// Otherwise, the segment is interpreted as being a middle portion.
     #[error("unrecognized object definition sequence flag")]
     UnrecognizedObjectSequenceFlag {
         /// The sequence flag that was parsed.
         parsed_sequence_flag: u8,
     },
+    /// The bitstream declares a segment size that is too small to hold the fixed fields of its
+    /// kind of segment.
+    #[error("segment is too short")]
+    SegmentTooShort {
+        /// The segment size that was parsed.
+        parsed_size: u16,
+        /// The minimum size for this kind of segment.
+        minimum_size: u16,
+    },
+    /// The bitstream declares a segment size that is too small to hold the fields that the
+    /// segment's own contents call for, such as the composition objects counted by a
+    /// presentation composition segment (PCS). Reading on would run into the next segment.
+    #[error("segment contents exceed declared size")]
+    SegmentSizeMismatch {
+        /// The segment size that was parsed.
+        parsed_size: u16,
+        /// The size required by the fields read so far.
+        required_size: u32,
+    },
+    /// The bitstream declares a size for a palette definition segment (PDS) that does not
+    /// consist of whole palette entries. As a PDS has no explicit entry count, the count is
+    /// derived from the size, which must therefore be two bytes plus a multiple of five.
+    #[error("malformed palette definition segment")]
+    MalformedPaletteDefinition {
+        /// The segment size that was parsed.
+        parsed_size: u16,
+    },
     /// The bitstream declares an invalid data length within an object definition segment (ODS).
     /// Specifically, the declared data length must agree with the segment's total size.
     #[error("invalid object data length")]
@@ -102,12 +131,95 @@ pub enum ReadError {
         /// The data length that was expected.
         expected_data_length: u32,
     },
+    /// The stream does not consist of whole segments laid end-to-end. Either a segment does not
+    /// begin with the magic number where the previous segment's declared size says it should,
+    /// or the stream ends partway through a segment.
+    #[error("segment framing breaks at offset {offset}")]
+    FramingBreak {
+        /// The byte offset at which the broken segment begins.
+        offset: u64,
+    },
+}
+
+/// Defines options that control how segments are read.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ReadOptions {
+    /// Skips any run of [pad bytes](Self::pad_byte) that precedes a segment's magic number.
+    /// This is a workaround for certain muxers that pad segments to some alignment; conforming
+    /// streams never contain such padding.
+    pub skip_padding: bool,
+    /// The byte value that is skipped when [padding is skipped](Self::skip_padding). This is
+    /// `0xFF` by default.
+    pub pad_byte: u8,
+    /// Captures segments of unrecognized kinds as [Segment::Unknown] instead of failing with
+    /// [ReadError::UnrecognizedKind]. This allows streams containing vendor-specific segments
+    /// to be passed through losslessly.
+    pub preserve_unknown: bool,
+    /// Interprets each segment's declared size as including the thirteen-byte header rather
+    /// than covering the payload alone; conforming streams never do this. Streams written this
+    /// way can be recognized by every declared size exceeding the segment's payload by exactly
+    /// thirteen bytes. Reading them without this option over-reads segment payloads and loses
+    /// its place, which typically surfaces as an
+    /// [UnrecognizedMagicNumber](ReadError::UnrecognizedMagicNumber) error.
+    pub size_includes_header: bool,
+}
+
+impl Default for ReadOptions {
+
+    fn default() -> Self {
+        ReadOptions {
+            skip_padding: false,
+            pad_byte: 0xFF,
+            preserve_unknown: false,
+            size_includes_header: false,
+        }
+    }
+}
+
+/// Describes an object definition segment (ODS) whose data was read by
+/// [read_object_into](ReadSegmentExt::read_object_into).
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ObjectMeta {
+    /// The timestamp indicating when composition decoding should start.
+    pub pts: u32,
+    /// The timestamp indicating when the composition should be displayed.
+    pub dts: u32,
+    /// The ID of the object.
+    pub id: u16,
+    /// The version of the object.
+    pub version: u8,
+    /// The sequence flag of the segment, which is `0xC0` for a single object, `0x80` for the
+    /// initial portion of an object, `0x00` for a middle portion, and `0x40` for the final
+    /// portion.
+    pub sequence_flag: u8,
+    /// The width and height of the object in pixels. Only single objects and initial portions
+    /// declare these.
+    pub dimensions: Option<(u16, u16)>,
 }
 
 /// Allows reading segments from a source.
 pub trait ReadSegmentExt {
     /// Reads the next segment from a source.
     fn read_segment(&mut self) -> ReadResult<Segment>;
+    /// Reads the next segment from a source using the specified options.
+    fn read_segment_with_options(&mut self, options: &ReadOptions) -> ReadResult<Segment>;
+    /// Reads the next segment from a source, also returning the complete raw bytes (including
+    /// the header) that it was parsed from. This allows unmodified segments to be passed
+    /// through byte-for-byte.
+    fn read_segment_raw(&mut self) -> ReadResult<(Segment, Vec<u8>)>;
+    /// Reads the next segment and its raw bytes from a source using the specified options. Any
+    /// skipped padding is not included in the raw bytes.
+    fn read_segment_raw_with_options(&mut self, options: &ReadOptions)
+        -> ReadResult<(Segment, Vec<u8>)>;
+    /// Reads the next object definition segment (ODS) from a source, placing its data into
+    /// `buf` after clearing it, and returning the remaining fields. Any other kinds of segments
+    /// preceding it are skipped without being parsed. Reusing the same buffer across many
+    /// objects avoids allocating for each one.
+    fn read_object_into(&mut self, buf: &mut Vec<u8>) -> ReadResult<ObjectMeta>;
+    /// Reads the next object definition segment (ODS) from a source into `buf` using the
+    /// specified options.
+    fn read_object_into_with_options(&mut self, buf: &mut Vec<u8>, options: &ReadOptions)
+        -> ReadResult<ObjectMeta>;
 }
 
 impl<T> ReadSegmentExt for T where
@@ -115,86 +227,354 @@ impl<T> ReadSegmentExt for T where
 {
 
     fn read_segment(&mut self) -> ReadResult<Segment> {
+        self.read_segment_with_options(&ReadOptions::default())
+    }
 
-        let magic_number = self.read_u16::<BigEndian>()?;
+    fn read_segment_with_options(&mut self, options: &ReadOptions) -> ReadResult<Segment> {
+        read_segment_ordered::<BigEndian>(self, options)
+    }
 
-        if magic_number != 0x5047 {
-            return Err(ReadError::UnrecognizedMagicNumber { parsed_magic_number: magic_number })
-        }
+    fn read_segment_raw(&mut self) -> ReadResult<(Segment, Vec<u8>)> {
+        self.read_segment_raw_with_options(&ReadOptions::default())
+    }
+
+    fn read_segment_raw_with_options(&mut self, options: &ReadOptions)
+        -> ReadResult<(Segment, Vec<u8>)> {
+
+        let header = read_header::<BigEndian>(self, options)?;
+        let declared_size = header.size + if options.size_includes_header { 13 } else { 0 };
+        let mut raw = Vec::<u8>::with_capacity(13 + header.size as usize);
+
+        raw.extend_from_slice(&0x5047_u16.to_be_bytes());
+        raw.extend_from_slice(&header.pts.to_be_bytes());
+        raw.extend_from_slice(&header.dts.to_be_bytes());
+        raw.push(header.kind);
+        raw.extend_from_slice(&declared_size.to_be_bytes());
+        raw.resize(13 + header.size as usize, 0x00);
+        self.read_exact(&mut raw[13..])?;
+
+        let segment = parse_segment::<BigEndian>(&header, &mut &raw[13..], options)?;
+
+        Ok((segment, raw))
+    }
+
+    fn read_object_into(&mut self, buf: &mut Vec<u8>) -> ReadResult<ObjectMeta> {
+        self.read_object_into_with_options(buf, &ReadOptions::default())
+    }
 
-        let pts = self.read_u32::<BigEndian>()?;
-        let dts = self.read_u32::<BigEndian>()?;
-        let kind = self.read_u8()?;
-        let size = self.read_u16::<BigEndian>()?;
+    fn read_object_into_with_options(&mut self, buf: &mut Vec<u8>, options: &ReadOptions)
+        -> ReadResult<ObjectMeta> {
 
-        Ok(
-            match kind {
-                0x14 => {
-                    Segment::PaletteDefinition(parse_pds(pts, dts, self, size)?)
+        loop {
+
+            let Header { pts, dts, kind, size } = read_header::<BigEndian>(self, options)?;
+
+            if kind != 0x15 {
+                if copy(&mut self.by_ref().take(size as u64), &mut sink())? < size as u64 {
+                    return Err(IoError::from(ErrorKind::UnexpectedEof).into())
                 }
-                0x15 => {
+                continue
+            }
 
-                    let id = self.read_u16::<BigEndian>()?;
-                    let version = self.read_u8()?;
-                    let sequence_flag = self.read_u8()?;
+            check_size(size, 4)?;
 
-                    match sequence_flag {
-                        0xC0 => {
-                            Segment::SingleObjectDefinition(
-                                parse_sods(pts, dts, id, version, self, size)?
-                            )
-                        }
-                        0x80 => {
-                            Segment::InitialObjectDefinition(
-                                parse_iods(pts, dts, id, version, self, size)?
-                            )
-                        }
-                        0x00 => {
-                            Segment::MiddleObjectDefinition(
-                                parse_mods(pts, dts, id, version, self, size)?
-                            )
-                        }
-                        0x40 => {
-                            Segment::FinalObjectDefinition(
-                                parse_fods(pts, dts, id, version, self, size)?
-                            )
-                        }
-                        _ => {
-                            return Err(
-                                ReadError::UnrecognizedObjectSequenceFlag {
-                                    parsed_sequence_flag: sequence_flag
-                                }
-                            )
-                        }
+            let id = self.read_u16::<BigEndian>()?;
+            let version = self.read_u8()?;
+            let sequence_flag = self.read_u8()?;
+            let dimensions = match sequence_flag {
+                0xC0 | 0x80 => {
+
+                    check_size(size, 11)?;
+
+                    let parsed_data_length = self.read_u24::<BigEndian>()?;
+                    let expected_data_length = size as u32 - 7;
+
+                    if sequence_flag == 0xC0 && parsed_data_length != expected_data_length {
+                        return Err(
+                            ReadError::InvalidObjectDataLength {
+                                parsed_data_length,
+                                expected_data_length,
+                            }
+                        )
                     }
+
+                    Some((self.read_u16::<BigEndian>()?, self.read_u16::<BigEndian>()?))
                 }
-                0x16 => {
-                    Segment::PresentationComposition(parse_pcs(pts, dts, self)?)
-                }
-                0x17 => {
-                    Segment::WindowDefinition(parse_wds(pts, dts, self)?)
-                }
-                0x80 => {
-                    Segment::End(EndSegment { pts, dts })
+                0x00 | 0x40 => {
+                    None
                 }
                 _ => {
-                    return Err(ReadError::UnrecognizedKind { parsed_kind: kind })
+                    return Err(
+                        ReadError::UnrecognizedObjectSequenceFlag {
+                            parsed_sequence_flag: sequence_flag
+                        }
+                    )
                 }
+            };
+
+            buf.clear();
+            buf.resize(size as usize - if dimensions.is_some() { 11 } else { 4 }, 0x00);
+            self.read_exact(buf)?;
+
+            return Ok(ObjectMeta { pts, dts, id, version, sequence_flag, dimensions })
+        }
+    }
+}
+
+/// Collects the PTS of every presentation composition segment (PCS), and therefore of every
+/// display set, within a source in stream order. Only segment headers are parsed; the payload
+/// of each segment is skipped by seeking past it. This is far faster than reading each display
+/// set in full when only timing is needed. Collection stops once the end of the source is
+/// reached before the first byte of a segment header, whereas reaching it partway through a
+/// header is reported as an [UnexpectedEof](ErrorKind::UnexpectedEof) I/O error.
+pub fn collect_pts<R: Read + Seek>(mut r: R) -> ReadResult<Vec<u32>> {
+
+    let mut pts_values = Vec::<u32>::new();
+    let mut header = Vec::<u8>::with_capacity(13);
+
+    loop {
+
+        header.clear();
+
+        match r.by_ref().take(13).read_to_end(&mut header)? {
+            0 => break,
+            13 => {
             }
-        )
+            _ => return Err(IoError::from(ErrorKind::UnexpectedEof).into()),
+        }
+
+        let magic_number = u16::from_be_bytes([header[0], header[1]]);
+
+        if magic_number != 0x5047 {
+            return Err(ReadError::UnrecognizedMagicNumber { parsed_magic_number: magic_number })
+        }
+
+        if header[10] == 0x16 {
+            pts_values.push(u32::from_be_bytes([header[2], header[3], header[4], header[5]]));
+        }
+
+        r.seek(SeekFrom::Current(u16::from_be_bytes([header[11], header[12]]) as i64))?;
     }
+
+    Ok(pts_values)
 }
 
-fn parse_pcs(
+/// Checks the structural integrity of a stream by confirming that it consists of whole
+/// segments laid end-to-end, returning the number of segments it contains. Only segment headers
+/// are parsed; each declared size must lead exactly to the magic number of the next segment or
+/// to the end of the stream. Payloads are skipped without being parsed, so this is a fast check
+/// for corruption or truncation. The first break in framing is reported as
+/// [FramingBreak](ReadError::FramingBreak).
+pub fn validate_framing<R: Read>(mut r: R) -> ReadResult<usize> {
+
+    let mut count = 0;
+    let mut offset = 0_u64;
+    let mut header = Vec::<u8>::with_capacity(13);
+
+    loop {
+
+        header.clear();
+
+        match r.by_ref().take(13).read_to_end(&mut header)? {
+            0 => break,
+            13 => {
+            }
+            _ => return Err(ReadError::FramingBreak { offset }),
+        }
+
+        if header[0..2] != [0x50, 0x47] {
+            return Err(ReadError::FramingBreak { offset })
+        }
+
+        let size = u16::from_be_bytes([header[11], header[12]]) as u64;
+
+        if copy(&mut r.by_ref().take(size), &mut sink())? < size {
+            return Err(ReadError::FramingBreak { offset })
+        }
+
+        count += 1;
+        offset += 13 + size;
+    }
+
+    Ok(count)
+}
+
+/// Reads the next segment from a source as though every multi-byte field other than the magic
+/// number were little-endian. This is strictly a diagnostic for confirming whether a mystery
+/// stream has been byte-swapped by a broken tool; conforming streams are always big-endian and
+/// should never be read this way. As the declared size of each segment is also byte-swapped,
+/// reading will likely lose its place within a conforming stream.
+#[cfg(feature = "debug-endian")]
+pub fn read_segment_le<R: Read>(mut r: R) -> ReadResult<Segment> {
+    read_segment_ordered::<byteorder::LittleEndian>(&mut r, &ReadOptions::default())
+}
+
+/// The fields shared by every segment header.
+#[derive(Clone, Copy)]
+struct Header {
+    pts: u32,
+    dts: u32,
+    kind: u8,
+    /// The size of the payload alone, regardless of how the stream declares it.
+    size: u16,
+}
+
+/// Reads a segment header, skipping any padding and adjusting the declared size according to
+/// the specified options. Every multi-byte field other than the magic number is interpreted
+/// using the specified byte order.
+fn read_header<B: ByteOrder>(input: &mut dyn Read, options: &ReadOptions)
+    -> ReadResult<Header> {
+
+    let magic_number = if options.skip_padding {
+
+        let mut byte = input.read_u8()?;
+
+        while byte == options.pad_byte {
+            byte = input.read_u8()?;
+        }
+
+        u16::from_be_bytes([byte, input.read_u8()?])
+    } else {
+        input.read_u16::<BigEndian>()?
+    };
+
+    if magic_number != 0x5047 {
+        return Err(ReadError::UnrecognizedMagicNumber { parsed_magic_number: magic_number })
+    }
+
+    let pts = input.read_u32::<B>()?;
+    let dts = input.read_u32::<B>()?;
+    let kind = input.read_u8()?;
+    let size = input.read_u16::<B>()?;
+    let size = if options.size_includes_header {
+        check_size(size, 13)?;
+        size - 13
+    } else {
+        size
+    };
+
+    Ok(Header { pts, dts, kind, size })
+}
+
+/// Reads a segment, interpreting every multi-byte field other than the magic number using the
+/// specified byte order.
+fn read_segment_ordered<B: ByteOrder>(input: &mut dyn Read, options: &ReadOptions)
+    -> ReadResult<Segment> {
+
+    let header = read_header::<B>(input, options)?;
+
+    parse_segment::<B>(&header, input, options)
+}
+
+/// Parses the payload of a segment whose header has already been read.
+fn parse_segment<B: ByteOrder>(header: &Header, input: &mut dyn Read, options: &ReadOptions)
+    -> ReadResult<Segment> {
+
+    let Header { pts, dts, kind, size } = *header;
+
+    check_size(
+        size,
+        match kind {
+            0x14 => 2,
+            0x15 => 4,
+            0x16 => 11,
+            0x17 => 1,
+            _ => 0,
+        },
+    )?;
+
+    Ok(
+        match kind {
+            0x14 => {
+                Segment::PaletteDefinition(parse_pds(pts, dts, input, size)?)
+            }
+            0x15 => {
+
+                let id = input.read_u16::<B>()?;
+                let version = input.read_u8()?;
+                let sequence_flag = input.read_u8()?;
+
+                match sequence_flag {
+                    0xC0 => {
+                        Segment::SingleObjectDefinition(
+                            parse_sods::<B>(pts, dts, id, version, input, size)?
+                        )
+                    }
+                    0x80 => {
+                        Segment::InitialObjectDefinition(
+                            parse_iods::<B>(pts, dts, id, version, input, size)?
+                        )
+                    }
+                    0x00 => {
+                        Segment::MiddleObjectDefinition(
+                            parse_mods(pts, dts, id, version, input, size)?
+                        )
+                    }
+                    0x40 => {
+                        Segment::FinalObjectDefinition(
+                            parse_fods(pts, dts, id, version, input, size)?
+                        )
+                    }
+                    _ => {
+                        return Err(
+                            ReadError::UnrecognizedObjectSequenceFlag {
+                                parsed_sequence_flag: sequence_flag
+                            }
+                        )
+                    }
+                }
+            }
+            0x16 => {
+                Segment::PresentationComposition(parse_pcs::<B>(pts, dts, input, size)?)
+            }
+            0x17 => {
+                Segment::WindowDefinition(parse_wds::<B>(pts, dts, input)?)
+            }
+            0x80 => {
+                Segment::End(EndSegment { pts, dts })
+            }
+            _ if options.preserve_unknown => {
+
+                let mut data = vec![0x00_u8; size as usize];
+
+                input.read_exact(&mut data)?;
+
+                Segment::Unknown(UnknownSegment { pts, dts, kind, data })
+            }
+            _ => {
+                return Err(ReadError::UnrecognizedKind { parsed_kind: kind })
+            }
+        }
+    )
+}
+
+fn check_fits(size: u16, required_size: u32) -> ReadResult<()> {
+    if required_size > size as u32 {
+        Err(ReadError::SegmentSizeMismatch { parsed_size: size, required_size })
+    } else {
+        Ok(())
+    }
+}
+
+fn check_size(size: u16, minimum_size: u16) -> ReadResult<()> {
+    if size < minimum_size {
+        Err(ReadError::SegmentTooShort { parsed_size: size, minimum_size })
+    } else {
+        Ok(())
+    }
+}
+
+fn parse_pcs<B: ByteOrder>(
     pts: u32,
     dts: u32,
     input: &mut dyn Read,
+    size: u16,
 ) -> ReadResult<PresentationCompositionSegment> {
 
-    let width = input.read_u16::<BigEndian>()?;
-    let height = input.read_u16::<BigEndian>()?;
+    let width = input.read_u16::<B>()?;
+    let height = input.read_u16::<B>()?;
     let frame_rate = input.read_u8()?;
-    let composition_number = input.read_u16::<BigEndian>()?;
+    let composition_number = input.read_u16::<B>()?;
     let parsed_composition_state = input.read_u8()?;
     let composition_state = match parsed_composition_state {
         0x00 => CompositionState::Normal,
@@ -217,22 +597,28 @@ fn parse_pcs(
     let palette_id = input.read_u8()?;
     let comp_obj_count = input.read_u8()? as usize;
     let mut composition_objects = Vec::new();
+    let mut consumed = 11;
 
     for _ in 0..comp_obj_count {
 
-        let object_id = input.read_u16::<BigEndian>()?;
+        consumed += 8;
+        check_fits(size, consumed)?;
+
+        let object_id = input.read_u16::<B>()?;
         let window_id = input.read_u8()?;
         let flags = input.read_u8()?;
-        let x = input.read_u16::<BigEndian>()?;
-        let y = input.read_u16::<BigEndian>()?;
+        let x = input.read_u16::<B>()?;
+        let y = input.read_u16::<B>()?;
         let forced = flags & 0x40 != 0;
         let crop = if flags & 0x80 != 0 {
+            consumed += 8;
+            check_fits(size, consumed)?;
             Some(
                 Crop {
-                    x: input.read_u16::<BigEndian>()?,
-                    y: input.read_u16::<BigEndian>()?,
-                    width: input.read_u16::<BigEndian>()?,
-                    height: input.read_u16::<BigEndian>()?,
+                    x: input.read_u16::<B>()?,
+                    y: input.read_u16::<B>()?,
+                    width: input.read_u16::<B>()?,
+                    height: input.read_u16::<B>()?,
                 }
             )
         } else {
@@ -267,7 +653,7 @@ fn parse_pcs(
     )
 }
 
-fn parse_wds(
+fn parse_wds<B: ByteOrder>(
     pts: u32,
     dts: u32,
     input: &mut dyn Read,
@@ -280,10 +666,10 @@ fn parse_wds(
         windows.push(
             WindowDefinition {
                 id: input.read_u8()?,
-                x: input.read_u16::<BigEndian>()?,
-                y: input.read_u16::<BigEndian>()?,
-                width: input.read_u16::<BigEndian>()?,
-                height: input.read_u16::<BigEndian>()?,
+                x: input.read_u16::<B>()?,
+                y: input.read_u16::<B>()?,
+                width: input.read_u16::<B>()?,
+                height: input.read_u16::<B>()?,
             }
         );
     }
@@ -304,6 +690,10 @@ fn parse_pds(
     size: u16,
 ) -> ReadResult<PaletteDefinitionSegment> {
 
+    if !(size - 2).is_multiple_of(5) {
+        return Err(ReadError::MalformedPaletteDefinition { parsed_size: size })
+    }
+
     let count = (size - 2) / 5;
     let id = input.read_u8()?;
     let version = input.read_u8()?;
@@ -331,7 +721,7 @@ fn parse_pds(
     )
 }
 
-fn parse_sods(
+fn parse_sods<B: ByteOrder>(
     pts: u32,
     dts: u32,
     id: u16,
@@ -340,8 +730,10 @@ fn parse_sods(
     size: u16,
 ) -> ReadResult<SingleObjectDefinitionSegment> {
 
+    check_size(size, 11)?;
+
     // PGS streams record +4 bytes for the object data size, for some reason.
-    let parsed_data_length = input.read_u24::<BigEndian>()?;
+    let parsed_data_length = input.read_u24::<B>()?;
     let expected_data_length = size as u32 - 7;
 
     if parsed_data_length != expected_data_length {
@@ -353,8 +745,8 @@ fn parse_sods(
         )
     }
 
-    let width = input.read_u16::<BigEndian>()?;
-    let height = input.read_u16::<BigEndian>()?;
+    let width = input.read_u16::<B>()?;
+    let height = input.read_u16::<B>()?;
     let mut data = vec![0x00_u8; size as usize - 11]; input.read_exact(&mut data)?;
 
     Ok(
@@ -370,7 +762,7 @@ fn parse_sods(
     )
 }
 
-fn parse_iods(
+fn parse_iods<B: ByteOrder>(
     pts: u32,
     dts: u32,
     id: u16,
@@ -379,9 +771,11 @@ fn parse_iods(
     size: u16,
 ) -> ReadResult<InitialObjectDefinitionSegment> {
 
-    let length = input.read_u24::<BigEndian>()? as usize;
-    let width = input.read_u16::<BigEndian>()?;
-    let height = input.read_u16::<BigEndian>()?;
+    check_size(size, 11)?;
+
+    let length = input.read_u24::<B>()? as usize;
+    let width = input.read_u16::<B>()?;
+    let height = input.read_u16::<B>()?;
     let mut data = vec![0x00_u8; size as usize - 11]; input.read_exact(&mut data)?;
 
     Ok(
@@ -119,6 +119,12 @@ impl<T> WriteSegmentExt for T where
                 self.write_u8(0x80)?;
                 vec![]
             }
+            Segment::Unknown(us) => {
+                self.write_u32::<BigEndian>(us.pts)?;
+                self.write_u32::<BigEndian>(us.dts)?;
+                self.write_u8(us.kind)?;
+                us.data.clone()
+            }
         };
 
         self.write_u16::<BigEndian>(payload.len() as u16)?;
@@ -15,7 +15,7 @@ use super::{
     segmentread::ReadSegmentExt,
     segmentwrite::WriteSegmentExt,
 };
-use std::io::Cursor;
+use std::io::{Cursor, ErrorKind};
 use rand::{thread_rng, Rng, RngCore};
 
 #[test]
@@ -353,14 +353,654 @@ fn test_es() {
     cycle(&segment);
 }
 
-fn cycle(segment: &Segment) {
+#[test]
+fn test_unknown_preserved() {
+
+    let mut rng = thread_rng();
+    let mut data = vec![0x00_u8; 64]; rng.fill_bytes(&mut data);
+    let segment = Segment::Unknown(
+        UnknownSegment {
+            pts: rng.gen(),
+            dts: rng.gen(),
+            kind: 0x42,
+            data,
+        }
+    );
+    let options = ReadOptions {
+        preserve_unknown: true,
+        ..Default::default()
+    };
+    let mut buffer = vec![];
+
+    buffer.write_segment(&segment).unwrap();
+
+    assert!(matches!(
+        Cursor::new(&buffer).read_segment(),
+        Err(ReadError::UnrecognizedKind { parsed_kind: 0x42 }),
+    ));
+
+    let cycled_segment = Cursor::new(&buffer).read_segment_with_options(&options).unwrap();
+    let mut cycled_buffer = vec![];
+
+    cycled_buffer.write_segment(&cycled_segment).unwrap();
+
+    assert_eq!(cycled_segment, segment);
+    assert_eq!(cycled_buffer, buffer);
+}
+
+#[test]
+fn test_pcs_inflated_object_count() {
+
+    let buffer = vec![
+        0x50, 0x47, // magic number
+        0x00, 0x00, 0x00, 0x00, // PTS
+        0x00, 0x00, 0x00, 0x00, // DTS
+        0x16, // PCS
+        0x00, 0x13, // size
+        0x07, 0x80, 0x04, 0x38, 0x10, // width, height, and frame rate
+        0x00, 0x00, 0x80, 0x00, 0x00, // composition number, state, and palette update
+        0x02, // composition object count
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // composition object
+        0x50, 0x47, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // next segment
+    ];
+
+    assert!(matches!(
+        Cursor::new(buffer).read_segment(),
+        Err(ReadError::SegmentSizeMismatch { parsed_size: 19, required_size: 27 }),
+    ));
+}
+
+#[test]
+fn test_mods_too_short() {
+
+    let buffer = vec![
+        0x50, 0x47, // magic number
+        0x00, 0x00, 0x00, 0x00, // PTS
+        0x00, 0x00, 0x00, 0x00, // DTS
+        0x15, // ODS
+        0x00, 0x02, // size
+        0x00, 0x01,
+    ];
+
+    assert!(matches!(
+        Cursor::new(buffer).read_segment(),
+        Err(ReadError::SegmentTooShort { parsed_size: 2, minimum_size: 4 }),
+    ));
+}
+
+#[test]
+fn test_sods_too_short() {
+
+    let buffer = vec![
+        0x50, 0x47, // magic number
+        0x00, 0x00, 0x00, 0x00, // PTS
+        0x00, 0x00, 0x00, 0x00, // DTS
+        0x15, // ODS
+        0x00, 0x06, // size
+        0x00, 0x01, 0x00, 0xC0, 0x00, 0x00,
+    ];
+
+    assert!(matches!(
+        Cursor::new(buffer).read_segment(),
+        Err(ReadError::SegmentTooShort { parsed_size: 6, minimum_size: 11 }),
+    ));
+}
+
+#[test]
+fn test_skip_padding() {
+
+    let first = Segment::End(EndSegment { pts: 1, dts: 0 });
+    let second = Segment::End(EndSegment { pts: 2, dts: 0 });
+    let mut buffer = vec![0xFF, 0xFF];
+    let options = ReadOptions {
+        skip_padding: true,
+        ..Default::default()
+    };
+
+    buffer.write_segment(&first).unwrap();
+    buffer.extend([0xFF, 0xFF, 0xFF]);
+    buffer.write_segment(&second).unwrap();
+
+    assert!(matches!(
+        Cursor::new(&buffer).read_segment(),
+        Err(ReadError::UnrecognizedMagicNumber { parsed_magic_number: 0xFFFF }),
+    ));
+
+    let mut cursor = Cursor::new(&buffer);
+
+    assert_eq!(cursor.read_segment_with_options(&options).unwrap(), first);
+    assert_eq!(cursor.read_segment_with_options(&options).unwrap(), second);
+}
+
+#[test]
+fn test_size_includes_header() {
+
+    let segments = vec![
+        Segment::PaletteDefinition(
+            PaletteDefinitionSegment {
+                pts: 1_000,
+                dts: 0,
+                id: 1,
+                version: 0,
+                entries: vec![
+                    PaletteEntry {
+                        id: 0,
+                        y: 235,
+                        cr: 128,
+                        cb: 128,
+                        alpha: 255,
+                    },
+                ],
+            }
+        ),
+        Segment::End(EndSegment { pts: 1_000, dts: 0 }),
+    ];
+    let mut buffer = vec![];
+
+    for segment in &segments {
+
+        let mut segment_buffer = vec![];
+
+        segment_buffer.write_segment(segment).unwrap();
+
+        let size = u16::from_be_bytes([segment_buffer[11], segment_buffer[12]]) + 13;
+
+        segment_buffer[11..13].copy_from_slice(&size.to_be_bytes());
+        buffer.extend(segment_buffer);
+    }
+
+    let options = ReadOptions {
+        size_includes_header: true,
+        ..Default::default()
+    };
+    let mut cursor = Cursor::new(buffer);
+
+    for segment in &segments {
+        assert_eq!(&cursor.read_segment_with_options(&options).unwrap(), segment);
+    }
+
+    assert!(matches!(
+        Cursor::new(vec![0x50, 0x47, 0, 0, 0, 0, 0, 0, 0, 0, 0x80, 0x00, 0x0C])
+            .read_segment_with_options(&options),
+        Err(ReadError::SegmentTooShort { parsed_size: 12, minimum_size: 13 }),
+    ));
+}
+
+#[test]
+fn test_collect_pts() {
+
+    let mut buffer = vec![];
+
+    for pts in [1_000, 2_000, 3_000] {
+        buffer.write_segment(&Segment::PresentationComposition(
+            PresentationCompositionSegment {
+                pts,
+                dts: 0,
+                width: 1920,
+                height: 1080,
+                frame_rate: 0x10,
+                composition_number: 0,
+                composition_state: CompositionState::EpochStart,
+                palette_update_only: false,
+                palette_id: 0x00,
+                composition_objects: vec![],
+            }
+        )).unwrap();
+        buffer.write_segment(&Segment::PaletteDefinition(
+            PaletteDefinitionSegment {
+                pts,
+                dts: 0,
+                id: 0,
+                version: 0,
+                entries: vec![],
+            }
+        )).unwrap();
+        buffer.write_segment(&Segment::End(EndSegment { pts, dts: 0 })).unwrap();
+    }
+
+    assert_eq!(collect_pts(Cursor::new(&buffer)).unwrap(), vec![1_000, 2_000, 3_000]);
+    assert!(collect_pts(Cursor::new(&[])).unwrap().is_empty());
+    assert!(matches!(
+        collect_pts(Cursor::new(&[0x00; 13])),
+        Err(ReadError::UnrecognizedMagicNumber { parsed_magic_number: 0x0000 }),
+    ));
+    assert!(matches!(
+        collect_pts(Cursor::new(&buffer[..buffer.len() - 8])),
+        Err(ReadError::IoError { source }) if source.kind() == ErrorKind::UnexpectedEof,
+    ));
+}
+
+#[test]
+fn test_validate_framing() {
+
+    let mut buffer = vec![];
+
+    buffer.write_segment(&Segment::PaletteDefinition(
+        PaletteDefinitionSegment {
+            pts: 1_000,
+            dts: 0,
+            id: 0,
+            version: 0,
+            entries: vec![],
+        }
+    )).unwrap();
+
+    let second_offset = buffer.len();
+
+    buffer.write_segment(&Segment::End(EndSegment { pts: 1_000, dts: 0 })).unwrap();
+    buffer.write_segment(&Segment::End(EndSegment { pts: 2_000, dts: 0 })).unwrap();
+
+    assert_eq!(validate_framing(buffer.as_slice()).unwrap(), 3);
+    assert_eq!(validate_framing(&[][..]).unwrap(), 0);
+    assert!(matches!(
+        validate_framing(&buffer[..buffer.len() - 1]),
+        Err(ReadError::FramingBreak { offset }) if offset == buffer.len() as u64 - 13,
+    ));
+    assert!(matches!(
+        validate_framing(&buffer[..second_offset - 1]),
+        Err(ReadError::FramingBreak { offset: 0 }),
+    ));
+
+    buffer[second_offset] = 0x00;
+
+    assert!(matches!(
+        validate_framing(buffer.as_slice()),
+        Err(ReadError::FramingBreak { offset }) if offset == second_offset as u64,
+    ));
+}
+
+#[test]
+fn test_pds_malformed() {
+
+    let buffer = vec![
+        0x50, 0x47, // magic number
+        0x00, 0x00, 0x00, 0x00, // PTS
+        0x00, 0x00, 0x00, 0x00, // DTS
+        0x14, // PDS
+        0x00, 0x09, // size
+        0x00, 0x00, // ID and version
+        0x01, 0x10, 0x80, 0x80, 0xFF, // complete entry
+        0x02, 0x10, // truncated entry
+    ];
+
+    assert!(matches!(
+        Cursor::new(buffer).read_segment(),
+        Err(ReadError::MalformedPaletteDefinition { parsed_size: 9 }),
+    ));
+}
+
+#[test]
+fn test_recompute_iods_length() {
+
+    let mut iods = InitialObjectDefinitionSegment {
+        id: 1,
+        width: 100,
+        height: 100,
+        data: vec![0x01; 10],
+        ..Default::default()
+    };
+    let mut fods = FinalObjectDefinitionSegment {
+        id: 1,
+        data: vec![0x01; 6],
+        ..Default::default()
+    };
+
+    recompute_iods_length(&mut iods, &[], &fods);
+
+    assert_eq!(iods.length, 20);
+
+    let mods = [
+        MiddleObjectDefinitionSegment {
+            id: 1,
+            data: vec![0x01; 5],
+            ..Default::default()
+        },
+    ];
+
+    fods.data.truncate(2);
+    recompute_iods_length(&mut iods, &mods, &fods);
+
+    assert_eq!(iods.length, 21);
+}
+
+#[test]
+fn test_frame_rate() {
+
+    for value in 0..=255 {
+        if let Some(frame_rate) = FrameRate::from_byte(value) {
+            assert_eq!(frame_rate.to_byte(), value);
+        }
+    }
+
+    assert_eq!(FrameRate::from_byte(0x10), Some(FrameRate::Fps23_976));
+    assert_eq!(FrameRate::from_byte(0x50), None);
+    assert_eq!(FrameRate::Fps23_976.to_string(), "23.976");
+    assert!((FrameRate::Fps59_94.fps() - 59.94).abs() < 0.001);
+}
+
+#[test]
+fn test_segment_timestamps() {
+
+    let mut segments = vec![
+        Segment::PresentationComposition(
+            PresentationCompositionSegment { pts: 1, dts: 2, ..Default::default() }
+        ),
+        Segment::WindowDefinition(WindowDefinitionSegment { pts: 3, dts: 4, windows: vec![] }),
+        Segment::PaletteDefinition(
+            PaletteDefinitionSegment { pts: 5, dts: 6, ..Default::default() }
+        ),
+        Segment::SingleObjectDefinition(
+            SingleObjectDefinitionSegment { pts: 7, dts: 8, ..Default::default() }
+        ),
+        Segment::InitialObjectDefinition(
+            InitialObjectDefinitionSegment { pts: 9, dts: 10, ..Default::default() }
+        ),
+        Segment::MiddleObjectDefinition(
+            MiddleObjectDefinitionSegment { pts: 11, dts: 12, ..Default::default() }
+        ),
+        Segment::FinalObjectDefinition(
+            FinalObjectDefinitionSegment { pts: 13, dts: 14, ..Default::default() }
+        ),
+        Segment::End(EndSegment { pts: 15, dts: 16 }),
+        Segment::Unknown(UnknownSegment { pts: 17, dts: 18, ..Default::default() }),
+    ];
+
+    for (index, segment) in segments.iter_mut().enumerate() {
+
+        let index = index as u32;
+
+        assert_eq!(segment.pts(), 2 * index + 1);
+        assert_eq!(segment.dts(), 2 * index + 2);
+
+        segment.set_pts(100 + index);
+        segment.set_dts(200 + index);
+
+        assert_eq!(segment.pts(), 100 + index);
+        assert_eq!(segment.dts(), 200 + index);
+    }
+
+    match &segments[0] {
+        Segment::PresentationComposition(pcs) => assert_eq!((pcs.pts, pcs.dts), (100, 200)),
+        _ => panic!("segment kind changed"),
+    }
+}
+
+#[test]
+fn test_pcs_layout() {
 
+    let segment = Segment::PresentationComposition(
+        PresentationCompositionSegment {
+            pts: 0x01020304,
+            dts: 0x05060708,
+            width: 1920,
+            height: 1080,
+            frame_rate: 0x10,
+            composition_number: 0x0A0B,
+            composition_state: CompositionState::EpochStart,
+            palette_update_only: false,
+            palette_id: 0x03,
+            composition_objects: vec![
+                CompositionObject {
+                    object_id: 0x0102,
+                    window_id: 0x04,
+                    x: 0x0506,
+                    y: 0x0708,
+                    forced: true,
+                    crop: None,
+                },
+                CompositionObject {
+                    object_id: 0x1112,
+                    window_id: 0x14,
+                    x: 0x1516,
+                    y: 0x1718,
+                    forced: false,
+                    crop: Some(
+                        Crop {
+                            x: 0x2122,
+                            y: 0x2324,
+                            width: 0x2526,
+                            height: 0x2728,
+                        }
+                    ),
+                },
+            ],
+        }
+    );
     let mut buffer = vec![];
 
     buffer.write_segment(&segment).unwrap();
 
+    let layout = segment.describe_layout();
+    let field = |name: &str| {
+        let field = layout.iter().find(|field| field.name == name).unwrap();
+        &buffer[field.offset..field.offset + field.length]
+    };
+
+    assert_eq!(layout.last().map(|field| field.offset + field.length), Some(buffer.len()));
+    assert!(layout.windows(2).all(|pair| pair[0].offset + pair[0].length == pair[1].offset));
+    assert_eq!(field("magic_number"), &[0x50, 0x47]);
+    assert_eq!(field("pts"), &[0x01, 0x02, 0x03, 0x04]);
+    assert_eq!(field("dts"), &[0x05, 0x06, 0x07, 0x08]);
+    assert_eq!(field("kind"), &[0x16]);
+    assert_eq!(field("size"), &[0x00, 0x23]);
+    assert_eq!(field("width"), &[0x07, 0x80]);
+    assert_eq!(field("height"), &[0x04, 0x38]);
+    assert_eq!(field("frame_rate"), &[0x10]);
+    assert_eq!(field("composition_number"), &[0x0A, 0x0B]);
+    assert_eq!(field("composition_state"), &[0x80]);
+    assert_eq!(field("palette_update_flag"), &[0x00]);
+    assert_eq!(field("palette_id"), &[0x03]);
+    assert_eq!(field("composition_object_count"), &[0x02]);
+    assert_eq!(field("composition_objects[0].object_id"), &[0x01, 0x02]);
+    assert_eq!(field("composition_objects[0].window_id"), &[0x04]);
+    assert_eq!(field("composition_objects[0].flags"), &[0x40]);
+    assert_eq!(field("composition_objects[0].x"), &[0x05, 0x06]);
+    assert_eq!(field("composition_objects[0].y"), &[0x07, 0x08]);
+    assert_eq!(field("composition_objects[1].object_id"), &[0x11, 0x12]);
+    assert_eq!(field("composition_objects[1].flags"), &[0x80]);
+    assert_eq!(field("composition_objects[1].crop.x"), &[0x21, 0x22]);
+    assert_eq!(field("composition_objects[1].crop.height"), &[0x27, 0x28]);
+}
+
+fn cycle(segment: &Segment) {
+
+    let mut buffer = vec![];
+
+    buffer.write_segment(segment).unwrap();
+
     let mut cursor = Cursor::new(buffer);
     let cycled_segment = cursor.read_segment().unwrap();
 
     assert_eq!(cycled_segment, *segment);
+
+    let mut cursor = Cursor::new(cursor.into_inner());
+    let (raw_segment, raw) = cursor.read_segment_raw().unwrap();
+
+    assert_eq!(raw_segment, *segment);
+    assert_eq!(raw, cursor.into_inner());
+}
+
+#[test]
+fn test_read_object_into() {
+
+    let mut buffer = vec![];
+
+    buffer.write_segment(&Segment::End(EndSegment { pts: 1_000, dts: 0 })).unwrap();
+    buffer.write_segment(&Segment::SingleObjectDefinition(
+        SingleObjectDefinitionSegment {
+            pts: 1_000,
+            dts: 0,
+            id: 1,
+            version: 2,
+            width: 64,
+            height: 32,
+            data: vec![0xAA; 40],
+        }
+    )).unwrap();
+    buffer.write_segment(&Segment::PaletteDefinition(
+        PaletteDefinitionSegment {
+            pts: 2_000,
+            dts: 0,
+            id: 0,
+            version: 0,
+            entries: vec![],
+        }
+    )).unwrap();
+    buffer.write_segment(&Segment::FinalObjectDefinition(
+        FinalObjectDefinitionSegment {
+            pts: 2_000,
+            dts: 0,
+            id: 3,
+            version: 4,
+            data: vec![0xBB; 8],
+        }
+    )).unwrap();
+
+    let mut cursor = Cursor::new(buffer);
+    let mut data = Vec::<u8>::new();
+
+    assert_eq!(
+        cursor.read_object_into(&mut data).unwrap(),
+        ObjectMeta {
+            pts: 1_000,
+            dts: 0,
+            id: 1,
+            version: 2,
+            sequence_flag: 0xC0,
+            dimensions: Some((64, 32)),
+        },
+    );
+    assert_eq!(data, vec![0xAA; 40]);
+
+    let pointer = data.as_ptr();
+
+    assert_eq!(
+        cursor.read_object_into(&mut data).unwrap(),
+        ObjectMeta {
+            pts: 2_000,
+            dts: 0,
+            id: 3,
+            version: 4,
+            sequence_flag: 0x40,
+            dimensions: None,
+        },
+    );
+    assert_eq!(data, vec![0xBB; 8]);
+    assert_eq!(data.as_ptr(), pointer);
+    assert!(matches!(
+        cursor.read_object_into(&mut data),
+        Err(ReadError::IoError { source }) if source.kind() == ErrorKind::UnexpectedEof,
+    ));
+}
+
+#[test]
+fn test_raw_and_object_reads_use_options() {
+
+    let segments = vec![
+        Segment::End(EndSegment { pts: 1_000, dts: 0 }),
+        Segment::SingleObjectDefinition(
+            SingleObjectDefinitionSegment {
+                pts: 2_000,
+                dts: 0,
+                id: 1,
+                version: 2,
+                width: 8,
+                height: 4,
+                data: vec![0xAA; 6],
+            }
+        ),
+    ];
+    let mut buffer = vec![];
+    let mut raws = vec![];
+
+    for segment in &segments {
+
+        let mut raw = vec![];
+
+        raw.write_segment(segment).unwrap();
+
+        let size = u16::from_be_bytes([raw[11], raw[12]]) + 13;
+
+        raw[11..13].copy_from_slice(&size.to_be_bytes());
+        buffer.extend([0xFF, 0xFF]);
+        buffer.extend(&raw);
+        raws.push(raw);
+    }
+
+    let options = ReadOptions {
+        skip_padding: true,
+        size_includes_header: true,
+        ..Default::default()
+    };
+    let mut cursor = Cursor::new(&buffer);
+
+    for (segment, raw) in segments.iter().zip(&raws) {
+        assert_eq!(
+            cursor.read_segment_raw_with_options(&options).unwrap(),
+            (segment.clone(), raw.clone()),
+        );
+    }
+
+    let mut cursor = Cursor::new(&buffer);
+    let mut data = Vec::<u8>::new();
+
+    assert_eq!(
+        cursor.read_object_into_with_options(&mut data, &options).unwrap(),
+        ObjectMeta {
+            pts: 2_000,
+            dts: 0,
+            id: 1,
+            version: 2,
+            sequence_flag: 0xC0,
+            dimensions: Some((8, 4)),
+        },
+    );
+    assert_eq!(data, vec![0xAA; 6]);
+    assert!(matches!(
+        Cursor::new(&buffer).read_object_into(&mut data),
+        Err(ReadError::UnrecognizedMagicNumber { parsed_magic_number: 0xFFFF }),
+    ));
+}
+
+#[cfg(feature = "debug-endian")]
+#[test]
+fn test_read_segment_le() {
+
+    let segment = Segment::WindowDefinition(
+        WindowDefinitionSegment {
+            pts: 0x0001_5F90,
+            dts: 0x0000_0102,
+            windows: vec![
+                WindowDefinition {
+                    id: 3,
+                    x: 0x0102,
+                    y: 0x0304,
+                    width: 0x0780,
+                    height: 0x0438,
+                },
+            ],
+        }
+    );
+    let mut buffer = vec![];
+
+    buffer.write_segment(&segment).unwrap();
+
+    assert_eq!(
+        read_segment_le(buffer.as_slice()).unwrap(),
+        Segment::WindowDefinition(
+            WindowDefinitionSegment {
+                pts: 0x905F_0100,
+                dts: 0x0201_0000,
+                windows: vec![
+                    WindowDefinition {
+                        id: 3,
+                        x: 0x0201,
+                        y: 0x0403,
+                        width: 0x8007,
+                        height: 0x3804,
+                    },
+                ],
+            }
+        ),
+    );
 }
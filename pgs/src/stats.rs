@@ -0,0 +1,121 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a
+ * copy of the MPL was not distributed with this file, You can obtain one at
+ * https://mozilla.org/MPL/2.0/.
+ *
+ * SPDX-License-Identifier: MPL-2.0
+ */
+
+//! Summarizes entire PGS streams.
+
+#[cfg(test)]
+mod tests;
+
+use super::{
+    ts_to_timestamp,
+    displayset::{display_sets, DisplaySet, ReadResult},
+    epoch::Epoch,
+    segment::CompositionState,
+};
+use std::{
+    collections::BTreeSet,
+    fmt::{Display, Formatter, Result as FmtResult},
+    io::Read,
+    mem,
+};
+
+/// An overview of an entire PGS stream.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct StreamStats {
+    /// The number of display sets in the stream.
+    pub display_set_count: usize,
+    /// The number of epochs in the stream.
+    pub epoch_count: usize,
+    /// Each distinct screen resolution encountered, as `(width, height)`.
+    pub resolutions: BTreeSet<(u16, u16)>,
+    /// The total number of objects defined throughout the stream.
+    pub object_count: usize,
+    /// The largest number of windows defined at any single point within the stream.
+    pub peak_window_count: usize,
+    /// The span of time between the first and last display sets, in 90 kHz ticks.
+    pub duration: u32,
+    /// The number of display sets that present at least one forced composition object.
+    /// Acquisition points and palette updates are not counted, as they only repeat or alter a
+    /// caption that is already being shown.
+    pub forced_caption_count: usize,
+}
+
+impl StreamStats {
+
+    fn add(&mut self, ds: &DisplaySet, first_pts: u32) {
+
+        if self.display_set_count == 0 || ds.composition.state == CompositionState::EpochStart {
+            self.epoch_count += 1;
+        }
+
+        self.display_set_count += 1;
+        self.resolutions.insert((ds.width, ds.height));
+        self.object_count += ds.objects.len();
+        self.duration = ds.pts.saturating_sub(first_pts).max(self.duration);
+
+        if ds.composition.state != CompositionState::AcquisitionPoint
+            && !ds.palete_update_only
+            && ds.composition.objects.values().any(|co| co.forced) {
+            self.forced_caption_count += 1;
+        }
+    }
+
+    fn add_epoch(&mut self, epoch: &Epoch) {
+        self.peak_window_count = self.peak_window_count.max(epoch.peak_window_count() as usize);
+    }
+}
+
+impl Display for StreamStats {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+
+        writeln!(f, "display_sets = {}", self.display_set_count)?;
+        writeln!(f, "epochs = {}", self.epoch_count)?;
+
+        for (width, height) in &self.resolutions {
+            writeln!(f, "resolution = {}x{}", width, height)?;
+        }
+
+        writeln!(f, "objects = {}", self.object_count)?;
+        writeln!(f, "peak_windows = {}", self.peak_window_count)?;
+        writeln!(f, "duration = {}", ts_to_timestamp(self.duration))?;
+        write!(f, "forced_captions = {}", self.forced_caption_count)
+    }
+}
+
+/// Reads every display set from a source until the end of the stream is reached, returning an
+/// overview of the stream. The stream must end between display sets; should it end anywhere
+/// else, the error encountered while reading the truncated display set is returned.
+pub fn stream_stats<R: Read>(r: R) -> ReadResult<StreamStats> {
+
+    let mut stats = StreamStats::default();
+    let mut first_pts = None;
+    let mut epoch = Epoch::default();
+
+    for ds in display_sets(r) {
+
+        let ds = ds?;
+        let first_pts = *first_pts.get_or_insert(ds.pts);
+
+        if ds.composition.state == CompositionState::EpochStart {
+            stats.add_epoch(&mem::take(&mut epoch));
+        }
+
+        stats.add(&ds, first_pts);
+
+        // Only windows are needed to find the peak window count, so object data is not kept
+        // around for the rest of the epoch.
+        epoch.display_sets.push(DisplaySet { windows: ds.windows, ..Default::default() });
+    }
+
+    stats.add_epoch(&epoch);
+
+    Ok(stats)
+}
@@ -0,0 +1,139 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * To the extent possible under law, the person who associated CC0 with this file has waived all
+ * copyright and related or neighboring rights to this file.
+ *
+ * You should have received a copy of the CC0 legalcode along with this work. If not, see
+ * <http://creativecommons.org/publicdomain/zero/1.0/>.
+ *
+ * SPDX-License-Identifier: CC0-1.0
+ */
+
+use super::{
+    *,
+    super::displayset::{
+        Cid,
+        Composition,
+        CompositionObject,
+        Object,
+        Vid,
+        Window,
+        WriteDisplaySetExt,
+    },
+};
+
+fn display_set(pts: u32, state: CompositionState, forced: Option<bool>) -> DisplaySet {
+
+    let mut display_set = DisplaySet {
+        pts,
+        width: 1920,
+        height: 1080,
+        composition: Composition {
+            state,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    if let Some(forced) = forced {
+        display_set.windows.insert(0, Window::default());
+        display_set.objects.insert(
+            Vid {
+                id: 0,
+                version: 0,
+            },
+            Object {
+                width: 1,
+                height: 1,
+                lines: vec![vec![1]],
+            },
+        );
+        display_set.composition.objects.insert(
+            Cid {
+                object_id: 0,
+                window_id: 0,
+            },
+            CompositionObject {
+                forced,
+                ..Default::default()
+            },
+        );
+    }
+
+    display_set
+}
+
+#[test]
+fn test_stream_stats() {
+
+    let mut buffer = vec![];
+    let mut two_windows = display_set(5_000, CompositionState::EpochStart, Some(true));
+
+    two_windows.windows.insert(1, Window::default());
+    two_windows.width = 1280;
+    two_windows.height = 720;
+
+    for ds in [
+        display_set(1_000, CompositionState::EpochStart, Some(false)),
+        display_set(2_000, CompositionState::AcquisitionPoint, Some(false)),
+        display_set(3_000, CompositionState::Normal, None),
+        two_windows,
+        display_set(6_000, CompositionState::AcquisitionPoint, Some(true)),
+        display_set(91_000, CompositionState::Normal, None),
+    ] {
+        buffer.write_display_set(ds).unwrap();
+    }
+
+    let stats = stream_stats(buffer.as_slice()).unwrap();
+    let mut resolutions = BTreeSet::<(u16, u16)>::new();
+
+    resolutions.insert((1280, 720));
+    resolutions.insert((1920, 1080));
+
+    assert_eq!(
+        stats,
+        StreamStats {
+            display_set_count: 6,
+            epoch_count: 2,
+            resolutions,
+            object_count: 4,
+            peak_window_count: 2,
+            duration: 90_000,
+            forced_caption_count: 1,
+        },
+    );
+    assert_eq!(
+        stats.to_string(),
+        "display_sets = 6\n\
+        epochs = 2\n\
+        resolution = 1280x720\n\
+        resolution = 1920x1080\n\
+        objects = 4\n\
+        peak_windows = 2\n\
+        duration = 00:00:01.000\n\
+        forced_captions = 1",
+    );
+}
+
+#[test]
+fn test_stream_stats_empty() {
+    assert_eq!(stream_stats([].as_slice()).unwrap(), StreamStats::default());
+}
+
+#[test]
+fn test_stream_stats_truncated() {
+
+    let mut buffer = vec![];
+
+    buffer.write_display_set(display_set(1_000, CompositionState::EpochStart, Some(false)))
+        .unwrap();
+
+    let length = buffer.len();
+
+    buffer.write_display_set(display_set(2_000, CompositionState::Normal, None)).unwrap();
+
+    assert_eq!(stream_stats(&buffer[..length]).unwrap().display_set_count, 1);
+    assert!(stream_stats(&buffer[..length + 5]).is_err());
+    assert!(stream_stats(&buffer[..buffer.len() - 1]).is_err());
+}
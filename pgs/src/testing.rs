@@ -0,0 +1,112 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a
+ * copy of the MPL was not distributed with this file, You can obtain one at
+ * https://mozilla.org/MPL/2.0/.
+ *
+ * SPDX-License-Identifier: MPL-2.0
+ */
+
+//! Provides assertions for checking invariants of display sets and epochs within tests.
+//!
+//! # Overview
+//!
+//! These helpers are available to downstream crates through the `testing` feature. Each one
+//! panics with a descriptive message should its invariant not hold, in the manner of
+//! [assert!].
+
+#[cfg(test)]
+mod tests;
+
+use super::{
+    displayset::{DisplaySet, WriteDisplaySetExt},
+    epoch::Epoch,
+    segment::{CompositionState, ReadError as SegmentReadError, ReadSegmentExt, Segment},
+};
+use std::io::{Cursor, ErrorKind};
+
+/// Asserts that every segment a display set is written as carries the PTS and DTS of the
+/// display set itself.
+#[track_caller]
+pub fn assert_consistent_timing(ds: &DisplaySet) {
+
+    let mut buffer = vec![];
+
+    if let Err(err) = buffer.write_display_set(ds.clone()) {
+        panic!("display set could not be written: {}", err)
+    }
+
+    let mut cursor = Cursor::new(buffer);
+
+    for index in 0.. {
+
+        let segment = match cursor.read_segment() {
+            Ok(segment) => segment,
+            Err(SegmentReadError::IoError { source })
+                if source.kind() == ErrorKind::UnexpectedEof => break,
+            Err(err) => panic!("segment {} could not be read back: {}", index, err),
+        };
+        let (pts, dts) = match segment {
+            Segment::PresentationComposition(pcs) => (pcs.pts, pcs.dts),
+            Segment::WindowDefinition(wds) => (wds.pts, wds.dts),
+            Segment::PaletteDefinition(pds) => (pds.pts, pds.dts),
+            Segment::SingleObjectDefinition(sods) => (sods.pts, sods.dts),
+            Segment::InitialObjectDefinition(iods) => (iods.pts, iods.dts),
+            Segment::MiddleObjectDefinition(mods) => (mods.pts, mods.dts),
+            Segment::FinalObjectDefinition(fods) => (fods.pts, fods.dts),
+            Segment::End(es) => (es.pts, es.dts),
+            Segment::Unknown(us) => (us.pts, us.dts),
+        };
+
+        assert!(
+            pts == ds.pts && dts == ds.dts,
+            "segment {} has PTS/DTS of {}/{} rather than {}/{}",
+            index,
+            pts,
+            dts,
+            ds.pts,
+            ds.dts,
+        );
+    }
+}
+
+/// Asserts that an epoch is well formed. It must begin with an
+/// [EpochStart](CompositionState::EpochStart), the PTS of each display set must
+/// be greater than that of the one before it, and every display set must have the same screen
+/// resolution.
+#[track_caller]
+pub fn assert_valid_epoch(epoch: &Epoch) {
+
+    let Some(first) = epoch.display_sets.first() else {
+        panic!("epoch has no display sets")
+    };
+
+    assert!(
+        first.composition.state == CompositionState::EpochStart,
+        "epoch begins with a composition state of {:?} rather than EpochStart",
+        first.composition.state,
+    );
+
+    for (index, pair) in epoch.display_sets.windows(2).enumerate() {
+        assert!(
+            pair[1].pts > pair[0].pts,
+            "display set {} has a PTS of {}, which does not follow {}",
+            index + 1,
+            pair[1].pts,
+            pair[0].pts,
+        );
+    }
+
+    for (index, ds) in epoch.display_sets.iter().enumerate() {
+        assert!(
+            ds.width == first.width && ds.height == first.height,
+            "display set {} has a resolution of {}x{} rather than {}x{}",
+            index,
+            ds.width,
+            ds.height,
+            first.width,
+            first.height,
+        );
+    }
+}
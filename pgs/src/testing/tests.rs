@@ -0,0 +1,87 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * To the extent possible under law, the person who associated CC0 with this file has waived all
+ * copyright and related or neighboring rights to this file.
+ *
+ * You should have received a copy of the CC0 legalcode along with this work. If not, see
+ * <http://creativecommons.org/publicdomain/zero/1.0/>.
+ *
+ * SPDX-License-Identifier: CC0-1.0
+ */
+
+use super::{
+    *,
+    super::displayset::Composition,
+};
+
+fn display_set(pts: u32, state: CompositionState) -> DisplaySet {
+    DisplaySet {
+        pts,
+        dts: pts / 2,
+        width: 1920,
+        height: 1080,
+        frame_rate: 0x10,
+        composition: Composition {
+            state,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn epoch() -> Epoch {
+    Epoch {
+        display_sets: vec![
+            display_set(1_000, CompositionState::EpochStart),
+            display_set(2_000, CompositionState::Normal),
+            display_set(3_000, CompositionState::AcquisitionPoint),
+        ],
+    }
+}
+
+#[test]
+fn test_assert_consistent_timing() {
+    assert_consistent_timing(&display_set(90_000, CompositionState::EpochStart));
+}
+
+#[test]
+fn test_assert_valid_epoch() {
+    assert_valid_epoch(&epoch());
+}
+
+#[test]
+#[should_panic(expected = "rather than EpochStart")]
+fn test_assert_valid_epoch_start() {
+
+    let mut epoch = epoch();
+
+    epoch.display_sets[0].composition.state = CompositionState::Normal;
+    assert_valid_epoch(&epoch);
+}
+
+#[test]
+#[should_panic(expected = "does not follow")]
+fn test_assert_valid_epoch_pts() {
+
+    let mut epoch = epoch();
+
+    epoch.display_sets[2].pts = 2_000;
+    assert_valid_epoch(&epoch);
+}
+
+#[test]
+#[should_panic(expected = "rather than 1920x1080")]
+fn test_assert_valid_epoch_resolution() {
+
+    let mut epoch = epoch();
+
+    epoch.display_sets[1].height = 720;
+    assert_valid_epoch(&epoch);
+}
+
+#[test]
+#[should_panic(expected = "no display sets")]
+fn test_assert_valid_epoch_empty() {
+    assert_valid_epoch(&Epoch::default());
+}
@@ -0,0 +1,51 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * To the extent possible under law, the person who associated CC0 with this file has waived all
+ * copyright and related or neighboring rights to this file.
+ *
+ * You should have received a copy of the CC0 legalcode along with this work. If not, see
+ * <http://creativecommons.org/publicdomain/zero/1.0/>.
+ *
+ * SPDX-License-Identifier: CC0-1.0
+ */
+
+use super::*;
+
+#[test]
+fn test_timestamp_to_ts() {
+    assert_eq!(timestamp_to_ts("00:00:00.000"), Some(0));
+    assert_eq!(timestamp_to_ts("00:10:00.000"), Some(54_000_000));
+    assert_eq!(timestamp_to_ts("01:02:03.004"), Some(335_070_360));
+    assert_eq!(timestamp_to_ts("00:00:01"), Some(90_000));
+}
+
+#[test]
+fn test_timestamp_to_ts_invalid() {
+    assert_eq!(timestamp_to_ts(""), None);
+    assert_eq!(timestamp_to_ts("00:00"), None);
+    assert_eq!(timestamp_to_ts("00:60:00.000"), None);
+    assert_eq!(timestamp_to_ts("00:00:00.5"), None);
+    assert_eq!(timestamp_to_ts("00:00:00:00.000"), None);
+    assert_eq!(timestamp_to_ts("14:00:00.000"), None);
+}
+
+#[test]
+fn test_timestamp_cycle() {
+    for ts in [0, 90, 90_000, 335_070_360, 3_600_000 * 90] {
+        assert_eq!(timestamp_to_ts(&ts_to_timestamp(ts)), Some(ts));
+    }
+}
+
+#[test]
+fn test_ts_to_timecode() {
+    assert_eq!(ts_to_timecode(0, FrameRate::Fps29_97), "00:00:00;00");
+    assert_eq!(ts_to_timecode(1_799 * 3_003, FrameRate::Fps29_97), "00:00:59;29");
+    assert_eq!(ts_to_timecode(1_800 * 3_003, FrameRate::Fps29_97), "00:01:00;02");
+    assert_eq!(ts_to_timecode(17_982 * 3_003, FrameRate::Fps29_97), "00:10:00;00");
+    assert_eq!(ts_to_timecode(107_892 * 3_003, FrameRate::Fps29_97), "01:00:00;00");
+    assert_eq!(ts_to_timecode(5_405_400, FrameRate::Fps59_94), "00:01:00;04");
+    assert_eq!(ts_to_timecode(90_000, FrameRate::Fps25), "00:00:01:00");
+    assert_eq!(ts_to_timecode(3_754, FrameRate::Fps23_976), "00:00:00:01");
+    assert_eq!(ts_to_timecode(3_002, FrameRate::Fps29_97), "00:00:00;01");
+}
@@ -10,14 +10,18 @@
 
 use pgs::{
     ts_to_timestamp,
+    displayset::{decompress_lenient, Object, Palette, PaletteEntry},
     segment::{
         CompositionState,
+        FrameRate,
+        ReadOptions,
         ReadSegmentExt,
         Segment,
         ReadError,
     },
 };
 use std::{
+    collections::BTreeMap,
     fs::File,
     io::{stdin, BufReader, ErrorKind, Read},
 };
@@ -32,12 +36,22 @@ fn main() {
             .help("Input PGS file; use - for STDIN")
             .required(true)
         )
+        .arg(Arg::with_name("hex-objects")
+            .long("hex-objects")
+            .help("Prints a hexdump of the RLE data within each object definition segment")
+        )
+        .arg(Arg::with_name("ascii-objects")
+            .long("ascii-objects")
+            .help("Prints each decoded object as ASCII art using the current palette")
+        )
         .after_help(format!("This utility will dump PGS subtitle bitstream data.\n\n\
             Copyright © 2021 William Swartzendruber\n\
             Licensed under the Mozilla Public License 2.0\n\
             <{}>", env!("CARGO_PKG_REPOSITORY")).as_str())
         .get_matches();
     let input_value = matches.value_of("input").unwrap();
+    let hex_objects = matches.is_present("hex-objects");
+    let ascii_objects = matches.is_present("ascii-objects");
     let (mut stdin_read, mut file_read);
     let mut input = BufReader::<&mut dyn Read>::new(
         if input_value == "-" {
@@ -50,6 +64,15 @@ fn main() {
         }
     );
 
+    let options = ReadOptions {
+        preserve_unknown: true,
+        ..Default::default()
+    };
+
+    let mut palettes = BTreeMap::<u8, Palette>::new();
+    let mut palette_id = 0;
+    let mut pending_object = None::<(u16, u16, Vec<u8>)>;
+
     eprintln!("Iterating through PGS segments...");
 
     //
@@ -58,7 +81,7 @@ fn main() {
 
     loop {
 
-        match input.read_segment() {
+        match input.read_segment_with_options(&options) {
             Ok(segment) => {
                 match segment {
                     Segment::PresentationComposition(pcs) => {
@@ -66,6 +89,12 @@ fn main() {
                             "presentation_composition_segment({})",
                             ts_to_timestamp(pcs.pts),
                         );
+                        println!(
+                            "  frame_rate = 0x{:02X} ({})",
+                            pcs.frame_rate,
+                            FrameRate::from_byte(pcs.frame_rate)
+                                .map_or("unknown".to_string(), |rate| rate.to_string()),
+                        );
                         println!("  composition_number = {}", pcs.composition_number);
                         println!("  composition_state = {}", match pcs.composition_state {
                             CompositionState::EpochStart => "EPOCH_START",
@@ -78,6 +107,10 @@ fn main() {
                             println!("  palette_update_flags = 0x00")
                         }
                         println!("  palette_id = {}", pcs.palette_id);
+                        palette_id = pcs.palette_id;
+                        if pcs.composition_state == CompositionState::EpochStart {
+                            palettes.clear();
+                        }
                         for comp_obj in pcs.composition_objects.iter() {
                             println!("  window_information");
                             println!("    object_id = {}", comp_obj.object_id);
@@ -87,14 +120,14 @@ fn main() {
                             println!("    y = {}", comp_obj.y);
                             match &comp_obj.crop {
                                 Some(crop) => {
-                                    println!("  cropped = {}", true);
+                                    println!("  cropped = true");
                                     println!("    cropped_x = {}", crop.x);
                                     println!("    cropped_y = {}", crop.y);
                                     println!("    cropped_width = {}", crop.width);
                                     println!("    cropped_height = {}", crop.height);
                                 }
                                 None => {
-                                    println!("  cropped = {}", false);
+                                    println!("  cropped = false");
                                 }
                             }
                         }
@@ -117,6 +150,12 @@ fn main() {
                         println!("  object_width = {}", sods.width);
                         println!("  object_height = {}", sods.height);
                         println!("  object_data = [{}]", sods.data.len());
+                        if hex_objects {
+                            print_hexdump(&sods.data);
+                        }
+                        if ascii_objects {
+                            print_ascii(sods.width, sods.height, &sods.data, &palettes, palette_id);
+                        }
                     }
                     Segment::InitialObjectDefinition(iods) => {
                         println!("initial_object_definition_segment({})", ts_to_timestamp(iods.pts));
@@ -126,29 +165,65 @@ fn main() {
                         println!("  object_width = {}", iods.width);
                         println!("  object_height = {}", iods.height);
                         println!("  object_data = [{}]", iods.data.len());
+                        if hex_objects {
+                            print_hexdump(&iods.data);
+                        }
+                        pending_object = Some((iods.width, iods.height, iods.data));
                     }
                     Segment::MiddleObjectDefinition(mods) => {
                         println!("middle_object_definition_segment({})", ts_to_timestamp(mods.pts));
                         println!("  object_id = {}", mods.id);
                         println!("  object_version = {}", mods.version);
                         println!("  object_data = [{}]", mods.data.len());
+                        if hex_objects {
+                            print_hexdump(&mods.data);
+                        }
+                        if let Some((_, _, data)) = &mut pending_object {
+                            data.extend_from_slice(&mods.data);
+                        }
                     }
                     Segment::FinalObjectDefinition(fods) => {
                         println!("final_object_definition_segment({})", ts_to_timestamp(fods.pts));
                         println!("  object_id = {}", fods.id);
                         println!("  object_version = {}", fods.version);
                         println!("  object_data = [{}]", fods.data.len());
+                        if hex_objects {
+                            print_hexdump(&fods.data);
+                        }
+                        if let Some((width, height, mut data)) = pending_object.take() {
+                            data.extend_from_slice(&fods.data);
+                            if ascii_objects {
+                                print_ascii(width, height, &data, &palettes, palette_id);
+                            }
+                        }
                     }
                     Segment::PaletteDefinition(pds) => {
                         println!("palette_definition_segment({})", ts_to_timestamp(pds.pts));
                         println!("  palette_id = {}", pds.id);
                         println!("  palette_version = {}", pds.version);
                         println!("  pallet_entries = [{}]", pds.entries.len());
+                        let palette = palettes.entry(pds.id).or_default();
+                        for entry in &pds.entries {
+                            palette.entries.insert(
+                                entry.id,
+                                PaletteEntry {
+                                    y: entry.y,
+                                    cr: entry.cr,
+                                    cb: entry.cb,
+                                    alpha: entry.alpha,
+                                },
+                            );
+                        }
                     }
                     Segment::End(es) => {
                         println!("end_segment({})", ts_to_timestamp(es.pts));
                         println!();
                     }
+                    Segment::Unknown(us) => {
+                        println!("unknown_segment({})", ts_to_timestamp(us.pts));
+                        println!("  segment_type = 0x{:02X}", us.kind);
+                        println!("  segment_data = [{}]", us.data.len());
+                    }
                 }
             }
             Err(err) => {
@@ -168,3 +243,44 @@ fn main() {
         };
     }
 }
+
+fn print_hexdump(data: &[u8]) {
+
+    for (index, chunk) in data.chunks(16).enumerate() {
+
+        let mut hex = String::with_capacity(49);
+
+        for (offset, byte) in chunk.iter().enumerate() {
+            if offset == 8 {
+                hex.push(' ');
+            }
+            hex.push_str(&format!("{:02x} ", byte));
+        }
+
+        let ascii = chunk.iter()
+            .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+            .collect::<String>();
+
+        println!("    {:08x}  {:<49} |{}|", index * 16, hex, ascii);
+    }
+}
+
+fn print_ascii(
+    width: u16,
+    height: u16,
+    data: &[u8],
+    palettes: &BTreeMap<u8, Palette>,
+    palette_id: u8,
+) {
+
+    let object = Object {
+        width,
+        height,
+        lines: decompress_lenient(data).0,
+    };
+    let palette = palettes.get(&palette_id).cloned().unwrap_or_default();
+
+    for line in object.to_ascii(&palette).lines() {
+        println!("    |{}|", line);
+    }
+}
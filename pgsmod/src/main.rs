@@ -11,26 +11,28 @@
 #[cfg(test)]
 mod tests;
 
-mod rgb;
+mod range;
 
 use pgs::{
+    timestamp_to_ts,
     ts_to_timestamp,
+    epoch::{epochs, retime},
     displayset::{
+        display_sets,
         Object,
-        ReadDisplaySetExt,
-        ReadError as DisplaySetReadError,
         WriteDisplaySetExt,
     },
+    rgb::ColorMatrix,
     segment::{
         CompositionState,
-        ReadError as SegmentReadError,
+        FrameRate,
     },
 };
-use rgb::{rgb_pixel, ycbcr_pixel, YcbcrPixel};
+use range::TimeRange;
 use std::{
     collections::HashMap,
     fs::File,
-    io::{stdin, stdout, BufReader, BufWriter, ErrorKind, Read, Write},
+    io::{stdin, stdout, BufReader, BufWriter, Read, Write},
 };
 use clap::{app_from_crate, crate_authors, crate_description, crate_name, crate_version, Arg};
 
@@ -148,6 +150,140 @@ fn main() {
                 Ok(())
             })
         )
+        .arg(Arg::with_name("color-matrix")
+            .long("color-matrix")
+            .value_name("MATRIX")
+            .help("Color matrix used when scaling luminosity: bt601, bt709, or bt2020")
+            .takes_value(true)
+            .required(false)
+            .default_value("bt709")
+            .validator(|value| {
+                if parse_color_matrix(&value).is_some() {
+                    Ok(())
+                } else {
+                    Err("must be bt601, bt709, or bt2020".to_string())
+                }
+            })
+        )
+        .arg(Arg::with_name("brightness")
+            .long("brightness")
+            .value_name("LEVEL")
+            .help("Brightens or darkens the subtitles by adding the specified level minus 1.0 to \
+                each RGB channel; 1.0 leaves it unchanged")
+            .takes_value(true)
+            .required(false)
+            .validator(validate_factor)
+        )
+        .arg(Arg::with_name("contrast")
+            .long("contrast")
+            .value_name("FACTOR")
+            .help("Adjusts the contrast of the subtitles; 1.0 leaves it unchanged")
+            .takes_value(true)
+            .required(false)
+            .validator(validate_factor)
+        )
+        .arg(Arg::with_name("saturation")
+            .long("saturation")
+            .value_name("FACTOR")
+            .help("Adjusts the saturation of the subtitles; 1.0 leaves it unchanged")
+            .takes_value(true)
+            .required(false)
+            .validator(validate_factor)
+        )
+        .arg(Arg::with_name("safe-area")
+            .long("safe-area")
+            .value_name("PERCENT")
+            .help("Moves the subtitles inward to fit the specified safe-area percentage")
+            .takes_value(true)
+            .required(false)
+            .validator(|value| {
+                let percent = value.parse::<f64>();
+                if percent.is_err() {
+                    return Err("Must be a floating point value".to_string())
+                }
+                let percent_value = percent.unwrap();
+                if !(percent_value > 0.0 && percent_value <= 100.0) {
+                    return Err("Must be greater than 0 and no more than 100".to_string())
+                }
+                Ok(())
+            })
+        )
+        .arg(Arg::with_name("center-h")
+            .long("center-h")
+            .help("Centers the subtitles horizontally on the screen")
+            .takes_value(false)
+            .required(false)
+        )
+        .arg(Arg::with_name("center-v")
+            .long("center-v")
+            .help("Centers the subtitles vertically on the screen")
+            .takes_value(false)
+            .required(false)
+        )
+        .arg(Arg::with_name("retime")
+            .long("retime")
+            .value_name("SRC:DST")
+            .help("Re-times the subtitles from one framerate to another, such as 23.976:25")
+            .takes_value(true)
+            .required(false)
+            .validator(|value| {
+                if parse_retime(&value).is_some() {
+                    Ok(())
+                } else {
+                    Err("Must be two framerates in the form SRC:DST".to_string())
+                }
+            })
+        )
+        .arg(Arg::with_name("no-crop")
+            .long("no-crop")
+            .help("Bakes object cropping into the objects themselves and then strips it")
+            .takes_value(false)
+            .required(false)
+        )
+        .arg(Arg::with_name("clamp-legal")
+            .long("clamp-legal")
+            .help("Clamps palette colors to the legal studio range after all other color changes")
+            .takes_value(false)
+            .required(false)
+        )
+        .arg(Arg::with_name("start")
+            .long("start")
+            .short("s")
+            .value_name("TIMESTAMP")
+            .help("Discards display sets before this time (HH:MM:SS.mmm)")
+            .takes_value(true)
+            .required(false)
+            .validator(|value| {
+                if timestamp_to_ts(&value).is_some() {
+                    Ok(())
+                } else {
+                    Err("must be a timestamp in the form HH:MM:SS.mmm".to_string())
+                }
+            })
+        )
+        .arg(Arg::with_name("end")
+            .long("end")
+            .short("e")
+            .value_name("TIMESTAMP")
+            .help("Discards display sets at or after this time (HH:MM:SS.mmm)")
+            .takes_value(true)
+            .required(false)
+            .validator(|value| {
+                if timestamp_to_ts(&value).is_some() {
+                    Ok(())
+                } else {
+                    Err("must be a timestamp in the form HH:MM:SS.mmm".to_string())
+                }
+            })
+        )
+        .arg(Arg::with_name("rebase")
+            .long("rebase")
+            .short("r")
+            .help("Shifts timestamps so that the start time becomes zero")
+            .takes_value(false)
+            .required(false)
+            .requires("start")
+        )
         .arg(Arg::with_name("input")
             .index(1)
             .value_name("INPUT-FILE")
@@ -168,30 +304,28 @@ fn main() {
             Licensed under the Mozilla Public License 2.0\n\
             <{}>", env!("CARGO_PKG_REPOSITORY")).as_str())
         .get_matches();
-    let crop_width = match matches.value_of("crop-width") {
-        Some(cw) => Some(cw.parse::<u16>().unwrap()),
-        None => None
-    };
-    let crop_height = match matches.value_of("crop-height") {
-        Some(ch) => Some(ch.parse::<u16>().unwrap()),
-        None => None
-    };
-    let crop_x = match matches.value_of("crop-x") {
-        Some(cx) => Some(cx.parse::<u16>().unwrap()),
-        None => None
-    };
-    let crop_y = match matches.value_of("crop-y") {
-        Some(cy) => Some(cy.parse::<u16>().unwrap()),
-        None => None
-    };
+    let crop_width = matches.value_of("crop-width").map(|cw| cw.parse::<u16>().unwrap());
+    let crop_height = matches.value_of("crop-height").map(|ch| ch.parse::<u16>().unwrap());
+    let crop_x = matches.value_of("crop-x").map(|cx| cx.parse::<u16>().unwrap());
+    let crop_y = matches.value_of("crop-y").map(|cy| cy.parse::<u16>().unwrap());
     let margin = matches.value_of("margin").unwrap().parse::<u16>().unwrap();
-    let lum_scale = match matches.value_of("lum-scale") {
-        Some(factor) => Some(factor.parse::<f64>().unwrap()),
-        None => None,
-    };
+    let lum_scale = matches.value_of("lum-scale").map(|factor| factor.parse::<f64>().unwrap());
+    let color_matrix = parse_color_matrix(matches.value_of("color-matrix").unwrap()).unwrap();
+    let brightness = matches.value_of("brightness").map(|level| level.parse::<f64>().unwrap());
+    let contrast = matches.value_of("contrast").map(|factor| factor.parse::<f64>().unwrap());
+    let saturation = matches.value_of("saturation").map(|factor| factor.parse::<f64>().unwrap());
+    let safe_area = matches.value_of("safe-area").map(|percent| percent.parse::<f64>().unwrap());
+    let center_h = matches.is_present("center-h");
+    let center_v = matches.is_present("center-v");
+    let retime_ratio = matches.value_of("retime").map(|value| parse_retime(value).unwrap());
+    let start = matches.value_of("start").map(|ts| timestamp_to_ts(ts).unwrap());
+    let end = matches.value_of("end").map(|ts| timestamp_to_ts(ts).unwrap());
+    let rebase = matches.is_present("rebase");
+    let no_crop = matches.is_present("no-crop");
+    let clamp_legal = matches.is_present("clamp-legal");
     let input_value = matches.value_of("input").unwrap();
     let (mut stdin_read, mut file_read);
-    let mut input = BufReader::<&mut dyn Read>::new(
+    let input = BufReader::<&mut dyn Read>::new(
         if input_value == "-" {
             stdin_read = stdin();
             &mut stdin_read
@@ -216,167 +350,208 @@ fn main() {
     let mut screen_size = None;
     let mut width_crop = None;
     let mut height_crop = None;
+    let mut time_range = TimeRange::new(start, end, rebase);
 
-    loop {
+    let display_sets = display_sets(input)
+        .map(|display_set| match display_set {
+            Ok(display_set) => display_set,
+            Err(err) => panic!("Could not read display set: {}", err),
+        })
+        .flat_map(|display_set| time_range.filter(display_set));
+
+    for mut epoch in epochs(display_sets) {
 
         let mut objects = HashMap::<u16, Object>::new();
 
-        match input.read_display_set() {
-            Ok(mut display_set) => {
-
-                //
-                // VALIDATE/SET SCREEN SIZE
-                //
-
-                let ds_size = Size {
-                    width: display_set.width,
-                    height: display_set.height,
-                };
-
-                match screen_size {
-                    Some(ss) => {
-                        if ds_size != ss {
-                            panic!(
-                                "Inconsistent screen size encountered: {}x{}",
-                                ds_size.width,
-                                ds_size.height,
-                            )
-                        }
-                    }
-                    None => {
-                        eprintln!("Existing resolution: {}x{}", ds_size.width, ds_size.height);
-                        screen_size = Some(ds_size);
-                        width_crop = to_crop(ds_size.width, crop_width, crop_x);
-                        height_crop = to_crop(ds_size.height, crop_height, crop_y);
+        //
+        // BAKE OBJECT CROPPING
+        //
+
+        if no_crop {
+            epoch.bake_crops();
+        }
+
+        for display_set in epoch.display_sets.iter_mut() {
+
+            //
+            // VALIDATE/SET SCREEN SIZE
+            //
+
+            let ds_size = Size {
+                width: display_set.width,
+                height: display_set.height,
+            };
+
+            match screen_size {
+                Some(ss) => {
+                    if ds_size != ss {
+                        panic!(
+                            "Inconsistent screen size encountered: {}x{}",
+                            ds_size.width,
+                            ds_size.height,
+                        )
                     }
                 }
+                None => {
+                    eprintln!("Existing resolution: {}x{}", ds_size.width, ds_size.height);
+                    screen_size = Some(ds_size);
+                    width_crop = to_crop(ds_size.width, crop_width, crop_x);
+                    height_crop = to_crop(ds_size.height, crop_height, crop_y);
+                }
+            }
 
-                //
-                // UPDATE OBJECTS & WINDOWS
-                //
+            //
+            // STRIP OBJECT CROPPING
+            //
 
-                if display_set.composition.state == CompositionState::EpochStart
-                    || display_set.composition.state == CompositionState::AcquisitionPoint {
-                    objects.clear();
-                }
+            if no_crop {
+                display_set.remove_crops();
+            }
 
-                for (vid, object) in &display_set.objects {
-                    objects.insert(vid.id, object.clone());
-                }
+            //
+            // UPDATE OBJECTS & WINDOWS
+            //
+
+            if display_set.composition.state == CompositionState::EpochStart
+                || display_set.composition.state == CompositionState::AcquisitionPoint {
+                objects.clear();
+            }
+
+            for (vid, object) in &display_set.objects {
+                objects.insert(vid.id, object.clone());
+            }
 
-                //
-                // UDPATE SCREEN DIMENSIONS
-                //
+            //
+            // UDPATE SCREEN DIMENSIONS
+            //
 
-                match &width_crop {
-                    Some(wc) => {
-                        display_set.width = wc.size;
-                        for window in display_set.windows.values_mut() {
-                            window.x = new_item_offset(
-                                wc.size, wc.offset, window.width, window.x, margin
+            if let Some(wc) = &width_crop {
+                display_set.width = wc.size;
+                for window in display_set.windows.values_mut() {
+                    window.x = new_item_offset(
+                        wc.size, wc.offset, window.width, window.x, margin
+                    );
+                }
+                for (cid, co) in &mut display_set.composition.objects {
+                    match objects.get(&cid.object_id) {
+                        Some(object) => {
+                            co.x = new_item_offset(
+                                wc.size, wc.offset, object.width, co.x, margin
                             );
                         }
-                        for (cid, co) in &mut display_set.composition.objects {
-                            match objects.get(&cid.object_id) {
-                                Some(object) => {
-                                    co.x = new_item_offset(
-                                        wc.size, wc.offset, object.width, co.x, margin
-                                    );
-                                }
-                                None =>
-                                {
-                                    eprintln!(
-                                        "WARNING: {} - Referenced object not found.",
-                                        ts_to_timestamp(display_set.pts),
-                                    )
-                                }
-                            }
+                        None =>
+                        {
+                            eprintln!(
+                                "WARNING: {} - Referenced object not found.",
+                                ts_to_timestamp(display_set.pts),
+                            )
                         }
                     }
-                    None => {
-                    }
                 }
+            }
 
-                match &height_crop {
-                    Some(hc) => {
-                        display_set.height = hc.size;
-                        for window in display_set.windows.values_mut() {
-                            window.y = new_item_offset(
-                                hc.size, hc.offset, window.height, window.y, margin
+            if let Some(hc) = &height_crop {
+                display_set.height = hc.size;
+                for window in display_set.windows.values_mut() {
+                    window.y = new_item_offset(
+                        hc.size, hc.offset, window.height, window.y, margin
+                    );
+                }
+                for (cid, co) in &mut display_set.composition.objects {
+                    match objects.get(&cid.object_id) {
+                        Some(object) => {
+                            co.y = new_item_offset(
+                                hc.size, hc.offset, object.height, co.y, margin
                             );
                         }
-                        for (cid, co) in &mut display_set.composition.objects {
-                            match objects.get(&cid.object_id) {
-                                Some(object) => {
-                                    co.y = new_item_offset(
-                                        hc.size, hc.offset, object.height, co.y, margin
-                                    );
-                                }
-                                None =>
-                                {
-                                    eprintln!(
-                                        "WARNING: {} - Referenced object not found.",
-                                        ts_to_timestamp(display_set.pts),
-                                    )
-                                }
-                            }
+                        None =>
+                        {
+                            eprintln!(
+                                "WARNING: {} - Referenced object not found.",
+                                ts_to_timestamp(display_set.pts),
+                            )
                         }
                     }
-                    None => {
-                    }
                 }
+            }
 
-                //
-                // LUMINOSITY SCALING
-                //
-
-                match lum_scale {
-                    Some(factor) => {
-                        for palette in display_set.palettes.values_mut() {
-                            for entry in palette.entries.values_mut() {
-                                let mut rgb = rgb_pixel(
-                                    YcbcrPixel { y: entry.y, cb: entry.cb, cr: entry.cr }
-                                );
-                                rgb.red *= factor;
-                                rgb.green *= factor;
-                                rgb.blue *= factor;
-                                let ycbcr = ycbcr_pixel(rgb);
-                                entry.y = ycbcr.y;
-                                entry.cb = ycbcr.cb;
-                                entry.cr = ycbcr.cr;
-                            }
-                        }
-                    }
-                    None => {
-                    }
-                }
+        }
+
+        //
+        // CENTERING
+        //
+
+        let epoch_pts = epoch.display_sets.first().map_or(0, |display_set| display_set.pts);
+
+        if center_h {
+            if let Err(err) = epoch.recenter_horizontal() {
+                eprintln!("WARNING: {} - {}.", ts_to_timestamp(epoch_pts), err)
+            }
+        }
+
+        if center_v {
+            if let Err(err) = epoch.recenter_vertical() {
+                eprintln!("WARNING: {} - {}.", ts_to_timestamp(epoch_pts), err)
+            }
+        }
+
+        for mut display_set in epoch.display_sets {
+
+            //
+            // SAFE AREA
+            //
+
+            if let Some(percent) = safe_area {
+                display_set.apply_safe_area(percent);
+            }
+
+            //
+            // LUMINOSITY SCALING
+            //
+
+            if let Some(factor) = lum_scale {
+                display_set.apply_color_lut_with_matrix(
+                    &|red, green, blue| (red * factor, green * factor, blue * factor),
+                    color_matrix,
+                );
+            }
+
+            //
+            // BRIGHTNESS, CONTRAST, AND SATURATION
+            //
 
-                if let Err(err) = output.write_display_set(display_set) {
-                    panic!("Could not write display set to output stream: {:?}", err)
+            if brightness.is_some() || contrast.is_some() || saturation.is_some() {
+                for palette in display_set.palettes.values_mut() {
+                    palette.adjust(
+                        brightness.unwrap_or(1.0),
+                        contrast.unwrap_or(1.0),
+                        saturation.unwrap_or(1.0),
+                    );
                 }
             }
-            Err(err) => {
-                match err {
-                    DisplaySetReadError::ReadError { source } => {
-                        match source {
-                            SegmentReadError::IoError { source } => {
-                                if source.kind() != ErrorKind::UnexpectedEof {
-                                    panic!("Could not read segment due to IO error: {}", source)
-                                }
-                            }
-                            _ => {
-                                panic!(
-                                    "Could not read display set due to segment error: {}",
-                                    source,
-                                )
-                            }
-                        }
-                    }
-                    _ => panic!("Could not read display set due to bitstream error: {}", err)
+
+            //
+            // LEGAL RANGE CLAMPING
+            //
+
+            if clamp_legal {
+                for palette in display_set.palettes.values_mut() {
+                    palette.clamp_studio_range(true);
                 }
-                break
             }
-        };
+
+            //
+            // RE-TIMING
+            //
+
+            if let Some((numerator, denominator)) = retime_ratio {
+                retime(std::slice::from_mut(&mut display_set), numerator, denominator);
+            }
+
+            if let Err(err) = output.write_display_set(display_set) {
+                panic!("Could not write display set to output stream: {:?}", err)
+            }
+        }
     }
 }
 
@@ -427,3 +602,46 @@ fn new_item_offset(
         }
     }
 }
+
+fn validate_factor(value: String) -> Result<(), String> {
+    match value.parse::<f64>() {
+        Ok(factor) if factor.is_finite() && factor >= 0.0 => Ok(()),
+        Ok(_) => Err("Must be a finite, non-negative number".to_string()),
+        Err(_) => Err("Must be a floating point value".to_string()),
+    }
+}
+
+fn parse_retime(value: &str) -> Option<(u32, u32)> {
+
+    let (src, dst) = value.split_once(':')?;
+    let (src_num, src_den) = parse_frame_rate(src)?;
+    let (dst_num, dst_den) = parse_frame_rate(dst)?;
+
+    Some((src_num.checked_mul(dst_den)?, src_den.checked_mul(dst_num)?))
+}
+
+fn parse_color_matrix(value: &str) -> Option<ColorMatrix> {
+    match value {
+        "bt601" => Some(ColorMatrix::Bt601),
+        "bt709" => Some(ColorMatrix::Bt709),
+        "bt2020" => Some(ColorMatrix::Bt2020),
+        _ => None,
+    }
+}
+
+fn parse_frame_rate(value: &str) -> Option<(u32, u32)> {
+
+    let frame_rates = [
+        FrameRate::Fps23_976,
+        FrameRate::Fps24,
+        FrameRate::Fps25,
+        FrameRate::Fps29_97,
+        FrameRate::Fps50,
+        FrameRate::Fps59_94,
+    ];
+
+    match frame_rates.iter().find(|frame_rate| frame_rate.to_string() == value) {
+        Some(frame_rate) => Some(frame_rate.ratio()),
+        None => value.parse::<u32>().ok().filter(|&fps| fps > 0).map(|fps| (fps, 1)),
+    }
+}
@@ -0,0 +1,139 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a
+ * copy of the MPL was not distributed with this file, You can obtain one at
+ * https://mozilla.org/MPL/2.0/.
+ *
+ * SPDX-License-Identifier: MPL-2.0
+ */
+
+#[cfg(test)]
+mod tests;
+
+use pgs::{
+    displayset::{Composition, DisplaySet},
+    epoch::EpochContext,
+    segment::CompositionState,
+};
+
+pub struct TimeRange {
+    start: u32,
+    end: Option<u32>,
+    rebase: bool,
+    context: EpochContext,
+    previous: Option<DisplaySet>,
+    started: bool,
+    ended: bool,
+}
+
+impl TimeRange {
+
+    pub fn new(start: Option<u32>, end: Option<u32>, rebase: bool) -> Self {
+        TimeRange {
+            start: start.unwrap_or(0),
+            end,
+            rebase,
+            context: EpochContext::default(),
+            previous: None,
+            started: false,
+            ended: false,
+        }
+    }
+
+    /// Accepts the next display set from the input stream, returning the display sets that
+    /// should be emitted in its place.
+    pub fn filter(&mut self, display_set: DisplaySet) -> Vec<DisplaySet> {
+
+        let mut output = Vec::<DisplaySet>::new();
+
+        if self.ended {
+            return output
+        }
+
+        match self.end {
+            Some(end) if display_set.pts >= end => {
+                // Make sure whatever is on the screen gets taken down at the end of the range.
+                self.ended = true;
+                if self.started && self.showing() {
+                    if let Some(clear) = self.clear(end) {
+                        output.push(clear);
+                    }
+                }
+            }
+            _ => {
+                if display_set.pts < self.start {
+                    self.track(&display_set);
+                } else {
+                    if !self.started {
+                        // Starting mid-epoch requires the state of the epoch as of the start of
+                        // the range to be emitted first.
+                        self.started = true;
+                        if display_set.composition.state != CompositionState::EpochStart {
+                            if let Some(previous) = &self.previous {
+                                output.push(self.context.epoch_start(previous, self.start));
+                            }
+                        }
+                    }
+                    self.track(&display_set);
+                    output.push(display_set);
+                }
+            }
+        }
+
+        if self.rebase {
+            for display_set in output.iter_mut() {
+                display_set.pts -= self.start;
+                display_set.dts = display_set.dts.saturating_sub(self.start);
+            }
+        }
+
+        output
+    }
+
+    fn track(&mut self, display_set: &DisplaySet) {
+
+        // Only the properties of the display set itself are retained; its definitions are
+        // incorporated into the epoch's context instead.
+        self.context.update(display_set);
+        self.previous = Some(
+            DisplaySet {
+                pts: display_set.pts,
+                dts: display_set.dts,
+                width: display_set.width,
+                height: display_set.height,
+                frame_rate: display_set.frame_rate,
+                palette_id: display_set.palette_id,
+                composition: Composition {
+                    number: display_set.composition.number,
+                    state: display_set.composition.state,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+        );
+    }
+
+    fn showing(&self) -> bool {
+        !self.context.composition.objects.is_empty()
+    }
+
+    fn clear(&self, pts: u32) -> Option<DisplaySet> {
+        self.previous.as_ref().map(|previous|
+            DisplaySet {
+                pts,
+                dts: 0,
+                width: previous.width,
+                height: previous.height,
+                frame_rate: previous.frame_rate,
+                windows: self.context.windows.clone(),
+                composition: Composition {
+                    number: previous.composition.number.wrapping_add(1),
+                    state: CompositionState::Normal,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+        )
+    }
+}
@@ -0,0 +1,177 @@
+/*
+ * Copyright 2023 William Swartzendruber
+ *
+ * To the extent possible under law, the person who associated CC0 with this file has waived all
+ * copyright and related or neighboring rights to this file.
+ *
+ * You should have received a copy of the CC0 legalcode along with this work. If not, see
+ * <http://creativecommons.org/publicdomain/zero/1.0/>.
+ *
+ * SPDX-License-Identifier: CC0-1.0
+ */
+
+use super::*;
+use pgs::displayset::{Cid, CompositionObject, Object, Palette, Vid, Window};
+
+fn display_set(pts: u32, state: CompositionState, showing: bool) -> DisplaySet {
+
+    let mut display_set = DisplaySet {
+        pts,
+        width: 1920,
+        height: 1080,
+        composition: Composition {
+            state,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    display_set.windows.insert(0, Window { x: 0, y: 0, width: 100, height: 100 });
+
+    if showing {
+        display_set.composition.objects.insert(
+            Cid {
+                object_id: 0,
+                window_id: 0,
+            },
+            CompositionObject::default(),
+        );
+    }
+
+    display_set
+}
+
+fn filter_all(range: &mut TimeRange, sets: Vec<DisplaySet>) -> Vec<(u32, CompositionState)> {
+    sets.into_iter()
+        .flat_map(|ds| range.filter(ds))
+        .map(|ds| (ds.pts, ds.composition.state))
+        .collect()
+}
+
+#[test]
+fn test_range_starting_mid_epoch() {
+
+    let mut range = TimeRange::new(Some(1_500), Some(3_500), false);
+    let output = filter_all(
+        &mut range,
+        vec![
+            display_set(1_000, CompositionState::EpochStart, true),
+            display_set(2_000, CompositionState::Normal, false),
+            display_set(3_000, CompositionState::EpochStart, true),
+            display_set(4_000, CompositionState::Normal, false),
+        ],
+    );
+
+    assert_eq!(
+        output,
+        vec![
+            (1_500, CompositionState::EpochStart),
+            (2_000, CompositionState::Normal),
+            (3_000, CompositionState::EpochStart),
+            (3_500, CompositionState::Normal),
+        ],
+    );
+}
+
+#[test]
+fn test_range_starting_on_epoch() {
+
+    let mut range = TimeRange::new(Some(2_500), None, true);
+    let output = filter_all(
+        &mut range,
+        vec![
+            display_set(1_000, CompositionState::EpochStart, true),
+            display_set(2_000, CompositionState::Normal, false),
+            display_set(3_000, CompositionState::EpochStart, true),
+            display_set(4_000, CompositionState::Normal, false),
+        ],
+    );
+
+    assert_eq!(
+        output,
+        vec![
+            (500, CompositionState::EpochStart),
+            (1_500, CompositionState::Normal),
+        ],
+    );
+}
+
+#[test]
+fn test_range_ending_while_cleared() {
+
+    let mut range = TimeRange::new(None, Some(2_500), false);
+    let output = filter_all(
+        &mut range,
+        vec![
+            display_set(1_000, CompositionState::EpochStart, true),
+            display_set(2_000, CompositionState::Normal, false),
+            display_set(3_000, CompositionState::EpochStart, true),
+        ],
+    );
+
+    assert_eq!(
+        output,
+        vec![
+            (1_000, CompositionState::EpochStart),
+            (2_000, CompositionState::Normal),
+        ],
+    );
+}
+
+#[test]
+fn test_range_starting_after_caption_change() {
+
+    let caption = |pts, state, object_id: u16| {
+        let mut ds = display_set(pts, state, false);
+        ds.dts = pts - 100;
+        ds.objects.insert(
+            Vid {
+                id: object_id,
+                version: 0,
+            },
+            Object {
+                width: 1,
+                height: 1,
+                lines: vec![vec![object_id as u8]],
+            },
+        );
+        ds.composition.objects.insert(
+            Cid {
+                object_id,
+                window_id: 0,
+            },
+            CompositionObject::default(),
+        );
+        ds
+    };
+    let mut first = caption(1_000, CompositionState::EpochStart, 0);
+    let mut palette_switch = caption(3_000, CompositionState::Normal, 0);
+
+    first.palettes.insert(Vid { id: 0, version: 0 }, Palette::default());
+    first.palettes.insert(Vid { id: 0, version: 1 }, Palette::default());
+    first.palettes.insert(Vid { id: 1, version: 0 }, Palette::default());
+    palette_switch.objects.clear();
+    palette_switch.palette_id = 1;
+
+    let mut range = TimeRange::new(Some(2_500), None, false);
+    let output = vec![
+        first,
+        caption(2_000, CompositionState::Normal, 1),
+        palette_switch,
+    ].into_iter().flat_map(|ds| range.filter(ds)).collect::<Vec<DisplaySet>>();
+
+    assert_eq!(output.len(), 2);
+    assert_eq!(output[0].pts, 2_500);
+    assert!(output[0].dts <= output[0].pts);
+    assert_eq!(output[0].composition.state, CompositionState::EpochStart);
+    assert_eq!(
+        output[0].composition.objects.keys().collect::<Vec<_>>(),
+        vec![&Cid { object_id: 1, window_id: 0 }],
+    );
+    assert_eq!(output[0].objects.keys().map(|vid| vid.id).collect::<Vec<_>>(), vec![0, 1]);
+    assert_eq!(
+        output[0].palettes.keys().cloned().collect::<Vec<_>>(),
+        vec![Vid { id: 0, version: 1 }, Vid { id: 1, version: 0 }],
+    );
+    assert!(output[0].windows.contains_key(&0));
+}
@@ -26,3 +26,30 @@ fn test_new_item_offset_too_high() {
 fn test_new_item_offset_too_low() {
     assert_eq!(new_item_offset(800, 140, 88, 852, 40), 672);
 }
+
+#[test]
+fn test_parse_retime() {
+    assert_eq!(parse_retime("23.976:25"), Some((24_000, 25_025)));
+    assert_eq!(parse_retime("25:23.976"), Some((25_025, 24_000)));
+    assert_eq!(parse_retime("30:60"), Some((30, 60)));
+    assert_eq!(parse_retime("23.976"), None);
+    assert_eq!(parse_retime("0:25"), None);
+    assert_eq!(parse_retime("23.98:25"), None);
+}
+
+#[test]
+fn test_parse_color_matrix() {
+    assert_eq!(parse_color_matrix("bt601"), Some(ColorMatrix::Bt601));
+    assert_eq!(parse_color_matrix("bt709"), Some(ColorMatrix::Bt709));
+    assert_eq!(parse_color_matrix("bt2020"), Some(ColorMatrix::Bt2020));
+    assert_eq!(parse_color_matrix("BT.709"), None);
+}
+
+#[test]
+fn test_validate_factor() {
+    assert!(validate_factor("1.5".to_string()).is_ok());
+    assert!(validate_factor("0".to_string()).is_ok());
+    assert!(validate_factor("-0.5".to_string()).is_err());
+    assert!(validate_factor("inf".to_string()).is_err());
+    assert!(validate_factor("bright".to_string()).is_err());
+}
@@ -9,17 +9,14 @@
  */
 
 use pgs::{
-    displayset::{
-        ReadDisplaySetExt,
-        ReadError as DisplaySetReadError,
-    },
-    segment::{
-        ReadError as SegmentReadError,
-    },
+    ts_to_timestamp,
+    displayset::display_sets,
+    segment::validate_framing,
+    stats::stream_stats,
 };
 use std::{
     fs::File,
-    io::{stdin, BufReader, ErrorKind, Read},
+    io::{stdin, BufReader, Read},
 };
 use clap::{app_from_crate, crate_authors, crate_description, crate_name, crate_version, Arg};
 
@@ -32,6 +29,14 @@ fn main() {
             .help("Input PGS file; use - for STDIN")
             .required(true)
         )
+        .arg(Arg::with_name("stats")
+            .long("stats")
+            .help("Prints an overview of the stream once it has been read")
+        )
+        .arg(Arg::with_name("check-framing")
+            .long("check-framing")
+            .help("Only checks that the stream consists of whole segments laid end-to-end")
+        )
         .after_help(format!("This utility will test PGS subtitles.\n\n\
             Copyright © 2021 William Swartzendruber\n\
             Licensed under the Mozilla Public License 2.0\n\
@@ -50,38 +55,41 @@ fn main() {
         }
     );
 
+    if matches.is_present("check-framing") {
+        eprintln!("Checking PGS segment framing...");
+        match validate_framing(&mut input) {
+            Ok(count) => println!("Framing is intact across {} segments.", count),
+            Err(err) => panic!("Could not validate segment framing: {}", err),
+        }
+        return
+    }
+
     eprintln!("Iterating through PGS display sets...");
 
+    if matches.is_present("stats") {
+        match stream_stats(&mut input) {
+            Ok(stats) => println!("{}", stats),
+            Err(err) => panic!("Could not read display set: {}", err),
+        }
+        return
+    }
+
     //
     // READ
     //
 
-    loop {
+    for display_set in display_sets(input) {
 
-        match input.read_display_set() {
-            Ok(_) => {
-            }
-            Err(err) => {
-                match err {
-                    DisplaySetReadError::ReadError { source } => {
-                        match source {
-                            SegmentReadError::IoError { source } => {
-                                if source.kind() != ErrorKind::UnexpectedEof {
-                                    panic!("Could not read segment due to IO error: {}", source)
-                                }
-                            }
-                            _ => {
-                                panic!(
-                                    "Could not read display set due to segment error: {}",
-                                    source,
-                                )
-                            }
-                        }
-                    }
-                    _ => panic!("Could not read display set due to bitstream error: {}", err)
-                }
-                break
-            }
+        let display_set = match display_set {
+            Ok(display_set) => display_set,
+            Err(err) => panic!("Could not read display set: {}", err),
         };
+
+        if display_set.needs_windows() {
+            eprintln!(
+                "WARNING: {} - Display set does not define the windows of its epoch.",
+                ts_to_timestamp(display_set.pts),
+            )
+        }
     }
 }